@@ -31,7 +31,8 @@ fn feature_conflicts() {
 
     // Database
     let db_sqlite = std::env::var("CARGO_FEATURE_DB_SQLITE").is_ok();
-    if !db_sqlite {
+    let db_postgres = std::env::var("CARGO_FEATURE_DB_POSTGRES").is_ok();
+    if !db_sqlite && !db_postgres {
         panic!("At least one database backend feature must be enabled!");
     }
 }
@@ -47,6 +48,7 @@ fn get_version() -> String {
         ("SENTRY", "s"),
         ("TLS_NATIVE", "tn"),
         ("TLS_RUSTLS", "tr"),
+        ("DB_POSTGRES", "p"),
     ];
     for (feature, name) in feature_names {
         if std::env::var(format!("CARGO_FEATURE_{feature}")).is_ok() {
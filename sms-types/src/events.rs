@@ -0,0 +1,230 @@
+//! Events that are sent via webhook or websocket.
+
+use serde::{Deserialize, Serialize};
+
+/// The Kind of Event.
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, Deserialize)]
+pub enum EventKind {
+    /// New SMS message received.
+    #[serde(rename = "incoming")]
+    IncomingMessage,
+
+    /// SMS message being sent from API or other connected client.
+    #[serde(rename = "outgoing")]
+    OutgoingMessage,
+
+    /// Delivery report update.
+    #[serde(rename = "delivery")]
+    DeliveryReport,
+
+    /// Modem hat connection status update.
+    #[serde(rename = "modem_status_update")]
+    ModemStatusUpdate,
+
+    /// An unsolicited position report from GNSS.
+    #[serde(rename = "gnss_position_report")]
+    GNSSPositionReport,
+
+    /// A scheduled message has been dispatched.
+    #[serde(rename = "scheduled_dispatched")]
+    ScheduledMessageDispatched,
+
+    /// A network-initiated USSD message.
+    #[serde(rename = "incoming_ussd")]
+    IncomingUSSD,
+
+    /// An incoming call to a modem.
+    #[serde(rename = "incoming_call")]
+    IncomingCall,
+
+    /// A modem's network registration has changed.
+    #[serde(rename = "network_status_change")]
+    NetworkStatusChange,
+
+    /// A multipart message stopped receiving parts, and was stored incomplete.
+    #[serde(rename = "incomplete_message")]
+    IncompleteMessage,
+}
+impl EventKind {
+    /// Total number of  `EventKind`'s.
+    pub const COUNT: usize = 10;
+
+    /// Make the `EventKind` into it's u16 bit representation.
+    #[inline]
+    #[must_use]
+    pub const fn to_bit(self) -> u16 {
+        match self {
+            EventKind::IncomingMessage => 1 << 0,
+            EventKind::OutgoingMessage => 1 << 1,
+            EventKind::DeliveryReport => 1 << 2,
+            EventKind::ModemStatusUpdate => 1 << 3,
+            EventKind::GNSSPositionReport => 1 << 4,
+            EventKind::ScheduledMessageDispatched => 1 << 5,
+            EventKind::IncomingUSSD => 1 << 6,
+            EventKind::IncomingCall => 1 << 7,
+            EventKind::NetworkStatusChange => 1 << 8,
+            EventKind::IncompleteMessage => 1 << 9,
+        }
+    }
+
+    /// Create a bitmask with all `EventKind`'s.
+    #[inline]
+    #[must_use]
+    pub const fn all_bits() -> u16 {
+        (1 << Self::COUNT) - 1
+    }
+
+    /// Takes a set of `EventKinds` and returns its mask.
+    #[inline]
+    #[must_use]
+    pub fn events_to_mask(events: &[EventKind]) -> u16 {
+        events.iter().fold(0, |acc, event| acc | event.to_bit())
+    }
+}
+impl From<&Event> for EventKind {
+    fn from(value: &Event) -> Self {
+        match value {
+            Event::IncomingMessage(_) => EventKind::IncomingMessage,
+            Event::OutgoingMessage(_) => EventKind::OutgoingMessage,
+            Event::DeliveryReport { .. } => EventKind::DeliveryReport,
+            Event::ModemStatusUpdate { .. } => EventKind::ModemStatusUpdate,
+
+            #[cfg(feature = "gnss")]
+            Event::GnssPositionReport(_) => EventKind::GNSSPositionReport,
+            Event::ScheduledMessageDispatched { .. } => EventKind::ScheduledMessageDispatched,
+            Event::IncomingUssd { .. } => EventKind::IncomingUSSD,
+            Event::IncomingCall(_) => EventKind::IncomingCall,
+            Event::NetworkStatusChange { .. } => EventKind::NetworkStatusChange,
+            Event::IncompleteMessage { .. } => EventKind::IncompleteMessage,
+        }
+    }
+}
+impl TryFrom<&str> for EventKind {
+    type Error = String;
+
+    /// Convert a str into an `EventKind`.
+    #[inline]
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "incoming" => Ok(EventKind::IncomingMessage),
+            "outgoing" => Ok(EventKind::OutgoingMessage),
+            "delivery" => Ok(EventKind::DeliveryReport),
+            "modem_status_update" => Ok(EventKind::ModemStatusUpdate),
+            "gnss_position_report" => Ok(EventKind::GNSSPositionReport),
+            "scheduled_dispatched" => Ok(EventKind::ScheduledMessageDispatched),
+            "incoming_ussd" => Ok(EventKind::IncomingUSSD),
+            "incoming_call" => Ok(EventKind::IncomingCall),
+            "network_status_change" => Ok(EventKind::NetworkStatusChange),
+            "incomplete_message" => Ok(EventKind::IncompleteMessage),
+            _ => Err(format!("Unknown event type {value}")),
+        }
+    }
+}
+
+/// Event types that can be sent by the server.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum Event {
+    /// New SMS message received.
+    #[serde(rename = "incoming")]
+    IncomingMessage(crate::sms::SmsMessage),
+
+    /// SMS message being sent from API or other connected client.
+    #[serde(rename = "outgoing")]
+    OutgoingMessage(crate::sms::SmsMessage),
+
+    /// Delivery report update.
+    #[serde(rename = "delivery")]
+    DeliveryReport {
+        /// The target `message_id` this delivery report applies to.
+        /// This is determined from the `message_reference` and sender.
+        message_id: i64,
+
+        /// The received delivery report.
+        report: crate::sms::SmsPartialDeliveryReport,
+
+        /// Whether the report could be for more than one sent message, in which case there's an
+        /// event for each of them and their status is left unchanged.
+        #[serde(default)]
+        ambiguous: bool,
+    },
+
+    /// Modem hat connection status update.
+    /// This can be either: Startup, Online, `ShuttingDown`, Offline
+    #[serde(rename = "modem_status_update")]
+    ModemStatusUpdate {
+        /// Previous state from last update.
+        previous: crate::modem::ModemStatusUpdateState,
+
+        /// Current state after update.
+        current: crate::modem::ModemStatusUpdateState,
+
+        /// The id of the modem that changed status.
+        #[serde(default)]
+        modem_id: Option<String>,
+
+        /// The SIM status from the last initialization attempt, if it got that far.
+        #[serde(default)]
+        sim_status: Option<crate::modem::SimStatus>,
+    },
+
+    /// An unsolicited position report from GNSS.
+    #[cfg(feature = "gnss")]
+    #[serde(rename = "gnss_position_report")]
+    GnssPositionReport(crate::gnss::PositionReport),
+
+    /// A scheduled message has reached its send time, and has been sent
+    /// (or has failed to send) through the server outbox.
+    #[serde(rename = "scheduled_dispatched")]
+    ScheduledMessageDispatched {
+        /// The `scheduled_id` that was returned when the message was scheduled.
+        scheduled_id: i64,
+
+        /// The stored `message_id` of the outgoing message.
+        message_id: i64,
+
+        /// Whether the message was sent, otherwise it ran out of send attempts.
+        success: bool,
+    },
+
+    /// A USSD message initiated by the network, or a late reply to a
+    /// request that has already timed out.
+    #[serde(rename = "incoming_ussd")]
+    IncomingUssd {
+        /// The id of the modem that received the message.
+        modem_id: String,
+
+        /// The received USSD message.
+        #[serde(flatten)]
+        message: crate::modem::UssdMessage,
+    },
+
+    /// An incoming call to a modem, sent once per call after it has been logged.
+    #[serde(rename = "incoming_call")]
+    IncomingCall(crate::modem::IncomingCall),
+
+    /// A modem's network registration has changed, eg: it has lost registration or started roaming.
+    #[serde(rename = "network_status_change")]
+    NetworkStatusChange {
+        /// The id of the modem whose registration changed.
+        modem_id: String,
+
+        /// The new registration.
+        #[serde(flatten)]
+        registration: crate::modem::NetworkRegistration,
+    },
+
+    /// A multipart message that stopped receiving parts before it was complete. The parts that
+    /// were received are stored as a message, marked as incomplete.
+    #[serde(rename = "incomplete_message")]
+    IncompleteMessage {
+        /// The stored message, with the received parts joined in order.
+        message: crate::sms::SmsMessage,
+
+        /// How many of the parts were received.
+        received_parts: u8,
+
+        /// How many parts the message was sent in.
+        total_parts: u8,
+    },
+}
@@ -0,0 +1,140 @@
+//! Types used by the SMS server Modem, sent in events.
+
+use serde::{Deserialize, Serialize};
+
+/// Represents the current status of the modem.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ModemStatusUpdateState {
+    /// Modem is starting up.
+    Startup,
+
+    /// Modem is online and operational.
+    Online,
+
+    /// Modem is shutting down.
+    ShuttingDown,
+
+    /// Modem is offline and not operational.
+    Offline,
+}
+impl std::fmt::Display for ModemStatusUpdateState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ModemStatusUpdateState::Startup => write!(f, "Startup"),
+            ModemStatusUpdateState::Online => write!(f, "Online"),
+            ModemStatusUpdateState::ShuttingDown => write!(f, "ShuttingDown"),
+            ModemStatusUpdateState::Offline => write!(f, "Offline"),
+        }
+    }
+}
+
+/// The state of the SIM card, checked while the modem is initialized.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum SimStatus {
+    /// The SIM is unlocked and ready.
+    Ready,
+
+    /// The SIM is locked, waiting for its PIN.
+    PinRequired,
+
+    /// The SIM is blocked after too many wrong PINs, and can only be unblocked with its PUK.
+    PukRequired,
+
+    /// There is no SIM card inserted.
+    NotInserted,
+
+    /// The SIM is locked some other way, eg: to another network.
+    Locked,
+}
+
+/// The network domain a registration update applies to.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum RegistrationDomain {
+    /// Calls and SMS, from +CREG.
+    CircuitSwitched,
+
+    /// Packet data, from +CGREG.
+    PacketSwitched,
+
+    /// LTE (EPS), from +CEREG.
+    Lte,
+}
+
+/// A network registration update from the modem.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NetworkRegistration {
+    /// The network domain this registration is for.
+    pub domain: RegistrationDomain,
+
+    /// Registration status (0=not registered, 1=registered home, 2=searching,
+    /// 3=registration denied, 4=unknown, 5=registered roaming).
+    pub status: u8,
+
+    /// Hex location area code (tracking area code for LTE) of the serving cell, if registered.
+    pub lac: Option<String>,
+
+    /// Hex id of the serving cell, if registered.
+    pub cell_id: Option<String>,
+
+    /// Access technology of the serving cell (0=GSM, 2=UTRAN, 3=GSM w/EGPRS, 7=E-UTRAN), if reported.
+    pub technology: Option<u8>,
+}
+impl NetworkRegistration {
+    /// Check if the modem is registered, either on its home network or roaming.
+    #[must_use]
+    pub fn is_registered(&self) -> bool {
+        matches!(self.status, 1 | 5)
+    }
+
+    /// Check if the modem is registered on a roaming network.
+    #[must_use]
+    pub fn is_roaming(&self) -> bool {
+        self.status == 5
+    }
+}
+
+/// A USSD reply, either to a request or initiated by the network.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UssdMessage {
+    /// Session status (0=complete, 1=further action required, 2=terminated by network,
+    /// 3=answered by another client, 4=not supported, 5=network timeout).
+    pub status: u8,
+
+    /// The decoded reply text, if the network sent any.
+    pub text: Option<String>,
+}
+impl UssdMessage {
+    /// Check if the network is waiting for a reply to continue the session, eg: a menu.
+    #[must_use]
+    pub fn is_action_required(&self) -> bool {
+        self.status == 1
+    }
+}
+
+/// An incoming call to a modem, logged once per call regardless of how many times it rang.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IncomingCall {
+    /// Unique identifier for this call.
+    pub call_id: Option<i64>,
+
+    /// The id of the modem that received the call.
+    pub modem_id: String,
+
+    /// The caller's phone number, if it wasn't withheld.
+    pub phone_number: Option<String>,
+
+    /// Whether the call was hung up by the server, otherwise it was left to ring out.
+    pub rejected: bool,
+
+    /// Unix timestamp when the call was received.
+    pub created_at: Option<u32>,
+}
@@ -0,0 +1,377 @@
+//! Generic types that apply to both HTTP and Websocket interfaces.
+
+use serde::{Deserialize, Serialize};
+
+/// Represents a stored SMS message from the database.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SmsMessage {
+    /// Unique identifier for the message.
+    pub message_id: Option<i64>,
+
+    /// The phone number associated with this message.
+    pub phone_number: String,
+
+    /// The actual text content of the message.
+    pub message_content: String,
+
+    /// Optional reference number for message tracking.
+    /// This is assigned by the modem and is only present for outgoing messages.
+    pub message_reference: Option<u8>,
+
+    /// Whether this message was sent (true) or received (false).
+    pub is_outgoing: bool,
+
+    /// Unix timestamp when the message was created.
+    pub created_at: Option<u32>,
+
+    /// Optional Unix timestamp when the message was completed/delivered.
+    pub completed_at: Option<u32>,
+
+    /// Service message center delivery status.
+    pub status: Option<u8>,
+
+    /// The id of the modem that sent or received this message, if known.
+    #[serde(default)]
+    pub modem_id: Option<String>,
+
+    /// Whether this is a received multipart message that stopped receiving parts before
+    /// it was complete, so the content is only the parts that were received.
+    #[serde(default)]
+    pub incomplete: bool,
+
+    /// Unix timestamp when an incoming message was sent, from the service centre timestamp.
+    /// This can be well before `created_at` if the message was queued while offline.
+    #[serde(default)]
+    pub sent_at: Option<u32>,
+
+    /// The hex PDU(s) an incoming message was decoded from, one per line for multipart
+    /// messages. Only stored if `store_raw_pdu` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_pdu: Option<String>,
+}
+impl SmsMessage {
+    /// Returns a clone of the message with the `message_id` option replaced.
+    #[must_use]
+    pub fn with_message_id(&self, id: Option<i64>) -> Self {
+        Self {
+            message_id: id,
+            ..self.clone()
+        }
+    }
+
+    /// Get the message `created_at` time as `SystemTime`.
+    #[must_use]
+    pub fn created_at(&self) -> Option<std::time::SystemTime> {
+        self.created_at
+            .map(|ts| std::time::UNIX_EPOCH + std::time::Duration::from_secs(u64::from(ts)))
+    }
+}
+
+/// The outgoing SMS message to be sent to a target number.
+#[derive(Serialize, PartialEq, Default, Debug, Clone)]
+pub struct SmsOutgoingMessage {
+    /// The target phone number, this should be in international format.
+    pub to: String,
+
+    /// The full message content. This will be split into multiple messages
+    /// by the server if required. This also supports Unicode emojis etc.
+    pub content: String,
+
+    /// The relative validity period to use for message sending. This determines
+    /// how long the message should remain waiting while undelivered.
+    /// By default, this is determined by the server (24 hours).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validity_period: Option<u8>,
+
+    /// Should the SMS message be sent as a Silent class? This makes a popup
+    /// show on the users device with the message content if they're logged in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flash: Option<bool>,
+
+    /// A timeout that should be applied to the entire request.
+    /// If one is not set, the default timeout is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+}
+impl SmsOutgoingMessage {
+    /// Create a new outgoing message with a default validity period and no flash.
+    /// The default validity period is applied by SMS-API, so usually 24 hours.
+    pub fn simple_message(to: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            to: to.into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the message flash state. This will show a popup if the recipient is
+    /// logged-in to their phone, otherwise as a normal text message.
+    #[must_use]
+    pub fn with_flash(mut self, flash: bool) -> Self {
+        self.flash = Some(flash);
+        self
+    }
+
+    /// Set a relative validity period value.
+    #[must_use]
+    pub fn with_validity_period(mut self, period: u8) -> Self {
+        self.validity_period = Some(period);
+        self
+    }
+
+    /// Set a request timeout value.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: u32) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Get the message sending validity period, either as set or default.
+    /// Returns class 0 for a flash message.
+    #[must_use]
+    pub fn get_validity_period(&self) -> u8 {
+        if self.flash.unwrap_or(false) {
+            return 0;
+        }
+        self.validity_period.unwrap_or(167) // 24hr
+    }
+}
+impl From<&SmsOutgoingMessage> for SmsMessage {
+    fn from(outgoing: &SmsOutgoingMessage) -> Self {
+        SmsMessage {
+            message_id: None,
+            phone_number: outgoing.to.clone(),
+            message_content: outgoing.content.clone(),
+            message_reference: None,
+            is_outgoing: true,
+            status: None,
+            created_at: None,
+            completed_at: None,
+            modem_id: None,
+            incomplete: false,
+            sent_at: None,
+            raw_pdu: None,
+        }
+    }
+}
+
+/// An incoming message from the Modem.
+#[derive(Debug, Clone)]
+pub struct SmsIncomingMessage {
+    /// The incoming sender address. This could also be an alphanumeric sender name.
+    /// This is usually for registered businesses or carrier messages.
+    pub phone_number: String,
+
+    /// The decoded multipart header.
+    pub user_data_header: Option<SmsMultipartHeader>,
+
+    /// The raw message content.
+    pub content: String,
+
+    /// Unix timestamp when the message was sent, from the service centre timestamp if valid.
+    pub sent_at: Option<u32>,
+
+    /// The hex PDU the message was decoded from.
+    pub raw_pdu: Option<String>,
+}
+impl From<&SmsIncomingMessage> for SmsMessage {
+    fn from(incoming: &SmsIncomingMessage) -> Self {
+        SmsMessage {
+            message_id: None,
+            phone_number: incoming.phone_number.clone(),
+            message_content: incoming.content.clone(),
+            message_reference: None,
+            is_outgoing: false,
+            status: None,
+            created_at: None,
+            completed_at: None,
+            modem_id: None,
+            incomplete: false,
+            sent_at: incoming.sent_at,
+            raw_pdu: incoming.raw_pdu.clone(),
+        }
+    }
+}
+
+/// An outgoing message waiting to be sent at a scheduled time.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SmsScheduledMessage {
+    /// Unique identifier for the scheduled message.
+    pub scheduled_id: i64,
+
+    /// The target phone number.
+    pub phone_number: String,
+
+    /// The message content to send.
+    pub message_content: String,
+
+    /// Should the message be sent as a flash message.
+    pub flash: bool,
+
+    /// The relative validity period to send with, if set.
+    pub validity_period: Option<u8>,
+
+    /// Unix timestamp of when the message should be sent.
+    pub send_at: u32,
+
+    /// Unix timestamp when the message was scheduled.
+    pub created_at: Option<u32>,
+}
+
+/// A received or stored delivery report.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SmsDeliveryReport {
+    /// Unique identifier for this delivery report.
+    pub report_id: Option<i64>,
+
+    /// Position of the message part the report is for, starting at 1. Unset for
+    /// reports of multipart messages stored before parts were tracked.
+    #[serde(default)]
+    pub part_index: Option<u8>,
+
+    /// Delivery status code from the network.
+    pub status: u8,
+
+    /// Whether this is the final delivery report for its message part.
+    pub is_final: bool,
+
+    /// Unix timestamp when the service centre received the message, from the report.
+    #[serde(default)]
+    pub submitted_at: Option<u32>,
+
+    /// Unix timestamp when the message was delivered or failed, from the report.
+    #[serde(default)]
+    pub discharged_at: Option<u32>,
+
+    /// Whether the report could belong to more than one sent message, in which case it's stored
+    /// for each of them and their status is left unchanged.
+    #[serde(default)]
+    pub ambiguous: bool,
+
+    /// Unix timestamp when this report was created.
+    pub created_at: Option<u32>,
+}
+
+/// The delivery state of one part of a sent message. Messages too long for a single SMS
+/// are sent as multiple parts, each with its own message reference and delivery reports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SmsMessagePart {
+    /// The message this is a part of.
+    pub message_id: i64,
+
+    /// Position of the part within the message, starting at 1.
+    pub part_index: u8,
+
+    /// The modem assigned message reference for this part.
+    pub message_reference: u8,
+
+    /// Delivery status code from the latest delivery report for this part.
+    pub status: Option<u8>,
+
+    /// Unix timestamp when this part reached a final delivery status.
+    pub completed_at: Option<u32>,
+}
+
+/// A partial message delivery report, as it comes from the modem.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SmsPartialDeliveryReport {
+    /// The target phone number that received the message (and has now sent back a delivery report).
+    pub phone_number: String,
+    /// The modem assigned message reference, this is basically useless outside short-term tracking
+    /// the `message_id` is unique should always be used instead for identification.
+    pub reference_id: u8,
+
+    /// The SMS TP-Status: <https://www.etsi.org/deliver/etsi_ts/123000_123099/123040/16.00.00_60/ts_123040v160000p.pdf#page=71>
+    pub status: u8,
+
+    /// Unix timestamp when the service centre received the message (TP-SCTS), if valid.
+    #[serde(default)]
+    pub submitted_at: Option<u32>,
+
+    /// Unix timestamp when the message was delivered or failed (TP-DT), if valid.
+    #[serde(default)]
+    pub discharged_at: Option<u32>,
+}
+
+/// A general category of status message delivery status reports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum SmsDeliveryReportStatusCategory {
+    /// The message has been sent, however not yet delivered.
+    Sent,
+
+    /// The message has been delivered.
+    Received,
+
+    /// The message has a temporary error, and sending will be retried by the carrier.
+    Retrying,
+
+    /// The message has a permanent error, the message will not be retried.
+    Failed,
+}
+impl From<u8> for SmsDeliveryReportStatusCategory {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => SmsDeliveryReportStatusCategory::Received, // Received by SME
+            0x01..=0x1F => SmsDeliveryReportStatusCategory::Sent, // Forwarded/Replaced/SC-specific success
+            0x20..=0x3F => SmsDeliveryReportStatusCategory::Retrying,
+            _ => SmsDeliveryReportStatusCategory::Failed, // Permanent errors (0x40..=0x6F) and reserved
+        }
+    }
+}
+impl std::fmt::Display for SmsDeliveryReportStatusCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SmsDeliveryReportStatusCategory::Sent => "Sent",
+            SmsDeliveryReportStatusCategory::Received => "Received",
+            SmsDeliveryReportStatusCategory::Retrying => "Retrying",
+            SmsDeliveryReportStatusCategory::Failed => "Failed",
+        })
+    }
+}
+impl From<&SmsDeliveryReport> for SmsDeliveryReportStatusCategory {
+    fn from(value: &SmsDeliveryReport) -> Self {
+        SmsDeliveryReportStatusCategory::from(value.status)
+    }
+}
+impl From<&SmsPartialDeliveryReport> for SmsDeliveryReportStatusCategory {
+    fn from(value: &SmsPartialDeliveryReport) -> Self {
+        SmsDeliveryReportStatusCategory::from(value.status)
+    }
+}
+
+/// The sms message multipart header.
+#[derive(Debug, Clone, Copy)]
+pub struct SmsMultipartHeader {
+    /// Sender assigned message reference (overflows). This is 8-bit for most senders,
+    /// but can be 16-bit if they use the 16-bit concatenation information element.
+    pub message_reference: u16,
+
+    /// The total amount of messages within this multipart.
+    pub total: u8,
+
+    /// The current received message index.
+    pub index: u8,
+}
+impl TryFrom<Vec<u8>> for SmsMultipartHeader {
+    type Error = &'static str;
+
+    /// Accepts the data of either an 8-bit (3 bytes) or 16-bit (4 bytes) reference header.
+    fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
+        let (message_reference, total, index) = match data[..] {
+            [reference, total, index] => (u16::from(reference), total, index),
+            [high, low, total, index] => (u16::from_be_bytes([high, low]), total, index),
+            _ => return Err("Invalid user data length!"),
+        };
+        Ok(Self {
+            message_reference,
+            total,
+            index,
+        })
+    }
+}
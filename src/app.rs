@@ -1,13 +1,16 @@
 use crate::config::AppConfig;
 use crate::events::EventBroadcaster;
-use crate::modem::types::ModemIncomingMessage;
+use crate::modem::types::{ModemIncomingMessage, ModemMessage};
 use crate::modem::ModemManager;
-use crate::sms::{SMSManager, SMSReceiver};
+use crate::sms::routing::{ModemRouter, RoutedModem};
+use crate::sms::{DeliveryReportOutcome, SMSDatabase, SMSManager, SMSReceiver};
 use crate::TracingReloadHandle;
 use anyhow::{bail, Result};
 use sms_types::events::Event;
+use sms_types::sms::SmsIncomingMessage;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 use tokio::task::JoinHandle;
 use tokio::time::interval;
 use tracing::log::{debug, error, info, warn};
@@ -36,29 +39,57 @@ impl AppHandles {
     ) -> Result<AppHandles> {
         let mut tasks = Vec::new();
 
-        // Start modem manager
-        let (mut modem, main_rx) = ModemManager::new(&config);
-        let (modem_handle, modem_sender) = match modem.start().await {
-            Ok(handle) => (handle, modem.get_sender()?),
-            Err(e) => bail!("Failed to start ModemManager: {:?}", e),
-        };
-        tasks.push(("Modem Handler", modem_handle));
+        // Connect to database, shared by the SMS manager and webhook retry queue.
+        let database = Arc::new(SMSDatabase::connect(&config.database, config.modem_ids()).await?);
+
+        // Create a modem manager for each modem, all sending into the same main channel.
+        // They aren't started until the receiver is running, so stored messages read
+        // during initialization always have somewhere to go.
+        let (main_tx, main_rx) = mpsc::unbounded_channel();
+        let mut modem_managers = Vec::with_capacity(config.modems.len());
+        let mut routed_modems = Vec::with_capacity(config.modems.len());
+        for modem_config in &config.modems {
+            let mut modem = ModemManager::new(modem_config.clone(), main_tx.clone());
+            routed_modems.push(RoutedModem::new(
+                &modem_config.id,
+                modem_config.prefixes.clone(),
+                modem.get_sender()?,
+            ));
+            modem_managers.push(modem);
+        }
+        let modems = Arc::new(ModemRouter::new(config.routing.policy, routed_modems)?);
 
         // Create event broadcaster (and webhook worker handle).
-        let (broadcaster, webhooks_handle) = EventBroadcaster::new(&config);
+        let (broadcaster, webhooks_handle) = EventBroadcaster::new(&config, &database);
         if let Some(webhooks_worker) = webhooks_handle {
             tasks.push(("Webhooks Worker", webhooks_worker));
         }
 
         // Setup SMS manager and receivers.
-        let sms_manager =
-            SMSManager::connect(config.database, modem_sender, broadcaster.clone()).await?;
+        let sms_manager = SMSManager::new(database, modems, broadcaster.clone());
+        tasks.push(("Outbox Worker", sms_manager.start_outbox()));
+
+        let sms_receiver = SMSReceiver::new(sms_manager.clone(), config.calls.policy);
+        sms_receiver.restore_multipart().await?;
 
         let (cleanup_handle, channel_handle) =
-            Self::start_sms_receiver(main_rx, sms_manager.clone(), broadcaster.clone());
+            Self::start_sms_receiver(main_rx, sms_receiver, broadcaster.clone());
         tasks.push(("Modem Cleanup", cleanup_handle));
         tasks.push(("Modem Channel", channel_handle));
 
+        // Start the modems now everything they send into is ready.
+        for (modem_config, mut modem) in config.modems.iter().zip(modem_managers) {
+            let modem_handle = match modem.start().await {
+                Ok(handle) => handle,
+                Err(e) => bail!(
+                    "Failed to start ModemManager '{}': {:?}",
+                    modem_config.id,
+                    e
+                ),
+            };
+            tasks.push(("Modem Handler", modem_handle));
+        }
+
         // Setup HTTP server if enabled.
         #[cfg(feature = "http-server")]
         if let Some(http_handle) = Self::start_http_server(
@@ -98,12 +129,10 @@ impl AppHandles {
     }
 
     fn start_sms_receiver(
-        mut main_rx: UnboundedReceiver<ModemIncomingMessage>,
-        sms_manager: SMSManager,
+        mut main_rx: UnboundedReceiver<ModemMessage>,
+        receiver: SMSReceiver,
         broadcaster: Option<EventBroadcaster>,
     ) -> (JoinHandle<()>, JoinHandle<()>) {
-        let receiver = SMSReceiver::new(sms_manager);
-
         // Cleanup task
         let mut cleanup_receiver = receiver.clone();
         let cleanup_handle = tokio::spawn(async move {
@@ -127,29 +156,58 @@ impl AppHandles {
     }
 
     async fn handle_modem_message(
-        message: ModemIncomingMessage,
+        ModemMessage { modem_id, message }: ModemMessage,
         receiver: &mut SMSReceiver,
         broadcaster: &Option<EventBroadcaster>,
     ) {
         match message {
             ModemIncomingMessage::IncomingSMS(incoming) => {
-                match receiver.handle_incoming_sms(incoming).await {
-                    Some(Ok(row_id)) => debug!("Stored SMS message #{row_id}"),
-                    Some(Err(e)) => error!("Failed to store SMS: {e:?}"),
-                    None => debug!("SMS is part of multipart message, not storing yet"),
+                Self::handle_incoming_sms(&modem_id, incoming, receiver).await;
+            }
+            ModemIncomingMessage::StoredSMS(incoming, stored_tx) => {
+                // The modem only deletes its copy once it's been stored.
+                if Self::handle_incoming_sms(&modem_id, incoming, receiver).await {
+                    let _ = stored_tx.send(());
                 }
             }
             ModemIncomingMessage::DeliveryReport(report) => {
-                match receiver.handle_delivery_report(report).await {
-                    Ok(message_id) => debug!("Updated delivery status for message #{message_id}"),
+                match receiver.handle_delivery_report(&modem_id, report).await {
+                    Ok(DeliveryReportOutcome::Matched(message_id)) => {
+                        debug!("Updated delivery status for message #{message_id}")
+                    }
+                    Ok(DeliveryReportOutcome::Ambiguous(message_ids)) => warn!(
+                        "Delivery report could be for messages {message_ids:?}, stored as ambiguous"
+                    ),
                     Err(e) => warn!("Failed to update delivery report: {e:?}"),
                 }
             }
-            ModemIncomingMessage::ModemStatusUpdate { previous, current } => {
+            ModemIncomingMessage::ModemStatusUpdate {
+                previous,
+                current,
+                sim_status,
+            } => {
                 if let Some(broadcaster) = broadcaster {
                     broadcaster.broadcast(Event::ModemStatusUpdate {
+                        modem_id: Some(modem_id.to_string()),
                         previous: previous.into(),
                         current: current.into(),
+                        sim_status,
+                    });
+                }
+            }
+            ModemIncomingMessage::NetworkStatusChange(registration) => {
+                if !registration.is_registered() {
+                    warn!("Modem '{modem_id}' is not registered: {registration:?}");
+                } else if registration.is_roaming() {
+                    info!("Modem '{modem_id}' is roaming: {registration:?}");
+                } else {
+                    debug!("Modem '{modem_id}' registration changed: {registration:?}");
+                }
+
+                if let Some(broadcaster) = broadcaster {
+                    broadcaster.broadcast(Event::NetworkStatusChange {
+                        modem_id: modem_id.to_string(),
+                        registration,
                     });
                 }
             }
@@ -158,7 +216,43 @@ impl AppHandles {
                     broadcaster.broadcast(Event::GnssPositionReport(location));
                 }
             }
-            _ => warn!("Unhandled message type: {message:?}"),
+            ModemIncomingMessage::IncomingUSSD(message) => {
+                debug!("Received USSD from modem '{modem_id}': {message:?}");
+                if let Some(broadcaster) = broadcaster {
+                    broadcaster.broadcast(Event::IncomingUssd {
+                        modem_id: modem_id.to_string(),
+                        message,
+                    });
+                }
+            }
+            ModemIncomingMessage::IncomingCall(phone_number) => {
+                match receiver.handle_incoming_call(&modem_id, phone_number).await {
+                    Ok(call_id) => debug!("Stored incoming call #{call_id}"),
+                    Err(e) => error!("Failed to store incoming call: {e:?}"),
+                }
+            }
+        }
+    }
+
+    /// Returns if the message (or its part of a multipart message) was stored.
+    async fn handle_incoming_sms(
+        modem_id: &Arc<str>,
+        incoming: SmsIncomingMessage,
+        receiver: &mut SMSReceiver,
+    ) -> bool {
+        match receiver.handle_incoming_sms(modem_id, incoming).await {
+            Some(Ok(row_id)) => {
+                debug!("Stored SMS message #{row_id}");
+                true
+            }
+            Some(Err(e)) => {
+                error!("Failed to store SMS: {e:?}");
+                false
+            }
+            None => {
+                debug!("SMS is part of multipart message, not storing yet");
+                true
+            }
         }
     }
 
@@ -0,0 +1,23 @@
+/// Exponential backoff in seconds for something that has failed attempts times,
+/// starting from base and doubling with each attempt up to max.
+pub fn backoff(base: u64, max: u64, attempts: u32) -> u64 {
+    let exponent = attempts.saturating_sub(1).min(16);
+    base.saturating_mul(1 << exponent).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(15, 900, 0), 15);
+        assert_eq!(backoff(15, 900, 1), 15);
+        assert_eq!(backoff(15, 900, 2), 30);
+        assert_eq!(backoff(15, 900, 3), 60);
+        assert_eq!(backoff(15, 900, 10), 900);
+        assert_eq!(backoff(15, 900, u32::MAX), 900);
+        assert_eq!(backoff(u64::MAX, 900, 2), 900);
+        assert_eq!(backoff(0, 900, 5), 0);
+    }
+}
@@ -1,10 +1,10 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use base64::engine::general_purpose;
 use base64::Engine;
 use reqwest::header::HeaderMap;
 use serde::Deserialize;
 use sms_types::events::EventKind;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -16,8 +16,18 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 pub struct AppConfig {
     pub database: DatabaseConfig,
 
+    /// A single modem, kept for existing configs. Moved into modems when loading.
     #[serde(default)]
-    pub modem: ModemConfig,
+    modem: Option<ModemConfig>,
+
+    #[serde(default)]
+    pub modems: Vec<ModemConfig>,
+
+    #[serde(default)]
+    pub routing: RoutingConfig,
+
+    #[serde(default)]
+    pub calls: CallsConfig,
 
     #[cfg(feature = "http-server")]
     #[serde(default)]
@@ -36,27 +46,202 @@ impl AppConfig {
         let config_content = fs::read_to_string(&config_path)
             .with_context(|| format!("Failed to read config file: {config_path:?}"))?;
 
-        let config: AppConfig = toml::from_str(&config_content)
+        let mut config: AppConfig = toml::from_str(&config_content)
             .with_context(|| format!("Failed to parse TOML config file: {config_path:?}"))?;
 
+        config.normalise_modems()?;
         Ok(config)
     }
+
+    /// The ids of every configured modem.
+    pub fn modem_ids(&self) -> Vec<String> {
+        self.modems.iter().map(|modem| modem.id.clone()).collect()
+    }
+
+    /// Merge the single modem config into modems, ensuring there's at least one with unique ids.
+    fn normalise_modems(&mut self) -> Result<()> {
+        if let Some(modem) = self.modem.take() {
+            if !self.modems.is_empty() {
+                bail!("Configure either a single [modem] or multiple [[modems]], not both!");
+            }
+            self.modems.push(modem);
+        }
+        if self.modems.is_empty() {
+            self.modems.push(ModemConfig::default());
+        }
+
+        let mut ids = HashSet::with_capacity(self.modems.len());
+        for modem in &self.modems {
+            if !ids.insert(modem.id.as_str()) {
+                bail!(
+                    "Duplicate modem id '{}', each modem must have a unique id!",
+                    modem.id
+                );
+            }
+            modem
+                .read_sim_pin()
+                .with_context(|| format!("Invalid SIM PIN for modem '{}'", modem.id))?;
+        }
+        Ok(())
+    }
+}
+
+/// How outgoing messages are assigned to a modem.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoutingPolicy {
+    /// Use the first online modem, in config order.
+    #[default]
+    Failover,
+
+    /// Rotate between online modems.
+    RoundRobin,
+
+    /// Use the modem with the longest prefix matching the destination, falling back
+    /// to modems without any prefixes. Prefers online modems within each group.
+    Prefix,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub policy: RoutingPolicy,
+}
+
+/// What to do with incoming calls, which are always logged and broadcast.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CallPolicy {
+    /// Leave the call to ring until the caller hangs up.
+    #[default]
+    RingOut,
+
+    /// Hang up the call as soon as it's received.
+    Reject,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CallsConfig {
+    #[serde(default)]
+    pub policy: CallPolicy,
+}
+
+/// The modem family, which decides the non-standard AT commands and notifications used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ModemProfile {
+    /// SIM800 and SIM868 modems, eg: the Waveshare GSM/GPRS/GNSS hat.
+    #[default]
+    #[serde(rename = "simcom-2g")]
+    Simcom2G,
+
+    /// SIM7500 and SIM7600 LTE modems.
+    #[serde(rename = "simcom-lte")]
+    SimcomLte,
+
+    /// Quectel LTE modems, eg: EC25, EG25-G and BG96.
+    #[serde(rename = "quectel")]
+    Quectel,
+}
+
+/// Selects a USB serial device by its ids, so it's found again under whatever name it gets.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UsbDeviceConfig {
+    /// Vendor id in hex, eg: "2c7c".
+    #[serde(deserialize_with = "deserialize_usb_id")]
+    pub vid: u16,
+
+    /// Product id in hex, eg: "0125".
+    #[serde(deserialize_with = "deserialize_usb_id")]
+    pub pid: u16,
+
+    /// Serial number, to tell apart multiple modems of the same model.
+    #[serde(default)]
+    pub serial: Option<String>,
+
+    /// Interface number of the AT command port, for modems with multiple serial ports.
+    #[serde(default)]
+    pub interface: Option<u8>,
+}
+
+/// How the watchdog recovers a modem that has stopped responding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchdogAction {
+    /// Send the initialization commands again, going offline if that fails.
+    #[default]
+    Reinitialize,
+
+    /// Toggle the GPIO power pin, then go offline until it reconnects.
+    PowerCycle,
+
+    /// Go offline until it reconnects.
+    Offline,
+}
+
+/// An extra command sent once the built-in initialization commands have succeeded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InitCommand {
+    /// The AT command, without a line ending.
+    pub command: String,
+
+    /// Text the response must contain, otherwise initialization fails.
+    #[serde(default = "default_init_command_expected")]
+    pub expected: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ModemConfig {
+    /// Identifies the modem in stored messages, events and HTTP requests.
+    #[serde(default = "default_modem_id")]
+    pub id: String,
+
+    /// Destination prefixes this modem sends to, only used by the prefix routing policy.
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+
     #[serde(default = "default_modem_device")]
     pub device: String,
 
+    /// If set, the device is found by its USB ids each time it's opened, instead of using device.
+    #[serde(default)]
+    pub usb: Option<UsbDeviceConfig>,
+
     #[serde(default = "default_modem_baud")]
     pub baud_rate: u32,
 
+    #[serde(default)]
+    pub profile: ModemProfile,
+
+    /// Extra commands to send during initialization, after the profile's own commands.
+    #[serde(default)]
+    pub init_commands: Vec<InitCommand>,
+
     #[serde(default = "default_false")]
     pub gnss_enabled: bool,
 
     #[serde(default = "default_gnss_report_interval")]
     pub gnss_report_interval: u32,
 
+    /// PIN to unlock the SIM with if it's locked, this takes priority over sim_pin_file.
+    #[serde(default)]
+    pub sim_pin: Option<String>,
+
+    /// File containing the SIM PIN, so it can be kept out of the config (eg: a secret mount).
+    #[serde(deserialize_with = "deserialize_optional_existing_file")]
+    #[serde(default)]
+    pub sim_pin_file: Option<PathBuf>,
+
+    /// Seconds without hearing from the modem before probing it with AT, 0 disables the watchdog.
+    #[serde(default = "default_watchdog_interval")]
+    pub watchdog_interval: u64,
+
+    /// Consecutive command timeouts (including probes) before the modem is recovered.
+    #[serde(default = "default_watchdog_max_timeouts")]
+    pub watchdog_max_timeouts: u32,
+
+    #[serde(default)]
+    pub watchdog_action: WatchdogAction,
+
     /// The size of Command bounded mpsc sender, should be low. eg: 32
     #[serde(default = "default_modem_cmd_buffer_size")]
     pub cmd_channel_buffer_size: usize,
@@ -79,13 +264,41 @@ pub struct ModemConfig {
     #[cfg(feature = "gpio")]
     pub gpio_repower: bool,
 }
+impl ModemConfig {
+    /// Get the configured SIM PIN, reading it from sim_pin_file if it isn't set directly.
+    /// The PIN is trimmed and must be 4-8 digits, as it's sent to the modem in AT+CPIN.
+    pub fn read_sim_pin(&self) -> Result<Option<String>> {
+        let pin = match (&self.sim_pin, &self.sim_pin_file) {
+            (Some(pin), _) => pin.trim().to_string(),
+            (None, Some(path)) => fs::read_to_string(path)
+                .map(|pin| pin.trim().to_string())
+                .with_context(|| format!("Failed to read SIM PIN file: {path:?}"))?,
+            (None, None) => return Ok(None),
+        };
+
+        if !(4..=8).contains(&pin.len()) || !pin.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("SIM PIN must be 4 to 8 digits!");
+        }
+        Ok(Some(pin))
+    }
+}
 impl Default for ModemConfig {
     fn default() -> Self {
         Self {
+            id: default_modem_id(),
+            prefixes: Vec::new(),
             device: default_modem_device(),
+            usb: None,
             baud_rate: default_modem_baud(),
+            profile: ModemProfile::default(),
+            init_commands: Vec::new(),
             gnss_enabled: default_false(),
             gnss_report_interval: default_gnss_report_interval(),
+            sim_pin: None,
+            sim_pin_file: None,
+            watchdog_interval: default_watchdog_interval(),
+            watchdog_max_timeouts: default_watchdog_max_timeouts(),
+            watchdog_action: WatchdogAction::default(),
             cmd_channel_buffer_size: default_modem_cmd_buffer_size(),
             read_buffer_size: default_modem_read_buffer_size(),
             line_buffer_size: default_modem_read_buffer_size(),
@@ -106,8 +319,29 @@ impl Default for ModemConfig {
 pub struct DatabaseConfig {
     pub database_url: String,
 
+    /// The active key, used to encrypt all new content.
     #[serde(deserialize_with = "deserialize_encryption_key")]
     pub encryption_key: [u8; 32],
+
+    /// Identifies the active key in stored ciphertext, change this when rotating keys.
+    #[serde(default)]
+    pub encryption_key_id: u8,
+
+    /// Previous keys, only used to decrypt content that hasn't been re-encrypted yet.
+    #[serde(default)]
+    pub decryption_keys: Vec<DecryptionKey>,
+
+    /// Store the (encrypted) raw PDU of incoming messages, to debug decoding issues.
+    #[serde(default)]
+    pub store_raw_pdu: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecryptionKey {
+    pub id: u8,
+
+    #[serde(deserialize_with = "deserialize_encryption_key")]
+    pub key: [u8; 32],
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -125,6 +359,18 @@ pub struct ConfiguredWebhook {
     #[serde(deserialize_with = "deserialize_optional_existing_file")]
     #[serde(default)]
     pub certificate_path: Option<PathBuf>,
+
+    /// Total delivery attempts before the event is moved to the dead-letter table.
+    #[serde(default = "default_webhook_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Seconds to wait before the first retry, doubling with each failed attempt.
+    #[serde(default = "default_webhook_retry_delay")]
+    pub retry_delay: u64,
+
+    /// If set, requests are signed with an HMAC-SHA256 of the body so receivers can verify them.
+    #[serde(default)]
+    pub secret: Option<String>,
 }
 impl ConfiguredWebhook {
     pub fn get_header_map(&self) -> Result<Option<HeaderMap>> {
@@ -220,12 +466,24 @@ pub struct SentryConfig {
     pub send_default_pii: bool,
 }
 
+fn default_modem_id() -> String {
+    "default".to_string()
+}
 fn default_modem_device() -> String {
     "/dev/ttyS0".to_string()
 }
 fn default_modem_baud() -> u32 {
     115200
 }
+fn default_watchdog_interval() -> u64 {
+    60
+}
+fn default_watchdog_max_timeouts() -> u32 {
+    3
+}
+fn default_init_command_expected() -> String {
+    "OK".to_string()
+}
 fn default_modem_cmd_buffer_size() -> usize {
     32
 }
@@ -235,6 +493,12 @@ fn default_modem_read_buffer_size() -> usize {
 fn default_webhook_events() -> Vec<EventKind> {
     vec![EventKind::IncomingMessage]
 }
+fn default_webhook_max_attempts() -> u32 {
+    5
+}
+fn default_webhook_retry_delay() -> u64 {
+    30
+}
 fn default_gnss_report_interval() -> u32 {
     0
 }
@@ -277,6 +541,15 @@ where
     Ok(key)
 }
 
+fn deserialize_usb_id<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let id = String::deserialize(deserializer)?;
+    u16::from_str_radix(id.trim_start_matches("0x"), 16)
+        .map_err(|_| serde::de::Error::custom(format!("Invalid USB id '{id}', expected hex")))
+}
+
 fn deserialize_existing_file<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -310,3 +583,31 @@ where
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_sim_pin() {
+        let with_pin = |pin: &str| ModemConfig {
+            sim_pin: Some(pin.to_string()),
+            ..ModemConfig::default()
+        };
+
+        assert_eq!(ModemConfig::default().read_sim_pin().unwrap(), None);
+        assert_eq!(
+            with_pin(" 1234\n").read_sim_pin().unwrap().as_deref(),
+            Some("1234")
+        );
+        assert_eq!(
+            with_pin("12345678").read_sim_pin().unwrap().as_deref(),
+            Some("12345678")
+        );
+
+        // Too short, too long, or anything that could change the AT command.
+        for pin in ["", "123", "123456789", "12a4", "1234\"\r\nAT+CFUN=0"] {
+            assert!(with_pin(pin).read_sim_pin().is_err(), "accepted {pin:?}");
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use crate::config::AppConfig;
+use crate::sms::SMSDatabase;
 use crate::webhooks::WebhookSender;
 use sms_types::events::Event;
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 use tracing::log::debug;
 
@@ -15,11 +17,14 @@ pub struct EventBroadcaster {
     pub websocket: Option<WebSocketManager>,
 }
 impl EventBroadcaster {
-    pub fn new(config: &AppConfig) -> (Option<Self>, Option<JoinHandle<()>>) {
+    pub fn new(
+        config: &AppConfig,
+        database: &Arc<SMSDatabase>,
+    ) -> (Option<Self>, Option<JoinHandle<()>>) {
         let (webhook_sender, webhook_handle) = config
             .webhooks
             .clone()
-            .map(WebhookSender::new)
+            .map(|webhooks| WebhookSender::new(webhooks, Arc::clone(database)))
             .map_or((None, None), |(sender, handle)| {
                 (Some(sender), Some(handle))
             });
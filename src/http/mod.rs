@@ -9,15 +9,17 @@ use crate::config::HTTPConfig;
 use crate::http::routes::*;
 use crate::http::types::HttpError;
 use crate::http::websocket::WebSocketManager;
+use crate::modem::types::{ModemRequest, ModemResponse};
 use crate::sms::SMSManager;
 use crate::TracingReloadHandle;
 use anyhow::{bail, Result};
 use axum::http::{HeaderName, HeaderValue, StatusCode};
 use axum::routing::{get, post};
+use std::sync::{Arc, OnceLock};
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
-use tracing::log::{debug, warn};
+use tracing::log::{debug, info, warn};
 
 #[cfg(feature = "openapi")]
 use utoipa::OpenApi;
@@ -31,6 +33,35 @@ pub struct HttpState {
     pub config: HTTPConfig,
     pub tracing_reload: TracingReloadHandle,
     pub websocket: Option<WebSocketManager>,
+
+    /// The configured phone number, or the first modem's own number if there isn't one.
+    pub phone_number: Arc<OnceLock<String>>,
+}
+
+/// Fill the phone number from the first modem's SIM once it's online, as it isn't configured.
+async fn detect_phone_number(sms_manager: SMSManager, phone_number: Arc<OnceLock<String>>) {
+    let Ok(modem) = sms_manager.borrow_modems().get(None) else {
+        return;
+    };
+    let sender = modem.sender.clone();
+    if sender.wait_for_online().await.is_err() {
+        return;
+    }
+
+    match sender
+        .send_request(ModemRequest::GetSubscriberNumber, None)
+        .await
+    {
+        Ok(ModemResponse::SubscriberNumber(Some(number))) => {
+            info!("Using phone number {number} from the SIM, as none is configured");
+            let _ = phone_number.set(number);
+        }
+        Ok(ModemResponse::SubscriberNumber(None)) => {
+            debug!("The SIM doesn't store its phone number, and none is configured")
+        }
+        Ok(response) => warn!("Unexpected response detecting phone number: {response}"),
+        Err(e) => warn!("Failed to detect phone number: {e:?}"),
+    }
 }
 
 async fn auth_middleware(
@@ -69,22 +100,40 @@ pub fn create_app(
 ) -> Result<axum::Router> {
     let mut router = axum::Router::new()
         .route("/db/messages", post(db_messages))
+        .route("/db/search", post(db_search))
         .route("/db/latest-numbers", post(db_latest_numbers))
         .route("/db/delivery-reports", post(db_delivery_reports))
+        .route("/db/message-parts", post(db_message_parts))
+        .route("/db/calls", post(db_calls))
         .route("/db/friendly-names/set", post(db_friendly_names_set))
         .route("/db/friendly-names/get", post(db_friendly_names_get))
         .route("/sms/send", post(sms_send))
+        .route("/sms/scheduled/list", post(sms_scheduled_list))
+        .route("/sms/scheduled/cancel", post(sms_scheduled_cancel))
+        .route("/sms/scheduled/reschedule", post(sms_scheduled_reschedule))
         .route("/sms/network-status", get(sms_get_network_status))
         .route("/sms/signal-strength", get(sms_get_signal_strength))
         .route("/sms/network-operator", get(sms_get_network_operator))
         .route("/sms/service-provider", get(sms_get_service_provider))
         .route("/sms/battery-level", get(sms_get_battery_level))
+        .route("/sms/identity", get(sms_get_identity))
         .route("/sms/device-info", get(sms_get_device_info))
+        .route("/ussd/send", post(ussd_send))
+        .route("/ussd/cancel", post(ussd_cancel))
         .route("/gnss/status", get(gnss_get_status))
         .route("/gnss/location", get(gnss_get_location))
         .route("/sys/phone-number", get(sys_phone_number))
         .route("/sys/version", get(sys_version))
+        .route("/sys/modems", get(sys_modems))
         .route("/sys/set-log-level", post(sys_set_log_level))
+        .route(
+            "/webhooks/dead-letters/list",
+            post(webhooks_dead_letters_list),
+        )
+        .route(
+            "/webhooks/dead-letters/replay",
+            post(webhooks_dead_letters_replay),
+        )
         .layer(SetResponseHeaderLayer::overriding(
             HeaderName::from_static("x-version"),
             HeaderValue::from_static(crate::VERSION),
@@ -137,12 +186,26 @@ pub fn create_app(
             .layer(ServiceBuilder::new().layer(SentryHttpLayer::new().enable_transaction()))
     }
 
+    let phone_number = Arc::new(OnceLock::new());
+    match &config.phone_number {
+        Some(configured) => {
+            let _ = phone_number.set(configured.clone());
+        }
+        None => {
+            tokio::spawn(detect_phone_number(
+                sms_manager.clone(),
+                phone_number.clone(),
+            ));
+        }
+    }
+
     // Shared HTTP route state.
     let state = HttpState {
         sms_manager,
         config,
         tracing_reload: _tracing_reload,
         websocket,
+        phone_number,
     };
     Ok(router.with_state(state))
 }
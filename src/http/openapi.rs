@@ -11,27 +11,41 @@ use utoipa::Modify;
     tags(
         (name = "Database", description = "Database routes"),
         (name = "SMS", description = "SMS sending and device information"),
+        (name = "USSD", description = "USSD codes and menus"),
         (name = "GNSS", description = "GNSS position data"),
         (name = "System", description = "System configuration and status"),
+        (name = "Webhooks", description = "Failed webhook delivery management"),
     ),
     paths(
         db_messages,
+        db_search,
         db_delivery_reports,
+        db_message_parts,
         db_latest_numbers,
+        db_calls,
         db_friendly_names_set,
         db_friendly_names_get,
         sms_send,
+        sms_scheduled_list,
+        sms_scheduled_cancel,
+        sms_scheduled_reschedule,
         sms_get_network_status,
         sms_get_signal_strength,
         sms_get_network_operator,
         sms_get_service_provider,
         sms_get_battery_level,
+        sms_get_identity,
         sms_get_device_info,
+        ussd_send,
+        ussd_cancel,
         gnss_get_status,
         gnss_get_location,
         sys_phone_number,
         sys_version,
+        sys_modems,
         sys_set_log_level,
+        webhooks_dead_letters_list,
+        webhooks_dead_letters_replay,
         websocket_upgrade
     ),
     modifiers(&OpenApiModifier)
@@ -135,14 +149,21 @@ pub mod responses {
         SmsMessagesResponse => Vec<sms_types::sms::SmsMessage>,
         LatestNumbersResponse => Vec<sms_types::http::LatestNumberFriendlyNamePair>,
         DeliveryReportsResponse => Vec<sms_types::sms::SmsDeliveryReport>,
-        SmsSendResponse => sms_types::http::HttpSmsSendResponse,
+        MessagePartsResponse => Vec<sms_types::sms::SmsMessagePart>,
+        SmsSendResponse => crate::http::types::SmsSendResult,
+        ScheduledMessagesResponse => Vec<sms_types::sms::SmsScheduledMessage>,
         NetworkStatusResponse => sms_types::http::HttpModemNetworkStatusResponse,
         SignalStrengthResponse => sms_types::http::HttpModemSignalStrengthResponse,
         NetworkOperatorResponse => sms_types::http::HttpModemNetworkOperatorResponse,
         BatteryLevelResponse => sms_types::http::HttpModemBatteryLevelResponse,
+        IdentityResponse => sms_types::http::HttpModemIdentityResponse,
         DeviceInfoResponse => sms_types::http::HttpSmsDeviceInfoResponse,
+        UssdResponse => sms_types::modem::UssdMessage,
+        CallsResponse => Vec<sms_types::modem::IncomingCall>,
         GnssFixStatusResponse => sms_types::gnss::FixStatus,
         GnssPositionResponse => sms_types::gnss::PositionReport,
+        WebhookDeadLettersResponse => Vec<crate::webhooks::WebhookDeadLetter>,
+        ModemsResponse => Vec<crate::http::types::ModemInfo>,
         BoolResponse => bool,
         StringResponse => String,
         OptionalStringResponse => Option<String>
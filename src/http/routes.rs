@@ -2,12 +2,14 @@ use crate::http::types::{HttpError, HttpResult, HttpSuccess};
 use crate::http::websocket::{handle_websocket, WebSocketConnection};
 use crate::http::HttpState;
 use crate::modem::types::{ModemRequest, ModemResponse};
+use crate::sms::outbox::OutboxOutcome;
 use axum::extract::{Query, State, WebSocketUpgrade};
 use axum::http::StatusCode;
 use axum::response::Response;
 use axum::Json;
 use sms_pdu::pdu::{PduAddress, TypeOfNumber};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tracing_subscriber::EnvFilter;
 
 macro_rules! modem_extract {
@@ -45,10 +47,10 @@ macro_rules! modem_extract {
         modem_extract!(@inner $response, $($rest)+)
     };
 
-    // Send command and extract
-    ($sms_manager:expr, $request:expr => $($rest:tt)+) => {{
+    // Send command to a modem and extract
+    ($sms_manager:expr, $modem_id:expr, $request:expr => $($rest:tt)+) => {{
         let response = $sms_manager
-            .send_command($request)
+            .send_command($request, $modem_id)
             .await
             .map_err(|e| HttpError {
                 status: StatusCode::INTERNAL_SERVER_ERROR,
@@ -96,6 +98,51 @@ pub async fn db_messages(
     Ok(HttpSuccess(messages))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/db/search",
+    tag = "Database",
+    summary = "Search SMS messages",
+    description = "Finds messages containing every word in the query, matched case-insensitively against whole words. Message content stays encrypted, as only keyed hashes of each word are indexed. Optionally filtered to a single phone number, and supports pagination.",
+    security(("api_key" = [])),
+    request_body(
+        content = crate::http::types::SearchMessagesRequest,
+        example = json!({"query": "delivery tomorrow", "phone_number": "+1234567890", "limit": 50})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::SmsMessagesResponse)
+    )
+))]
+pub async fn db_search(
+    State(state): State<HttpState>,
+    Json(payload): Json<crate::http::types::SearchMessagesRequest>,
+) -> HttpResult<Vec<sms_types::sms::SmsMessage>> {
+    if payload.query.trim().is_empty() {
+        return Err(HttpError {
+            status: StatusCode::BAD_REQUEST,
+            message: "Search query cannot be empty".to_string(),
+        });
+    }
+
+    let messages = state
+        .sms_manager
+        .borrow_database()
+        .search_messages(
+            &payload.query,
+            payload.phone_number.as_deref(),
+            payload.limit,
+            payload.offset,
+            payload.reverse,
+        )
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(messages))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(
     post,
     path = "/db/latest-numbers",
@@ -173,6 +220,75 @@ pub async fn db_delivery_reports(
     Ok(HttpSuccess(delivery_reports))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/db/message-parts",
+    tag = "Database",
+    summary = "Get message parts",
+    description = "Retrieves the parts of a sent message by its message ID, with each part's message reference and latest delivery status. Messages too long for a single SMS are sent as multiple parts, and the message is only completed once every part has a final status.",
+    security(("api_key" = [])),
+    request_body(
+        content = crate::http::types::MessageIdRequest,
+        example = json!({"message_id": 10})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::MessagePartsResponse)
+    )
+))]
+pub async fn db_message_parts(
+    State(state): State<HttpState>,
+    Json(payload): Json<crate::http::types::MessageIdRequest>,
+) -> HttpResult<Vec<sms_types::sms::SmsMessagePart>> {
+    let parts = state
+        .sms_manager
+        .borrow_database()
+        .get_message_parts(payload.message_id)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(parts))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/db/calls",
+    tag = "Database",
+    summary = "Get call log",
+    description = "Retrieves the log of incoming calls across all modems, with whether each was rejected by the call policy or left to ring out. Withheld numbers have no phone_number. Supports optional pagination.",
+    security(("api_key" = [])),
+    request_body(
+        content = Option<crate::http::types::GlobalFetchRequest>,
+        example = json!({"limit": 50})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::CallsResponse)
+    )
+))]
+pub async fn db_calls(
+    State(state): State<HttpState>,
+    Json(payload): Json<Option<crate::http::types::GlobalFetchRequest>>,
+) -> HttpResult<Vec<sms_types::modem::IncomingCall>> {
+    let (limit, offset, reverse) = match payload {
+        Some(req) => (req.limit, req.offset, req.reverse),
+        None => (None, None, false),
+    };
+
+    let calls = state
+        .sms_manager
+        .borrow_database()
+        .get_calls(limit, offset, reverse)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(calls))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(
     post,
     path = "/db/friendly-names/set",
@@ -245,20 +361,20 @@ pub async fn db_friendly_names_get(
     path = "/sms/send",
     tag = "SMS",
     summary = "Send SMS message",
-    description = "Sends an SMS message to the specified phone number. Supports flash messages (displayed immediately on the recipient's screen), custom validity periods, and configurable timeout. Returns the message ID and network reference ID on success.",
+    description = "Sends an SMS message to the specified phone number. Supports flash messages (displayed immediately on the recipient's screen), custom validity periods, and configurable timeout. Messages are queued in a persistent outbox first, so if the modem is offline or the send fails it is retried with backoff. If send_at is a future unix timestamp, the message is scheduled instead. Returns the message ID and network reference ID if sent, otherwise the outbox ID and reason it's still queued, or the scheduled ID.",
     security(("api_key" = [])),
     request_body(
         content = crate::http::types::SendSmsRequest,
         example = json!({"to": "+1234567890", "content": "Hello! This is a test message.", "flash": true, "timeout": 10})
     ),
     responses(
-        (status = 200, body = crate::http::openapi::responses::SmsMessagesResponse)
+        (status = 200, body = crate::http::openapi::responses::SmsSendResponse)
     )
 ))]
 pub async fn sms_send(
     State(state): State<HttpState>,
     Json(payload): Json<crate::http::types::SendSmsRequest>,
-) -> HttpResult<sms_types::http::HttpSmsSendResponse> {
+) -> HttpResult<crate::http::types::SmsSendResult> {
     let address = PduAddress::from_str(&payload.to).map_err(|e| HttpError {
         status: StatusCode::BAD_REQUEST,
         message: e.to_string(),
@@ -288,7 +404,6 @@ pub async fn sms_send(
         _ => {}
     }
 
-    // Create and send outgoing SMS message, handling unexpected return type.
     let outgoing = sms_types::sms::SmsOutgoingMessage {
         to,
         content: payload.content,
@@ -296,23 +411,160 @@ pub async fn sms_send(
         validity_period: payload.validity_period,
         timeout: payload.timeout,
     };
-    let (message_id_opt, response) =
-        state
+
+    // Schedule the message instead if it should be sent in the future.
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    if let Some(send_at) = payload.send_at.filter(|send_at| u64::from(*send_at) > now) {
+        let scheduled_id = state
             .sms_manager
-            .send_sms(outgoing)
+            .schedule_sms(outgoing, send_at)
             .await
             .map_err(|e| HttpError {
                 status: StatusCode::INTERNAL_SERVER_ERROR,
                 message: e.to_string(),
             })?;
 
-    Ok(HttpSuccess(sms_types::http::HttpSmsSendResponse {
-        message_id: message_id_opt.ok_or_else(|| HttpError {
+        return Ok(HttpSuccess(crate::http::types::SmsSendResult::Scheduled {
+            scheduled_id,
+            send_at,
+        }));
+    }
+
+    // Queue outgoing SMS message, waiting for the first attempt if the modem is online.
+    let outcome = state
+        .sms_manager
+        .queue_sms(outgoing)
+        .await
+        .map_err(|e| HttpError {
             status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: "Message sent but no message ID returned".to_string(),
-        })?,
-        reference_id: modem_extract!(response => SendResult)?,
-    }))
+            message: e.to_string(),
+        })?;
+
+    match outcome {
+        OutboxOutcome::Sent {
+            message_id,
+            reference_id,
+        } => Ok(HttpSuccess(crate::http::types::SmsSendResult::Sent(
+            sms_types::http::HttpSmsSendResponse {
+                message_id,
+                reference_id,
+            },
+        ))),
+        OutboxOutcome::Queued { outbox_id, reason } => {
+            Ok(HttpSuccess(crate::http::types::SmsSendResult::Queued {
+                outbox_id,
+                reason,
+            }))
+        }
+        OutboxOutcome::Failed { reason } => Err(HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: reason,
+        }),
+    }
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/sms/scheduled/list",
+    tag = "SMS",
+    summary = "List scheduled messages",
+    description = "Retrieves all scheduled messages that are waiting for their send time. Once dispatched they are removed from this list, and a scheduled_dispatched event is sent. Supports optional pagination.",
+    security(("api_key" = [])),
+    request_body(
+        content = Option<crate::http::types::GlobalFetchRequest>,
+        example = json!({"limit": 50})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::ScheduledMessagesResponse)
+    )
+))]
+pub async fn sms_scheduled_list(
+    State(state): State<HttpState>,
+    Json(payload): Json<Option<crate::http::types::GlobalFetchRequest>>,
+) -> HttpResult<Vec<sms_types::sms::SmsScheduledMessage>> {
+    let (limit, offset, reverse) = match payload {
+        Some(req) => (req.limit, req.offset, req.reverse),
+        None => (None, None, false),
+    };
+
+    let scheduled = state
+        .sms_manager
+        .borrow_database()
+        .get_scheduled_messages(limit, offset, reverse)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(scheduled))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/sms/scheduled/cancel",
+    tag = "SMS",
+    summary = "Cancel scheduled message",
+    description = "Cancels a scheduled message before it is dispatched. Returns false if there is no pending scheduled message with the ID, eg: it has already been dispatched.",
+    security(("api_key" = [])),
+    request_body(
+        content = crate::http::types::ScheduledIdRequest,
+        example = json!({"scheduled_id": 3})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::BoolResponse,
+            example = json!({"success": true, "data": true}))
+    )
+))]
+pub async fn sms_scheduled_cancel(
+    State(state): State<HttpState>,
+    Json(payload): Json<crate::http::types::ScheduledIdRequest>,
+) -> HttpResult<bool> {
+    let cancelled = state
+        .sms_manager
+        .borrow_database()
+        .cancel_scheduled_message(payload.scheduled_id)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(cancelled))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/sms/scheduled/reschedule",
+    tag = "SMS",
+    summary = "Reschedule message",
+    description = "Changes the send time of a scheduled message to a new unix timestamp. A time in the past dispatches the message immediately. Returns false if there is no pending scheduled message with the ID.",
+    security(("api_key" = [])),
+    request_body(
+        content = crate::http::types::RescheduleSmsRequest,
+        example = json!({"scheduled_id": 3, "send_at": 1767225600})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::BoolResponse,
+            example = json!({"success": true, "data": true}))
+    )
+))]
+pub async fn sms_scheduled_reschedule(
+    State(state): State<HttpState>,
+    Json(payload): Json<crate::http::types::RescheduleSmsRequest>,
+) -> HttpResult<bool> {
+    let rescheduled = state
+        .sms_manager
+        .reschedule_sms(payload.scheduled_id, payload.send_at)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(rescheduled))
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(
@@ -322,16 +574,19 @@ pub async fn sms_send(
     summary = "Get network registration status",
     description = "Returns the current network registration status and technology type (e.g., 2G, 3G, 4G) for the modem.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::NetworkStatusResponse,
-            example = json!({"success": true, "data": {"registration": 0, "technology": 1}}))
+            example = json!({"success": true, "data": {"registration": 1, "technology": 7}}))
     )
 ))]
 pub async fn sms_get_network_status(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::http::HttpModemNetworkStatusResponse> {
     let (registration, technology) = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetNetworkStatus => NetworkStatus { registration, technology }
     )?;
     Ok(HttpSuccess(
@@ -349,6 +604,7 @@ pub async fn sms_get_network_status(
     summary = "Get signal strength",
     description = "Returns the current signal strength (RSSI) and bit error rate (BER) from the modem. RSSI values typically range from 0-31, with higher values indicating stronger signal.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::SignalStrengthResponse,
             example = json!({"success": true, "data": {"rssi": 17, "ber": 0}}))
@@ -356,9 +612,11 @@ pub async fn sms_get_network_status(
 ))]
 pub async fn sms_get_signal_strength(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::http::HttpModemSignalStrengthResponse> {
     let (rssi, ber) = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetSignalStrength => SignalStrength { rssi, ber }
     )?;
     Ok(HttpSuccess(
@@ -373,6 +631,7 @@ pub async fn sms_get_signal_strength(
     summary = "Get network operator",
     description = "Returns information about the currently connected network operator, including the operator name and connection status.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::NetworkOperatorResponse,
             example = json!({"success": true, "data": {"status": 0, "format": 0, "operator": "vodafone"}}))
@@ -380,9 +639,11 @@ pub async fn sms_get_signal_strength(
 ))]
 pub async fn sms_get_network_operator(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::http::HttpModemNetworkOperatorResponse> {
     let (status, format, operator) = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetNetworkOperator => NetworkOperator { status, format, operator }
     )?;
     Ok(HttpSuccess(
@@ -401,14 +662,19 @@ pub async fn sms_get_network_operator(
     summary = "Get service provider",
     description = "Returns the name of the SIM card's service provider (e.g., the mobile carrier name stored on the SIM).",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::StringResponse,
             example = json!({"success": true, "data": "ASDA Mobile"}))
     )
 ))]
-pub async fn sms_get_service_provider(State(state): State<HttpState>) -> HttpResult<String> {
+pub async fn sms_get_service_provider(
+    State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
+) -> HttpResult<String> {
     let service_provider = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetServiceProvider => ServiceProvider
     )?;
     Ok(HttpSuccess(service_provider))
@@ -421,6 +687,7 @@ pub async fn sms_get_service_provider(State(state): State<HttpState>) -> HttpRes
     summary = "Get battery level",
     description = "Returns the current battery status, charge percentage, and voltage of the modem device. Only applicable for battery-powered modems, usually for GNSS warm starts.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::BatteryLevelResponse,
             example = json!({"success": true, "data": {"status": 0, "charge": 71, "voltage": 3.972}}))
@@ -428,9 +695,11 @@ pub async fn sms_get_service_provider(State(state): State<HttpState>) -> HttpRes
 ))]
 pub async fn sms_get_battery_level(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::http::HttpModemBatteryLevelResponse> {
     let (status, charge, voltage) = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetBatteryLevel => BatteryLevel { status, charge, voltage }
     )?;
     Ok(HttpSuccess(
@@ -442,6 +711,37 @@ pub async fn sms_get_battery_level(
     ))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/sms/identity",
+    tag = "SMS",
+    summary = "Get modem and SIM identity",
+    description = "Returns the modem's IMEI, manufacturer, model and firmware, and the SIM's IMSI, ICCID and phone number. Any value the modem doesn't report is null, eg: the SIM identity without a SIM, or the phone number if the SIM doesn't store it.",
+    security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::IdentityResponse,
+            example = json!({"success": true, "data": {"imei": "867123456789012", "imsi": "234150000000001", "iccid": "8944150000000000001", "manufacturer": "SIMCOM INCORPORATED", "model": "SIMCOM_SIM7600G-H", "firmware": "LE20B04SIM7600G22", "phone_number": null}}))
+    )
+))]
+pub async fn sms_get_identity(
+    State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
+) -> HttpResult<sms_types::http::HttpModemIdentityResponse> {
+    let modem_id = query.modem_id.as_deref();
+    Ok(HttpSuccess(sms_types::http::HttpModemIdentityResponse {
+        imei: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetIMEI => Identity).ok(),
+        imsi: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetIMSI => Identity).ok(),
+        iccid: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetICCID => Identity).ok(),
+        manufacturer: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetManufacturer => Identity).ok(),
+        model: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetModel => Identity).ok(),
+        firmware: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetFirmwareVersion => Identity).ok(),
+        phone_number: modem_extract!(state.sms_manager, modem_id, ModemRequest::GetSubscriberNumber => SubscriberNumber)
+            .ok()
+            .flatten(),
+    }))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(
     get,
     path = "/sms/device-info",
@@ -449,32 +749,102 @@ pub async fn sms_get_battery_level(
     summary = "Get device information",
     description = "Returns all modem information, this is more efficient than requesting each individually.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::DeviceInfoResponse))
     )
 )]
 pub async fn sms_get_device_info(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::http::HttpSmsDeviceInfoResponse> {
     Ok(HttpSuccess(sms_types::http::HttpSmsDeviceInfoResponse {
         version: crate::VERSION.to_string(),
-        phone_number: state.config.phone_number.clone(),
-        service_provider: modem_extract!(state.sms_manager, ModemRequest::GetServiceProvider => ServiceProvider).ok(),
-        network_operator: modem_extract!(state.sms_manager, ModemRequest::GetNetworkOperator => NetworkOperator { status, format, operator })
+        phone_number: state.phone_number.get().cloned(),
+        service_provider: modem_extract!(state.sms_manager, query.modem_id.as_deref(), ModemRequest::GetServiceProvider => ServiceProvider).ok(),
+        network_operator: modem_extract!(state.sms_manager, query.modem_id.as_deref(), ModemRequest::GetNetworkOperator => NetworkOperator { status, format, operator })
             .ok()
             .map(|(status, format, operator)| sms_types::http::HttpModemNetworkOperatorResponse { status, format, operator }),
-        network_status: modem_extract!(state.sms_manager, ModemRequest::GetNetworkStatus => NetworkStatus { registration, technology })
+        network_status: modem_extract!(state.sms_manager, query.modem_id.as_deref(), ModemRequest::GetNetworkStatus => NetworkStatus { registration, technology })
             .ok()
             .map(|(registration, technology)| sms_types::http::HttpModemNetworkStatusResponse { registration, technology }),
-        battery: modem_extract!(state.sms_manager, ModemRequest::GetBatteryLevel => BatteryLevel { status, charge, voltage })
+        battery: modem_extract!(state.sms_manager, query.modem_id.as_deref(), ModemRequest::GetBatteryLevel => BatteryLevel { status, charge, voltage })
             .ok()
             .map(|(status, charge, voltage)| sms_types::http::HttpModemBatteryLevelResponse { status, charge, voltage }),
-        signal: modem_extract!(state.sms_manager, ModemRequest::GetSignalStrength => SignalStrength { rssi, ber })
+        signal: modem_extract!(state.sms_manager, query.modem_id.as_deref(), ModemRequest::GetSignalStrength => SignalStrength { rssi, ber })
             .ok()
             .map(|(rssi, ber)| sms_types::http::HttpModemSignalStrengthResponse { rssi, ber }),
     }))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/ussd/send",
+    tag = "USSD",
+    summary = "Send USSD code",
+    description = "Runs a USSD code (eg: *100# for a balance check) and waits for the network's reply. If the reply is a menu its status is 1, and the choice is sent as the next code in the same session. Only digits, *, # and + are allowed.",
+    security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
+    request_body(
+        content = crate::http::types::SendUssdRequest,
+        example = json!({"code": "*100#"})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::UssdResponse,
+            example = json!({"success": true, "data": {"status": 1, "text": "Balance: 5.00\n1. Bundles\n2. Exit"}}))
+    )
+))]
+pub async fn ussd_send(
+    State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
+    Json(payload): Json<crate::http::types::SendUssdRequest>,
+) -> HttpResult<sms_types::modem::UssdMessage> {
+    let code = payload.code.trim();
+    if code.is_empty()
+        || code.len() > 182
+        || !code
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '*' | '#' | '+'))
+    {
+        return Err(HttpError {
+            status: StatusCode::BAD_REQUEST,
+            message: "Invalid USSD code".to_string(),
+        });
+    }
+
+    let message = modem_extract!(
+        state.sms_manager,
+        query.modem_id.as_deref(),
+        ModemRequest::SendUSSD { code: code.to_string() } => USSDResult
+    )?;
+    Ok(HttpSuccess(message))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/ussd/cancel",
+    tag = "USSD",
+    summary = "Cancel USSD session",
+    description = "Ends the current USSD session, eg: to leave a menu without choosing an option.",
+    security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::BoolResponse,
+            example = json!({"success": true, "data": true}))
+    )
+))]
+pub async fn ussd_cancel(
+    State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
+) -> HttpResult<bool> {
+    modem_extract!(
+        state.sms_manager,
+        query.modem_id.as_deref(),
+        ModemRequest::CancelUSSD => USSDResult
+    )?;
+    Ok(HttpSuccess(true))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(
     get,
     path = "/gnss/status",
@@ -482,15 +852,18 @@ pub async fn sms_get_device_info(
     summary = "Get GNSS fix status",
     description = "Returns the current GNSS fix status, indicating whether a position fix has been acquired and the type of fix (e.g., no fix, 2D fix, 3D fix).",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::GnssFixStatusResponse)
     )
 ))]
 pub async fn gnss_get_status(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::gnss::FixStatus> {
     let fix_status = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetGNSSStatus => GNSSStatus
     )?;
     Ok(HttpSuccess(fix_status))
@@ -503,15 +876,18 @@ pub async fn gnss_get_status(
     summary = "Get GNSS location",
     description = "Returns the current GNSS position report. Requires a valid GNSS fix.",
     security(("api_key" = [])),
+    params(crate::http::types::ModemQuery),
     responses(
         (status = 200, body = crate::http::openapi::responses::GnssPositionResponse)
     )
 ))]
 pub async fn gnss_get_location(
     State(state): State<HttpState>,
+    Query(query): Query<crate::http::types::ModemQuery>,
 ) -> HttpResult<sms_types::gnss::PositionReport> {
     let position_report = modem_extract!(
         state.sms_manager,
+        query.modem_id.as_deref(),
         ModemRequest::GetGNSSLocation => GNSSLocation
     )?;
     Ok(HttpSuccess(position_report))
@@ -538,7 +914,7 @@ pub async fn sys_version(State(_state): State<HttpState>) -> HttpResult<String>
     path = "/sys/phone-number",
     tag = "System",
     summary = "Get configured phone number",
-    description = "Returns the phone number configured for this SMS server, if one has been set in the configuration.",
+    description = "Returns the phone number configured for this SMS server. If none is configured, this is the first modem's own number once it's online, if its SIM stores it.",
     security(("api_key" = [])),
     responses(
         (status = 200, description = "System phone number retrieved successfully", body = crate::http::openapi::responses::OptionalStringResponse,
@@ -546,7 +922,41 @@ pub async fn sys_version(State(_state): State<HttpState>) -> HttpResult<String>
     )
 ))]
 pub async fn sys_phone_number(State(state): State<HttpState>) -> HttpResult<Option<String>> {
-    Ok(HttpSuccess(state.config.phone_number.clone()))
+    Ok(HttpSuccess(state.phone_number.get().cloned()))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/sys/modems",
+    tag = "System",
+    summary = "List modems",
+    description = "Returns every configured modem, its current status and how busy its command queue is, in config order. The first modem is used for commands that don't specify a modem_id.",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::ModemsResponse,
+            example = json!({"success": true, "data": [{"id": "default", "status": "Online", "queued": 0, "queue_wait_ms": 12}]}))
+    )
+))]
+pub async fn sys_modems(
+    State(state): State<HttpState>,
+) -> HttpResult<Vec<crate::http::types::ModemInfo>> {
+    let modems = state
+        .sms_manager
+        .borrow_modems()
+        .modems()
+        .iter()
+        .map(|modem| {
+            let queue = modem.sender.queue_stats();
+            crate::http::types::ModemInfo {
+                id: modem.id.to_string(),
+                status: modem.sender.status().into(),
+                queued: queue.depth,
+                queue_wait_ms: queue.last_wait.as_millis() as u64,
+            }
+        })
+        .collect();
+
+    Ok(HttpSuccess(modems))
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(
@@ -587,6 +997,76 @@ pub async fn sys_set_log_level(
     Ok(HttpSuccess(success))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/webhooks/dead-letters/list",
+    tag = "Webhooks",
+    summary = "List dead-lettered webhook deliveries",
+    description = "Retrieves webhook deliveries that failed every retry attempt, with the event that was being sent and the last error. Supports optional pagination.",
+    security(("api_key" = [])),
+    request_body(
+        content = Option<crate::http::types::GlobalFetchRequest>,
+        example = json!({"limit": 50})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::WebhookDeadLettersResponse)
+    )
+))]
+pub async fn webhooks_dead_letters_list(
+    State(state): State<HttpState>,
+    Json(payload): Json<Option<crate::http::types::GlobalFetchRequest>>,
+) -> HttpResult<Vec<crate::webhooks::WebhookDeadLetter>> {
+    let (limit, offset, reverse) = match payload {
+        Some(req) => (req.limit, req.offset, req.reverse),
+        None => (None, None, false),
+    };
+
+    let dead_letters = state
+        .sms_manager
+        .borrow_database()
+        .get_webhook_dead_letters(limit, offset, reverse)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(dead_letters))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/webhooks/dead-letters/replay",
+    tag = "Webhooks",
+    summary = "Replay dead-lettered webhook delivery",
+    description = "Moves a dead-lettered delivery back into the retry queue, where it is sent again shortly with a fresh set of attempts. Returns false if there is no dead-letter with the ID.",
+    security(("api_key" = [])),
+    request_body(
+        content = crate::http::types::DeadLetterIdRequest,
+        example = json!({"dead_letter_id": 7})
+    ),
+    responses(
+        (status = 200, body = crate::http::openapi::responses::BoolResponse,
+            example = json!({"success": true, "data": true}))
+    )
+))]
+pub async fn webhooks_dead_letters_replay(
+    State(state): State<HttpState>,
+    Json(payload): Json<crate::http::types::DeadLetterIdRequest>,
+) -> HttpResult<bool> {
+    let replayed = state
+        .sms_manager
+        .borrow_database()
+        .replay_webhook_dead_letter(payload.dead_letter_id)
+        .await
+        .map_err(|e| HttpError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+
+    Ok(HttpSuccess(replayed))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(
     get,
     path = "/ws",
@@ -65,6 +65,30 @@ pub struct PhoneNumberFetchRequest {
     pub reverse: bool,
 }
 
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchMessagesRequest {
+    pub query: String,
+
+    #[serde(default)]
+    pub phone_number: Option<String>,
+
+    #[serde(default)]
+    pub limit: Option<u64>,
+
+    #[serde(default)]
+    pub offset: Option<u64>,
+
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MessageIdRequest {
+    pub message_id: i64,
+}
+
 #[derive(Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct MessageIdFetchRequest {
@@ -107,6 +131,38 @@ pub struct SendSmsRequest {
 
     #[serde(default)]
     pub timeout: Option<u32>,
+
+    #[serde(default)]
+    pub send_at: Option<u32>,
+}
+
+/// Either the sent message, or where it's waiting if it couldn't (or shouldn't) be sent yet.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum SmsSendResult {
+    Sent(sms_types::http::HttpSmsSendResponse),
+    Queued { outbox_id: i64, reason: String },
+    Scheduled { scheduled_id: i64, send_at: u32 },
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScheduledIdRequest {
+    pub scheduled_id: i64,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RescheduleSmsRequest {
+    pub scheduled_id: i64,
+    pub send_at: u32,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeadLetterIdRequest {
+    pub dead_letter_id: i64,
 }
 
 #[derive(Deserialize)]
@@ -128,6 +184,32 @@ pub struct GetFriendlyNameRequest {
     pub phone_number: String,
 }
 
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SendUssdRequest {
+    pub code: String,
+}
+
+/// Selects which modem a command is sent to, the first configured modem if omitted.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ModemQuery {
+    pub modem_id: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModemInfo {
+    pub id: String,
+    pub status: sms_types::modem::ModemStatusUpdateState,
+
+    /// Commands waiting for the modem.
+    pub queued: usize,
+
+    /// How long the last command to start waited for the modem, in milliseconds.
+    pub queue_wait_ms: u64,
+}
+
 #[derive(Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
 pub struct WebSocketQuery {
@@ -189,7 +271,8 @@ mod websocket_query_tests {
         // All valid event types
         let query = WebSocketQuery {
             events: Some(
-                "incoming,outgoing,delivery,modem_status_update,gnss_position_report".to_string(),
+                "incoming,outgoing,delivery,modem_status_update,gnss_position_report,scheduled_dispatched,incoming_ussd,incoming_call,network_status_change,incomplete_message"
+                    .to_string(),
             ),
         };
         assert_eq!(query.get_event_types(), None);
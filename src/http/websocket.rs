@@ -8,7 +8,7 @@ use tracing::log::{debug, error, warn};
 use uuid::Uuid;
 
 pub type WebSocketConnection = (axum::extract::ws::WebSocket, Option<Vec<EventKind>>);
-type StoredConnection = (UnboundedSender<axum::extract::ws::Utf8Bytes>, u8); // sender + event mask
+type StoredConnection = (UnboundedSender<axum::extract::ws::Utf8Bytes>, u16); // sender + event mask
 
 #[derive(Clone)]
 pub struct WebSocketManager {
@@ -111,6 +111,7 @@ pub async fn handle_websocket(connection: WebSocketConnection, manager: WebSocke
                 Ok(axum::extract::ws::Message::Text(text)) => {
                     debug!("Received WebSocket message from {connection_id}: {text:?}")
                 }
+                #[allow(clippy::collapsible_match)]
                 Ok(axum::extract::ws::Message::Ping(ping)) => {
                     if ping_tx.send(ping).is_err() {
                         break;
@@ -1,4 +1,5 @@
 mod app;
+mod backoff;
 mod config;
 mod events;
 mod modem;
@@ -10,7 +11,7 @@ mod http;
 
 use crate::app::AppHandles;
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use std::path::PathBuf;
 use tracing::log::info;
@@ -27,6 +28,19 @@ const VERSION: &str = env!("VERSION");
 struct CliArguments {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Re-encrypt all stored content with the active encryption key, then exit.
+    Reencrypt {
+        /// Rows to re-encrypt per transaction.
+        #[arg(long, default_value_t = 500)]
+        batch_size: u32,
+    },
 }
 
 #[cfg(feature = "sentry")]
@@ -94,6 +108,22 @@ fn main() -> Result<()> {
     let args = CliArguments::parse();
     let config = config::AppConfig::load(args.config)?;
 
+    if let Some(CliCommand::Reencrypt { batch_size }) = args.command {
+        return tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(async move {
+                let database =
+                    sms::SMSDatabase::connect(&config.database, config.modem_ids()).await?;
+                let total = database.reencrypt_all(batch_size.max(1)).await?;
+                info!(
+                    "Re-encrypted {total} rows with encryption key id {}",
+                    config.database.encryption_key_id
+                );
+                Ok(())
+            });
+    }
+
     #[cfg(feature = "sentry")]
     let _sentry_guard = config.sentry.as_ref().map(init_sentry).transpose()?;
 
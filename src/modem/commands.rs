@@ -1,7 +1,8 @@
+use crate::modem::parsers::is_cusd_complete;
 use crate::modem::types::{ModemRequest, ModemResponse};
 use anyhow::{anyhow, bail, Result};
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::oneshot;
 use tracing::log::debug;
 
@@ -19,13 +20,17 @@ pub struct CommandContext {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum CommandState {
     WaitingForOk,
     WaitingForPrompt,
     WaitingForData,
+
+    /// The network replies to USSD asynchronously with +CUSD, after the OK.
+    WaitingForUssd,
 }
 impl CommandState {
-    pub fn is_complete(&self, content: &str) -> bool {
+    pub fn is_complete(&self, content: &str, response_buffer: &str) -> bool {
         match self {
             CommandState::WaitingForOk => {
                 content == "OK"
@@ -35,17 +40,40 @@ impl CommandState {
             }
             CommandState::WaitingForPrompt => false,
             CommandState::WaitingForData => {
-                // For SMS, look for the confirmation
-                content.starts_with("+CMGS:") || content == "OK" || content == "ERROR"
+                // For SMS, look for the confirmation. Commands that need a SIM fail with +CME ERROR.
+                content.starts_with("+CMGS:")
+                    || content == "OK"
+                    || content == "ERROR"
+                    || content.starts_with("+CME ERROR:")
+            }
+            CommandState::WaitingForUssd => {
+                content == "ERROR"
+                    || content.starts_with("+CME ERROR:")
+                    || is_cusd_complete(response_buffer)
             }
         }
     }
 }
 
+/// The queue lane a command waits in, highest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandPriority {
+    /// Status queries and other requests someone is waiting on.
+    Interactive,
+
+    /// Single part messages.
+    Single,
+
+    /// Parts of multipart messages, which can hold the modem for a long time.
+    Bulk,
+}
+
 #[derive(Debug)]
 pub struct OutgoingCommand {
     pub sequence: u32,
     pub request: ModemRequest,
+    pub priority: CommandPriority,
+    pub queued_at: Instant,
     timeout: Option<u32>,
     response_tx: Option<oneshot::Sender<ModemResponse>>,
 }
@@ -54,11 +82,14 @@ impl OutgoingCommand {
         sequence: u32,
         response_tx: oneshot::Sender<ModemResponse>,
         request: ModemRequest,
+        priority: CommandPriority,
         timeout: Option<u32>,
     ) -> Self {
         Self {
             sequence,
             request,
+            priority,
+            queued_at: Instant::now(),
             timeout,
             response_tx: Some(response_tx),
         }
@@ -1,3 +1,4 @@
+use crate::config::ModemProfile;
 use crate::modem::commands::CommandState;
 use crate::modem::parsers::*;
 use crate::modem::types::{
@@ -5,8 +6,12 @@ use crate::modem::types::{
 };
 use crate::modem::worker::WorkerEvent;
 use anyhow::{bail, Context, Result};
-use sms_pdu::pdu::{DeliverPdu, StatusReportPdu};
+use sms_pdu::gsm_encoding::{DecodedMessage, GsmMessageData};
+use sms_pdu::pdu::{DeliverPdu, MessageEncoding, StatusReportPdu};
+use sms_types::modem::UssdMessage;
 use sms_types::sms::{SmsIncomingMessage, SmsMultipartHeader, SmsPartialDeliveryReport};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::log::{debug, warn};
 
@@ -19,6 +24,58 @@ fn get_real_number(phone_number: String) -> String {
     }
 }
 
+/// Decode the user data of a message, working around sms-pdu using 7 fill bits instead of none when
+/// a 7-bit message's header ends on a septet boundary (eg: a 16-bit concatenation reference), which
+/// loses the first character of every part. The text is decoded again without the header instead.
+fn decode_message_data(data: GsmMessageData) -> Result<DecodedMessage> {
+    let mut msg = data.decode_message().map_err(anyhow::Error::msg)?;
+
+    let header_len = data.bytes.first().map_or(0, |len| usize::from(*len) + 1);
+    if data.udh
+        && data.encoding == MessageEncoding::Gsm7Bit
+        && header_len.is_multiple_of(7)
+        && header_len <= data.bytes.len()
+    {
+        let header_septets = u8::try_from(header_len * 8 / 7).unwrap_or(u8::MAX);
+        let text_data = GsmMessageData {
+            encoding: data.encoding,
+            udh: false,
+            bytes: data.bytes[header_len..].to_vec(),
+            user_data_len: data.user_data_len.saturating_sub(header_septets),
+        };
+        msg.text = text_data.decode_message().map_err(anyhow::Error::msg)?.text;
+    }
+    Ok(msg)
+}
+
+/// Decode a hex SMS-DELIVER PDU, from either a +CMT URC or a message listed from modem storage.
+pub fn decode_incoming_sms(content: &str) -> Result<SmsIncomingMessage> {
+    let content_hex = hex::decode(content).context("Failed to decode IncomingSMS hex content")?;
+    let deliver_pdu = DeliverPdu::try_from(content_hex.as_slice()).map_err(anyhow::Error::msg)?;
+
+    let msg = decode_message_data(deliver_pdu.get_message_data())?;
+
+    // Find multipart component (8-bit 0x00 or 16-bit 0x08 reference), convert into a SmsMultipartHeader.
+    let user_data_header = msg
+        .udh
+        .and_then(|udh| {
+            udh.components
+                .into_iter()
+                .find(|c| c.id == 0x00 || c.id == 0x08)
+        })
+        .map(|component| SmsMultipartHeader::try_from(component.data))
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+
+    Ok(SmsIncomingMessage {
+        phone_number: get_real_number(deliver_pdu.originating_address.to_string()),
+        user_data_header,
+        content: msg.text,
+        sent_at: smsc_timestamp_to_unix(&deliver_pdu.scts),
+        raw_pdu: Some(content.to_string()),
+    })
+}
+
 /// Guarantee the terminator is always present at compile-time.
 macro_rules! at_cmd {
     ($cmd:expr) => {
@@ -29,12 +86,22 @@ macro_rules! at_cmd {
     }};
 }
 
+/// The network repeats RING and +CLIP every few seconds while a call is ringing,
+/// so any repeat of the same caller within this gap is treated as the same call.
+const CALL_RING_GAP: Duration = Duration::from_secs(10);
+
 pub struct ModemEventHandlers {
     worker_event_tx: mpsc::UnboundedSender<WorkerEvent>,
+    profile: ModemProfile,
+    last_call: Mutex<Option<(Option<String>, Instant)>>,
 }
 impl ModemEventHandlers {
-    pub fn new(worker_event_tx: mpsc::UnboundedSender<WorkerEvent>) -> Self {
-        Self { worker_event_tx }
+    pub fn new(worker_event_tx: mpsc::UnboundedSender<WorkerEvent>, profile: ModemProfile) -> Self {
+        Self {
+            worker_event_tx,
+            profile,
+            last_call: Mutex::new(None),
+        }
     }
 
     pub async fn command_sender(&self, request: &ModemRequest) -> Result<CommandState> {
@@ -49,8 +116,29 @@ impl ModemEventHandlers {
             ModemRequest::GetNetworkOperator => self.write(at_cmd!("AT+COPS?")).await?,
             ModemRequest::GetServiceProvider => self.write(at_cmd!("AT+CSPN?")).await?,
             ModemRequest::GetBatteryLevel => self.write(at_cmd!("AT+CBC")).await?,
-            ModemRequest::GetGNSSStatus => self.write(at_cmd!("AT+CGPSSTATUS?")).await?,
-            ModemRequest::GetGNSSLocation => self.write(at_cmd!("AT+CGNSINF")).await?,
+            ModemRequest::GetIMEI => self.write(at_cmd!("AT+CGSN")).await?,
+            ModemRequest::GetIMSI => self.write(at_cmd!("AT+CIMI")).await?,
+            ModemRequest::GetICCID => self.write(at_cmd!("AT+CCID")).await?,
+            ModemRequest::GetManufacturer => self.write(at_cmd!("AT+CGMI")).await?,
+            ModemRequest::GetModel => self.write(at_cmd!("AT+CGMM")).await?,
+            ModemRequest::GetFirmwareVersion => self.write(at_cmd!("AT+CGMR")).await?,
+            ModemRequest::GetSubscriberNumber => self.write(at_cmd!("AT+CNUM")).await?,
+            ModemRequest::SendUSSD { code } => {
+                let command = at_cmd!("AT+CUSD=1,\"{}\",15", code);
+                self.write(command.as_bytes()).await?;
+                return Ok(CommandState::WaitingForUssd);
+            }
+            ModemRequest::CancelUSSD => self.write(at_cmd!("AT+CUSD=2")).await?,
+            ModemRequest::HangUp => self.write(at_cmd!("ATH")).await?,
+            ModemRequest::Probe => self.write(at_cmd!("AT")).await?,
+            ModemRequest::GetGNSSStatus => {
+                let command = at_cmd!("{}", self.profile.gnss_status_command());
+                self.write(command.as_bytes()).await?
+            }
+            ModemRequest::GetGNSSLocation => {
+                let command = at_cmd!("{}", self.profile.gnss_location_command());
+                self.write(command.as_bytes()).await?
+            }
         }
         Ok(CommandState::WaitingForData)
     }
@@ -80,32 +168,9 @@ impl ModemEventHandlers {
         debug!("UnsolicitedMessage: {:?} -> {:?}", &message_kind, &content);
 
         match message_kind {
-            UnsolicitedMessageKind::IncomingSMS => {
-                let content_hex =
-                    hex::decode(content).context("Failed to decode IncomingSMS hex content")?;
-                let deliver_pdu =
-                    DeliverPdu::try_from(content_hex.as_slice()).map_err(anyhow::Error::msg)?;
-
-                let msg = deliver_pdu
-                    .get_message_data()
-                    .decode_message()
-                    .map_err(anyhow::Error::msg)?;
-
-                // Find multipart component, convert into a SmsMultipartHeader.
-                let user_data_header = msg
-                    .udh
-                    .and_then(|udh| udh.components.into_iter().find(|c| c.id == 0x00))
-                    .map(|component| SmsMultipartHeader::try_from(component.data))
-                    .transpose()
-                    .map_err(anyhow::Error::msg)?;
-
-                let incoming = SmsIncomingMessage {
-                    phone_number: get_real_number(deliver_pdu.originating_address.to_string()),
-                    user_data_header,
-                    content: msg.text,
-                };
-                Ok(Some(ModemIncomingMessage::IncomingSMS(incoming)))
-            }
+            UnsolicitedMessageKind::IncomingSMS => Ok(Some(ModemIncomingMessage::IncomingSMS(
+                decode_incoming_sms(content)?,
+            ))),
             UnsolicitedMessageKind::DeliveryReport => {
                 let content_hex = hex::decode(content).map_err(anyhow::Error::msg)?;
                 let status_report_pdu = StatusReportPdu::try_from(content_hex.as_slice())
@@ -115,20 +180,40 @@ impl ModemEventHandlers {
                     status: status_report_pdu.status as u8,
                     phone_number: get_real_number(status_report_pdu.recipient_address.to_string()),
                     reference_id: status_report_pdu.message_reference,
+                    submitted_at: smsc_timestamp_to_unix(&status_report_pdu.scts),
+                    discharged_at: smsc_timestamp_to_unix(&status_report_pdu.discharge_time),
                 };
                 Ok(Some(ModemIncomingMessage::DeliveryReport(report)))
             }
-            UnsolicitedMessageKind::NetworkStatusChange => {
-                Ok(Some(ModemIncomingMessage::NetworkStatusChange(0)))
-            }
+            UnsolicitedMessageKind::NetworkStatusChange => Ok(Some(
+                ModemIncomingMessage::NetworkStatusChange(parse_registration_urc(content)?),
+            )),
             UnsolicitedMessageKind::ShuttingDown => {
                 warn!("The modem is shutting down!");
                 self.set_status(ModemStatus::ShuttingDown).await?;
                 Ok(None)
             }
-            UnsolicitedMessageKind::GNSSPositionReport => Ok(Some(
-                ModemIncomingMessage::GNSSPositionReport(parse_cgnsinf_response(content, true)?),
-            )),
+            UnsolicitedMessageKind::GNSSPositionReport => {
+                Ok(Some(ModemIncomingMessage::GNSSPositionReport(
+                    self.profile.parse_gnss_location(content, true)?,
+                )))
+            }
+            UnsolicitedMessageKind::IncomingUSSD => Ok(Some(ModemIncomingMessage::IncomingUSSD(
+                parse_cusd_response(content)?,
+            ))),
+            UnsolicitedMessageKind::Ring => {
+                // The caller is reported by the +CLIP that follows.
+                debug!("The modem is ringing");
+                Ok(None)
+            }
+            UnsolicitedMessageKind::IncomingCall => {
+                let phone_number = parse_clip_response(content)?.map(get_real_number);
+                if self.is_new_call(&phone_number) {
+                    Ok(Some(ModemIncomingMessage::IncomingCall(phone_number)))
+                } else {
+                    Ok(None)
+                }
+            }
         }
     }
 
@@ -138,7 +223,13 @@ impl ModemEventHandlers {
         response: &String,
     ) -> Result<ModemResponse> {
         debug!("Command response: {request:?} -> {response:?}");
-        if !response.trim_end().ends_with("OK") {
+        // The network's USSD reply usually arrives after the OK, and some modems
+        // report having no GNSS fix as an error.
+        let allows_error = matches!(
+            request,
+            ModemRequest::SendUSSD { .. } | ModemRequest::GetGNSSStatus
+        );
+        if !allows_error && !response.trim_end().ends_with("OK") {
             bail!("Modem response does not end with OK");
         }
 
@@ -176,15 +267,48 @@ impl ModemEventHandlers {
                     voltage,
                 })
             }
+            ModemRequest::GetIMEI
+            | ModemRequest::GetIMSI
+            | ModemRequest::GetICCID
+            | ModemRequest::GetManufacturer
+            | ModemRequest::GetModel
+            | ModemRequest::GetFirmwareVersion => Ok(ModemResponse::Identity(
+                parse_identity_response(response, request.expected_response_prefix(self.profile))?,
+            )),
+            ModemRequest::GetSubscriberNumber => Ok(ModemResponse::SubscriberNumber(
+                parse_cnum_response(response)?,
+            )),
+            ModemRequest::SendUSSD { .. } => Ok(ModemResponse::USSDResult(
+                parse_cusd_response(response)
+                    .with_context(|| format!("USSD request failed: {}", response.trim()))?,
+            )),
+            ModemRequest::CancelUSSD => Ok(ModemResponse::USSDResult(UssdMessage {
+                status: 2,
+                text: None,
+            })),
+            ModemRequest::HangUp => Ok(ModemResponse::CallEnded),
+            ModemRequest::Probe => Ok(ModemResponse::Alive),
             ModemRequest::GetGNSSStatus => Ok(ModemResponse::GNSSStatus(
-                parse_cgpsstatus_response(response)?,
+                self.profile.parse_gnss_status(response)?,
             )),
             ModemRequest::GetGNSSLocation => Ok(ModemResponse::GNSSLocation(
-                parse_cgnsinf_response(response, false)?,
+                self.profile.parse_gnss_location(response, false)?,
             )),
         }
     }
 
+    /// Record a caller ID notification, returning false if it's another ring of the last call.
+    fn is_new_call(&self, phone_number: &Option<String>) -> bool {
+        let now = Instant::now();
+        let mut last_call = self.last_call.lock().unwrap_or_else(|e| e.into_inner());
+        let is_repeat = last_call.as_ref().is_some_and(|(last_number, last_ring)| {
+            last_number == phone_number && now.duration_since(*last_ring) < CALL_RING_GAP
+        });
+
+        *last_call = Some((phone_number.clone(), now));
+        !is_repeat
+    }
+
     async fn write(&self, data: &[u8]) -> Result<()> {
         self.worker_event_tx
             .send(WorkerEvent::WriteCommand(data.to_vec()))
@@ -1,63 +1,96 @@
-use crate::config::{AppConfig, ModemConfig};
+use crate::config::ModemConfig;
 use crate::modem::commands::OutgoingCommand;
+use crate::modem::port::open_port;
 use crate::modem::sender::ModemSender;
-use crate::modem::types::ModemIncomingMessage;
+use crate::modem::types::{ModemMessage, ModemStatus, QueueStats};
 use crate::modem::worker::ModemWorker;
-use anyhow::{anyhow, Context, Result};
-use tokio::sync::mpsc;
-use tokio_serial::SerialPortBuilderExt;
+use anyhow::{anyhow, Result};
+use tokio::sync::{mpsc, watch};
 use tracing::log::error;
 
 mod buffer;
 mod commands;
-mod handlers;
+pub(crate) mod handlers;
 mod parsers;
+mod port;
+mod profile;
+mod queue;
 pub mod sender;
+mod simulator;
 mod state_machine;
 pub mod types;
 mod worker;
 
 pub struct ModemManager {
     config: ModemConfig,
-    main_tx: mpsc::UnboundedSender<ModemIncomingMessage>,
+    main_tx: mpsc::UnboundedSender<ModemMessage>,
     command_tx: Option<mpsc::Sender<OutgoingCommand>>,
+    command_rx: Option<mpsc::Receiver<OutgoingCommand>>,
+    status_tx: Option<watch::Sender<ModemStatus>>,
+    status_rx: Option<watch::Receiver<ModemStatus>>,
+    queue_tx: Option<watch::Sender<QueueStats>>,
+    queue_rx: Option<watch::Receiver<QueueStats>>,
 }
 impl ModemManager {
-    pub fn new(config: &AppConfig) -> (Self, mpsc::UnboundedReceiver<ModemIncomingMessage>) {
-        let (main_tx, main_rx) = mpsc::unbounded_channel();
-        let manager = Self {
-            config: config.modem.clone(),
-            main_tx,
-            command_tx: None,
-        };
+    /// Create a manager for a single modem, all modems share the same main_tx.
+    /// The sender is available straight away, commands queue until the modem is started.
+    pub fn new(config: ModemConfig, main_tx: mpsc::UnboundedSender<ModemMessage>) -> Self {
+        let (command_tx, command_rx) = mpsc::channel(config.cmd_channel_buffer_size);
+        let (status_tx, status_rx) = watch::channel(ModemStatus::Startup);
+        let (queue_tx, queue_rx) = watch::channel(QueueStats::default());
 
-        (manager, main_rx)
+        Self {
+            config,
+            main_tx,
+            command_tx: Some(command_tx),
+            command_rx: Some(command_rx),
+            status_tx: Some(status_tx),
+            status_rx: Some(status_rx),
+            queue_tx: Some(queue_tx),
+            queue_rx: Some(queue_rx),
+        }
     }
 
     pub async fn start(&mut self) -> Result<tokio::task::JoinHandle<()>> {
-        let (command_tx, command_rx) = mpsc::channel(self.config.cmd_channel_buffer_size);
-        self.command_tx = Some(command_tx);
+        let (Some(command_rx), Some(status_tx), Some(queue_tx)) = (
+            self.command_rx.take(),
+            self.status_tx.take(),
+            self.queue_tx.take(),
+        ) else {
+            return Err(anyhow!("ModemManager has already been started!"));
+        };
 
-        let port = tokio_serial::new(&self.config.device, self.config.baud_rate)
-            .open_native_async()
-            .context("Failed to open serial port!")?;
+        let port = open_port(&self.config)?;
 
-        let worker = ModemWorker::new(port, self.main_tx.clone(), self.config.clone())?;
+        let id = self.config.id.clone();
+        let worker = ModemWorker::new(
+            port,
+            self.main_tx.clone(),
+            status_tx,
+            queue_tx,
+            self.config.clone(),
+        )?;
         let handle = tokio::spawn(async move {
             if let Err(e) = worker.initialize_and_run(command_rx).await {
-                error!("ModemWorker error: {e}");
+                error!("ModemWorker '{id}' error: {e}");
             }
-            error!("ModemWorker exit");
+            error!("ModemWorker '{id}' exit");
         });
 
         Ok(handle)
     }
 
     pub fn get_sender(&mut self) -> Result<ModemSender> {
-        if let Some(command_tx) = self.command_tx.take() {
-            Ok(ModemSender::new(command_tx))
+        if let (Some(command_tx), Some(status_rx), Some(queue_rx)) = (
+            self.command_tx.take(),
+            self.status_rx.take(),
+            self.queue_rx.take(),
+        ) {
+            Ok(ModemSender::new(command_tx, status_rx, queue_rx))
         } else {
-            Err(anyhow!("Could not get ModemSender, command_tx channel has already been taken or the modem hasn't been started!"))
+            Err(anyhow!(
+                "Could not get ModemSender, command_tx channel has already been taken!"
+            ))
         }
     }
 }
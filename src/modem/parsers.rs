@@ -1,5 +1,7 @@
 use anyhow::{anyhow, Result};
+use sms_pdu::pdu::SmscTimestamp;
 use sms_types::gnss::{FixStatus, PositionReport};
+use sms_types::modem::{NetworkRegistration, RegistrationDomain, SimStatus, UssdMessage};
 
 pub fn parse_cmgs_result(response: &str) -> Result<u8> {
     let cmgs_line = response
@@ -16,34 +18,63 @@ pub fn parse_cmgs_result(response: &str) -> Result<u8> {
         .map_err(|_| anyhow!("Invalid CMGS message reference number"))
 }
 
-pub fn parse_creg_response(response: &str) -> Result<(u8, u8)> {
+/// Parse an AT+CREG? reply: `<n>,<stat>[,<lac>,<ci>[,<AcT>]]`, where `<n>` is the notification
+/// mode set with AT+CREG=<n>. Returns the registration status and access technology, if reported.
+pub fn parse_creg_response(response: &str) -> Result<(u8, Option<u8>)> {
     let creg_line = response
         .lines()
         .find(|line| line.trim().starts_with("+CREG:"))
         .ok_or_else(|| anyhow!("No CREG response found in buffer"))?;
 
-    let data = creg_line
+    // Past the mode, the reply is the same as a notification.
+    let (_mode, registration) = creg_line
         .trim()
-        .strip_prefix("+CREG:")
-        .ok_or_else(|| anyhow!("Malformed CREG response"))?
-        .trim();
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Missing registration status"))?;
+    let registration = parse_registration_urc(&format!("+CREG:{registration}"))?;
 
-    let mut parts = data.split(',');
-    let registration: u8 = parts
-        .next()
-        .ok_or_else(|| anyhow!("Missing registration status"))?
-        .trim()
-        .parse()
-        .map_err(|_| anyhow!("Invalid registration status"))?;
+    Ok((registration.status, registration.technology))
+}
 
-    let technology: u8 = parts
+/// Parse a +CREG, +CGREG or +CEREG registration notification, as enabled with mode 2:
+/// `<stat>[,<lac>,<ci>[,<AcT>]]`, where anything after the status is only given when registered.
+/// For +CEREG the location area code is the tracking area code.
+pub fn parse_registration_urc(content: &str) -> Result<NetworkRegistration> {
+    let line = content.trim();
+    let (domain, data) = if let Some(data) = line.strip_prefix("+CREG:") {
+        (RegistrationDomain::CircuitSwitched, data)
+    } else if let Some(data) = line.strip_prefix("+CGREG:") {
+        (RegistrationDomain::PacketSwitched, data)
+    } else if let Some(data) = line.strip_prefix("+CEREG:") {
+        (RegistrationDomain::Lte, data)
+    } else {
+        return Err(anyhow!("No CREG, CGREG or CEREG notification found"));
+    };
+
+    let mut fields = data.split(',').map(|field| field.trim().trim_matches('"'));
+    let status = fields
         .next()
-        .ok_or_else(|| anyhow!("Missing technology status"))?
-        .trim()
-        .parse()
-        .map_err(|_| anyhow!("Invalid technology status"))?;
-
-    Ok((registration, technology))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid registration status"))?;
+
+    let mut next_field = || fields.next().filter(|field| !field.is_empty());
+    let lac = next_field().map(str::to_string);
+    let cell_id = next_field().map(str::to_string);
+    let technology = next_field()
+        .map(|technology| {
+            technology
+                .parse()
+                .map_err(|_| anyhow!("Invalid access technology"))
+        })
+        .transpose()?;
+
+    Ok(NetworkRegistration {
+        domain,
+        status,
+        lac,
+        cell_id,
+        technology,
+    })
 }
 
 pub fn parse_csq_response(response: &str) -> Result<(i32, i32)> {
@@ -209,6 +240,344 @@ pub fn parse_cgnsinf_response(response: &str, unsolicited: bool) -> Result<Posit
     PositionReport::try_from(fields).map_err(anyhow::Error::msg)
 }
 
+/// Convert a ddmmyy date and hhmmss.sss time into the yyyyMMddhhmmss.sss format used by CGNSINF.
+fn gnss_utc_time(date: &str, time: &str) -> Result<String> {
+    if date.len() != 6 || !date.is_ascii() {
+        return Err(anyhow!("Invalid GNSS date: {date}"));
+    }
+    Ok(format!(
+        "20{}{}{}{time}",
+        &date[4..6],
+        &date[2..4],
+        &date[0..2]
+    ))
+}
+
+/// Parse a ddmm.mmmm (or dddmm.mmmm) coordinate with its hemisphere into decimal degrees.
+fn parse_nmea_coordinate(value: &str, hemisphere: &str) -> Result<f64> {
+    let value: f64 = value
+        .parse()
+        .map_err(|_| anyhow!("Invalid GNSS coordinate: {value}"))?;
+
+    let degrees = (value / 100.0).trunc() + (value % 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Ok(degrees),
+        "S" | "W" => Ok(-degrees),
+        _ => Err(anyhow!("Invalid GNSS hemisphere: {hemisphere}")),
+    }
+}
+
+/// Parse a SIMCom LTE +CGPSINFO response or notification:
+/// `<lat>,<N/S>,<lon>,<E/W>,<date>,<UTC time>,<alt>,<speed>,<course>`, which is empty without a fix.
+pub fn parse_cgpsinfo_response(response: &str) -> Result<PositionReport> {
+    let cgpsinfo_line = response
+        .lines()
+        .find(|line| line.trim().starts_with("+CGPSINFO:"))
+        .ok_or_else(|| anyhow!("No CGPSINFO response found in buffer"))?;
+
+    let fields: Vec<&str> = cgpsinfo_line
+        .trim()
+        .trim_start_matches("+CGPSINFO:")
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if fields.len() < 9 {
+        return Err(anyhow!(
+            "Insufficient GNSS data fields got {}",
+            fields.len()
+        ));
+    }
+
+    let mut report = PositionReport {
+        run_status: true,
+        fix_status: false,
+        utc_time: String::new(),
+        latitude: None,
+        longitude: None,
+        msl_altitude: None,
+        ground_speed: None,
+        ground_course: None,
+        fix_mode: FixStatus::NotFix,
+        hdop: None,
+        pdop: None,
+        vdop: None,
+        gps_in_view: None,
+        gnss_used: None,
+        glonass_in_view: None,
+    };
+    if fields[0].is_empty() {
+        return Ok(report);
+    }
+
+    report.fix_status = true;
+    report.utc_time = gnss_utc_time(fields[4], fields[5])?;
+    report.latitude = Some(parse_nmea_coordinate(fields[0], fields[1])?);
+    report.longitude = Some(parse_nmea_coordinate(fields[2], fields[3])?);
+    report.msl_altitude = fields[6].parse().ok();
+
+    // Speed is in knots, converted to km/h to match CGNSINF.
+    report.ground_speed = fields[7].parse::<f32>().ok().map(|knots| knots * 1.852);
+    report.ground_course = fields[8].parse().ok();
+    report.fix_mode = if report.msl_altitude.is_some() {
+        FixStatus::Fix3D
+    } else {
+        FixStatus::Fix2D
+    };
+    Ok(report)
+}
+
+/// Parse a Quectel +QGPSLOC response, requested in decimal degrees (mode 2):
+/// `<UTC time>,<lat>,<lon>,<hdop>,<alt>,<fix>,<cog>,<spkm>,<spkn>,<date>,<nsat>`.
+pub fn parse_qgpsloc_response(response: &str) -> Result<PositionReport> {
+    let qgpsloc_line = response
+        .lines()
+        .find(|line| line.trim().starts_with("+QGPSLOC:"))
+        .ok_or_else(|| anyhow!("No QGPSLOC response found in buffer"))?;
+
+    let fields: Vec<&str> = qgpsloc_line
+        .trim()
+        .trim_start_matches("+QGPSLOC:")
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if fields.len() < 11 {
+        return Err(anyhow!(
+            "Insufficient GNSS data fields got {}",
+            fields.len()
+        ));
+    }
+
+    Ok(PositionReport {
+        run_status: true,
+        fix_status: true,
+        utc_time: gnss_utc_time(fields[9], fields[0])?,
+        latitude: fields[1].parse().ok(),
+        longitude: fields[2].parse().ok(),
+        msl_altitude: fields[4].parse().ok(),
+        ground_speed: fields[7].parse().ok(),
+        ground_course: fields[6].parse().ok(),
+        fix_mode: match fields[5] {
+            "2" => FixStatus::Fix2D,
+            "3" => FixStatus::Fix3D,
+            _ => FixStatus::Unknown,
+        },
+        hdop: fields[3].parse().ok(),
+        pdop: None,
+        vdop: None,
+        gps_in_view: None,
+        gnss_used: fields[10].parse().ok(),
+        glonass_in_view: None,
+    })
+}
+
+/// Parse the single value returned by an identity command (eg: AT+CGSN), which some
+/// modems give alone on a line and others after the command prefix.
+pub fn parse_identity_response(response: &str, prefix: &str) -> Result<String> {
+    let value = response
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && *line != "OK")
+        .ok_or_else(|| anyhow!("No identity found in response"))?;
+
+    let value = value
+        .strip_prefix(prefix)
+        .unwrap_or(value)
+        .trim()
+        .trim_matches('"');
+    if value.is_empty() || value == "ERROR" || value.starts_with("+CME ERROR:") {
+        return Err(anyhow!("Invalid identity response: {value}"));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse the first number from a +CNUM response, giving None if the SIM doesn't store its own number.
+pub fn parse_cnum_response(response: &str) -> Result<Option<String>> {
+    let Some(cnum_line) = response
+        .lines()
+        .find(|line| line.trim().starts_with("+CNUM:"))
+    else {
+        return Ok(None);
+    };
+
+    // +CNUM: [<alpha>],<number>,<type>[,...], the alpha label can be empty or quoted.
+    let number = cnum_line
+        .split('"')
+        .nth(3)
+        .ok_or_else(|| anyhow!("Missing CNUM number"))?
+        .trim();
+    Ok((!number.is_empty()).then(|| number.to_string()))
+}
+
+/// Parse the SIM status from a +CPIN response. Most modems answer with an error instead
+/// when there's no SIM inserted, either as a numeric (10) or verbose code.
+pub fn parse_cpin_response(response: &str) -> Result<SimStatus> {
+    for line in response.lines().map(str::trim) {
+        if let Some(code) = line.strip_prefix("+CPIN:") {
+            return Ok(match code.trim() {
+                "READY" => SimStatus::Ready,
+                "SIM PIN" => SimStatus::PinRequired,
+                "SIM PUK" => SimStatus::PukRequired,
+                _ => SimStatus::Locked,
+            });
+        }
+        if let Some(error) = line.strip_prefix("+CME ERROR:") {
+            return match error.trim() {
+                "10" | "SIM not inserted" => Ok(SimStatus::NotInserted),
+                error => Err(anyhow!("SIM status check failed: {error}")),
+            };
+        }
+    }
+    Err(anyhow!("No CPIN response found in buffer"))
+}
+
+/// Parse a PDU mode message listing into (index, status, pdu) for each stored message.
+pub fn parse_cmgl_response(response: &str) -> Result<Vec<(u32, u8, String)>> {
+    let mut messages = Vec::new();
+    let mut lines = response
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+
+    while let Some(line) = lines.next() {
+        let Some(data) = line.strip_prefix("+CMGL:") else {
+            continue;
+        };
+
+        let mut fields = data.split(',').map(str::trim);
+        let index = fields
+            .next()
+            .and_then(|index| index.parse().ok())
+            .ok_or_else(|| anyhow!("Invalid CMGL message index"))?;
+        let status = fields
+            .next()
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| anyhow!("Invalid CMGL message status"))?;
+
+        let pdu = lines
+            .next()
+            .filter(|pdu| pdu.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| anyhow!("Missing PDU for CMGL message {index}"))?;
+        messages.push((index, status, pdu.to_string()));
+    }
+
+    Ok(messages)
+}
+
+/// Check if a buffer holds a whole +CUSD response, as the quoted text can span multiple lines.
+pub fn is_cusd_complete(response: &str) -> bool {
+    response
+        .find("+CUSD:")
+        .is_some_and(|start| response[start..].matches('"').count() != 1)
+}
+
+/// Parse a +CUSD response, decoding the text if it's hex encoded UCS2.
+pub fn parse_cusd_response(response: &str) -> Result<UssdMessage> {
+    let start = response
+        .find("+CUSD:")
+        .ok_or_else(|| anyhow!("No CUSD response found in buffer"))?;
+    let data = response[start + "+CUSD:".len()..].trim();
+
+    let status = data
+        .split(',')
+        .next()
+        .and_then(|status| status.trim().parse().ok())
+        .ok_or_else(|| anyhow!("Invalid CUSD status"))?;
+
+    let (Some(quote_start), Some(quote_end)) = (data.find('"'), data.rfind('"')) else {
+        return Ok(UssdMessage { status, text: None });
+    };
+    if quote_start >= quote_end {
+        return Err(anyhow!("Invalid quoted CUSD text"));
+    }
+
+    let text = &data[quote_start + 1..quote_end];
+    let dcs = data[quote_end + 1..]
+        .trim_start_matches(',')
+        .trim()
+        .parse::<u8>()
+        .ok();
+
+    Ok(UssdMessage {
+        status,
+        text: Some(decode_ussd_text(text, dcs)),
+    })
+}
+
+/// Modems give UCS2 text as hex, anything else is already in the TE character set.
+fn decode_ussd_text(text: &str, dcs: Option<u8>) -> String {
+    // CBS data coding: UCS2 with language indication, or the UCS2 general data coding group.
+    let is_ucs2 = dcs.is_some_and(|dcs| dcs == 0x11 || dcs & 0xCC == 0x48);
+    if !is_ucs2 || !text.len().is_multiple_of(4) {
+        return text.to_string();
+    }
+
+    let units = hex::decode(text).map(|bytes| {
+        bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>()
+    });
+    match units {
+        Ok(units) => String::from_utf16_lossy(&units),
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Parse a +CLIP caller ID notification, giving None if the number was withheld.
+pub fn parse_clip_response(response: &str) -> Result<Option<String>> {
+    let clip_line = response
+        .lines()
+        .find(|line| line.trim().starts_with("+CLIP:"))
+        .ok_or_else(|| anyhow!("No CLIP notification found in buffer"))?;
+
+    let data = clip_line.trim()["+CLIP:".len()..].trim();
+    let number = data
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.split_once('"'))
+        .map(|(number, _)| number.trim())
+        .ok_or_else(|| anyhow!("Missing CLIP caller number"))?;
+
+    Ok((!number.is_empty()).then(|| number.to_string()))
+}
+
+/// Convert a service centre timestamp into a unix timestamp, or None if it isn't a valid date.
+pub fn smsc_timestamp_to_unix(timestamp: &SmscTimestamp) -> Option<u32> {
+    let SmscTimestamp {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        timezone,
+    } = *timestamp;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // Days since the epoch from a civil date, years are always 20xx.
+    let year = 2000 + i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    // The timezone is in quarter hours, sms-pdu decodes its sign bit as an extra 80.
+    let offset_quarters = match timezone {
+        0..80 => i64::from(timezone),
+        _ => -(i64::from(timezone) - 80),
+    };
+    let local = days * 86400 + i64::from(hour) * 3600 + i64::from(minute) * 60 + i64::from(second);
+    u32::try_from(local - offset_quarters * 15 * 60).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,27 +654,32 @@ mod tests {
 
     #[test]
     fn test_parse_creg_response() {
-        // Success cases - test both values
-        let response = "+CREG: 1,7\r\nOK\r\n";
+        // Success cases - the leading mode is skipped
+        let response = "+CREG: 0,1\r\nOK\r\n";
         let (reg, tech) = parse_creg_response(response).unwrap();
         assert_eq!(reg, 1, "Expected registration status 1");
-        assert_eq!(tech, 7, "Expected technology status 7");
+        assert_eq!(tech, None, "Expected no technology in mode 0");
 
-        let response = "  +CREG:  2 , 4  \r\nOK\r\n";
+        let response = "  +CREG:  0 , 5  \r\nOK\r\n";
         let (reg, tech) = parse_creg_response(response).unwrap();
-        assert_eq!(reg, 2, "Expected registration status 2 with whitespace");
-        assert_eq!(tech, 4, "Expected technology status 4 with whitespace");
+        assert_eq!(reg, 5, "Expected registration status 5 with whitespace");
+        assert_eq!(tech, None, "Expected no technology with whitespace");
 
-        // Test various valid combinations
-        let response = "+CREG: 0,0\r\n";
+        // Mode 2 (set during initialization) adds the location and technology.
+        let response = "+CREG: 2,1,\"1A2B\",\"0000C3D4\",7\r\nOK\r\n";
         let (reg, tech) = parse_creg_response(response).unwrap();
-        assert_eq!(reg, 0, "Expected minimum registration status");
-        assert_eq!(tech, 0, "Expected minimum technology status");
+        assert_eq!(reg, 1, "Expected registration status 1 in mode 2");
+        assert_eq!(tech, Some(7), "Expected technology 7 in mode 2");
 
-        let response = "+CREG: 5,9\r\n";
+        let response = "+CREG: 2,5,\"1A2B\",\"0000C3D4\"\r\n";
         let (reg, tech) = parse_creg_response(response).unwrap();
-        assert_eq!(reg, 5, "Expected registration status 5");
-        assert_eq!(tech, 9, "Expected technology status 9");
+        assert_eq!(reg, 5, "Expected registration status 5 in mode 2");
+        assert_eq!(tech, None, "Expected no technology without AcT");
+
+        let response = "+CREG: 2,2\r\n";
+        let (reg, tech) = parse_creg_response(response).unwrap();
+        assert_eq!(reg, 2, "Expected searching without a location");
+        assert_eq!(tech, None, "Expected no technology while searching");
 
         // Failure cases
         let response = "OK\r\n";
@@ -318,29 +692,22 @@ mod tests {
         let response = "+CREG: 1\r\n";
         let err = parse_creg_response(response).unwrap_err();
         assert!(
-            err.to_string().contains("Missing technology status"),
-            "Expected missing technology status error"
+            err.to_string().contains("Missing registration status"),
+            "Expected missing registration status error"
         );
 
-        let response = "+CREG: abc,7\r\n";
+        let response = "+CREG: 2,abc\r\n";
         let err = parse_creg_response(response).unwrap_err();
         assert!(
             err.to_string().contains("Invalid registration status"),
             "Expected invalid registration status error"
         );
 
-        let response = "+CREG: 1,xyz\r\n";
-        let err = parse_creg_response(response).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid technology status"),
-            "Expected invalid technology status error"
-        );
-
-        let response = "+CREG: 1,\r\n"; // Empty technology field
+        let response = "+CREG: 2,1,\"1A2B\",\"0000C3D4\",xyz\r\n";
         let err = parse_creg_response(response).unwrap_err();
         assert!(
-            err.to_string().contains("Invalid technology status"),
-            "Expected error for empty technology field"
+            err.to_string().contains("Invalid access technology"),
+            "Expected invalid access technology error"
         );
     }
 
@@ -768,4 +1135,329 @@ mod tests {
             "Expected error for insufficient CGNSINF fields"
         );
     }
+
+    #[test]
+    fn test_parse_cmgl_response() {
+        let response = "+CMGL: 1,0,,24\r\n07911326040000F0040B911346610089F60000208062917314080CC8F71D14969741F977FD07\r\n+CMGL: 3,1,,5\r\n0004AB\r\n\r\nOK\r\n";
+        let messages = parse_cmgl_response(response).unwrap();
+        assert_eq!(messages.len(), 2, "Expected two stored messages");
+        assert_eq!(messages[0].0, 1);
+        assert_eq!(messages[0].1, 0);
+        assert!(messages[0].2.starts_with("07911326"));
+        assert_eq!(messages[1], (3, 1, "0004AB".to_string()));
+
+        // Empty storage
+        let messages = parse_cmgl_response("\r\nOK\r\n").unwrap();
+        assert!(messages.is_empty(), "Expected no stored messages");
+
+        // Failure cases
+        assert!(
+            parse_cmgl_response("+CMGL: x,0,,24\r\n0004AB\r\nOK\r\n").is_err(),
+            "Expected error for invalid index"
+        );
+        assert!(
+            parse_cmgl_response("+CMGL: 1,0,,24\r\nOK\r\n").is_err(),
+            "Expected error for missing PDU"
+        );
+    }
+
+    #[test]
+    fn test_parse_cusd_response() {
+        let response = "OK\n+CUSD: 0,\"Your balance is 5.00\",15\n";
+        let message = parse_cusd_response(response).unwrap();
+        assert_eq!(message.status, 0);
+        assert_eq!(message.text.as_deref(), Some("Your balance is 5.00"));
+
+        // Menus span multiple lines.
+        let response = "+CUSD: 1,\"Balance: 5.00\n1. Bundles\n2. Exit\",15";
+        let message = parse_cusd_response(response).unwrap();
+        assert!(message.is_action_required());
+        assert_eq!(
+            message.text.as_deref(),
+            Some("Balance: 5.00\n1. Bundles\n2. Exit")
+        );
+
+        // UCS2 text is hex encoded.
+        let response = "+CUSD: 0,\"00480069002000A3\",72";
+        let message = parse_cusd_response(response).unwrap();
+        assert_eq!(message.text.as_deref(), Some("Hi £"));
+
+        // Sessions can end without any text.
+        let message = parse_cusd_response("+CUSD: 2").unwrap();
+        assert_eq!(message.status, 2);
+        assert_eq!(message.text, None);
+
+        // Failure cases
+        assert!(
+            parse_cusd_response("OK\n").is_err(),
+            "Expected error for missing CUSD response"
+        );
+        assert!(
+            parse_cusd_response("+CUSD: x,\"Text\",15").is_err(),
+            "Expected error for invalid status"
+        );
+    }
+
+    #[test]
+    fn test_is_cusd_complete() {
+        assert!(is_cusd_complete("OK\n+CUSD: 0,\"Done\",15\n"));
+        assert!(is_cusd_complete("+CUSD: 2"));
+        assert!(!is_cusd_complete("OK\n"));
+        assert!(!is_cusd_complete("+CUSD: 1,\"Balance: 5.00\n1. Bundles\n"));
+        assert!(is_cusd_complete(
+            "+CUSD: 1,\"Balance: 5.00\n1. Bundles\n2. Exit\",15"
+        ));
+    }
+
+    #[test]
+    fn test_parse_clip_response() {
+        let response = "+CLIP: \"+447700900123\",145,\"\",0,\"\",0";
+        assert_eq!(
+            parse_clip_response(response).unwrap().as_deref(),
+            Some("+447700900123")
+        );
+
+        // Withheld numbers are reported empty.
+        let response = "+CLIP: \"\",128,\"\",0,\"\",1";
+        assert_eq!(parse_clip_response(response).unwrap(), None);
+
+        // Failure cases
+        assert!(
+            parse_clip_response("RING").is_err(),
+            "Expected error for missing CLIP notification"
+        );
+        assert!(
+            parse_clip_response("+CLIP: 145").is_err(),
+            "Expected error for unquoted number"
+        );
+    }
+
+    #[test]
+    fn test_parse_registration_urc() {
+        let registration = parse_registration_urc("+CREG: 5,\"1A2B\",\"0000C3D4\",7").unwrap();
+        assert_eq!(registration.domain, RegistrationDomain::CircuitSwitched);
+        assert!(registration.is_roaming());
+        assert_eq!(registration.lac.as_deref(), Some("1A2B"));
+        assert_eq!(registration.cell_id.as_deref(), Some("0000C3D4"));
+        assert_eq!(registration.technology, Some(7));
+
+        // The location is only given while registered.
+        let registration = parse_registration_urc("+CGREG: 0").unwrap();
+        assert_eq!(registration.domain, RegistrationDomain::PacketSwitched);
+        assert!(!registration.is_registered());
+        assert_eq!(registration.lac, None);
+        assert_eq!(registration.technology, None);
+
+        // Older modems don't report the access technology.
+        let registration = parse_registration_urc("+CGREG: 1,\"1A2B\",\"C3D4\"").unwrap();
+        assert!(registration.is_registered() && !registration.is_roaming());
+        assert_eq!(registration.cell_id.as_deref(), Some("C3D4"));
+        assert_eq!(registration.technology, None);
+
+        let registration = parse_registration_urc("+CEREG: 1,\"00C3\",\"0A1B2C3D\",7").unwrap();
+        assert_eq!(registration.domain, RegistrationDomain::Lte);
+        assert_eq!(registration.lac.as_deref(), Some("00C3"));
+
+        // Failure cases
+        assert!(
+            parse_registration_urc("+CSQ: 20,99").is_err(),
+            "Expected error for other notifications"
+        );
+        assert!(
+            parse_registration_urc("+CREG: x").is_err(),
+            "Expected error for invalid status"
+        );
+        assert!(
+            parse_registration_urc("+CREG: 1,\"1A2B\",\"C3D4\",x").is_err(),
+            "Expected error for invalid access technology"
+        );
+    }
+
+    #[test]
+    fn test_parse_identity_response() {
+        let response = "\n867123456789012\n\nOK\n";
+        assert_eq!(
+            parse_identity_response(response, "+CGSN:").unwrap(),
+            "867123456789012"
+        );
+
+        // Some modems prefix the value.
+        let response = "+CGMR: LE20B04SIM7600G22\nOK\n";
+        assert_eq!(
+            parse_identity_response(response, "+CGMR:").unwrap(),
+            "LE20B04SIM7600G22"
+        );
+        let response = "+CCID: \"8944100000000000001F\"\nOK\n";
+        assert_eq!(
+            parse_identity_response(response, "+CCID:").unwrap(),
+            "8944100000000000001F"
+        );
+
+        // Failure cases
+        assert!(
+            parse_identity_response("OK\n", "+CIMI:").is_err(),
+            "Expected error for missing identity"
+        );
+        assert!(
+            parse_identity_response("+CME ERROR: 10\n", "+CIMI:").is_err(),
+            "Expected error for SIM not inserted"
+        );
+    }
+
+    #[test]
+    fn test_parse_cnum_response() {
+        let response = "+CNUM: \"\",\"+447700900001\",145,7,4\nOK\n";
+        assert_eq!(
+            parse_cnum_response(response).unwrap().as_deref(),
+            Some("+447700900001")
+        );
+
+        let response = "+CNUM: \"Voice\",\"07700900001\",129\nOK\n";
+        assert_eq!(
+            parse_cnum_response(response).unwrap().as_deref(),
+            Some("07700900001")
+        );
+
+        // Most SIMs don't store their own number.
+        assert_eq!(parse_cnum_response("OK\n").unwrap(), None);
+
+        // Failure cases
+        assert!(
+            parse_cnum_response("+CNUM: ,145\nOK\n").is_err(),
+            "Expected error for missing number"
+        );
+    }
+
+    #[test]
+    fn test_parse_cpin_response() {
+        assert_eq!(
+            parse_cpin_response("+CPIN: READY\nOK\n").unwrap(),
+            SimStatus::Ready
+        );
+        assert_eq!(
+            parse_cpin_response("\r\n+CPIN: SIM PIN\r\n\r\nOK\r\n").unwrap(),
+            SimStatus::PinRequired
+        );
+        assert_eq!(
+            parse_cpin_response("+CPIN: SIM PUK\nOK\n").unwrap(),
+            SimStatus::PukRequired
+        );
+        assert_eq!(
+            parse_cpin_response("+CPIN: PH-NET PIN\nOK\n").unwrap(),
+            SimStatus::Locked
+        );
+        assert_eq!(
+            parse_cpin_response("+CME ERROR: 10\n").unwrap(),
+            SimStatus::NotInserted
+        );
+        assert_eq!(
+            parse_cpin_response("+CME ERROR: SIM not inserted\n").unwrap(),
+            SimStatus::NotInserted
+        );
+
+        // Failure cases
+        assert!(
+            parse_cpin_response("+CME ERROR: 14\n").is_err(),
+            "Expected error for SIM busy"
+        );
+        assert!(
+            parse_cpin_response("OK\n").is_err(),
+            "Expected error for missing CPIN"
+        );
+    }
+
+    #[test]
+    fn test_parse_cgpsinfo_response() {
+        let response =
+            "+CGPSINFO: 5130.444000,N,00007.668000,W,150823,120000.0,85.4,10.0,90.0\r\nOK\r\n";
+        let report = parse_cgpsinfo_response(response).unwrap();
+        assert!(report.fix_status);
+        assert_eq!(report.utc_time, "20230815120000.0");
+        assert!((report.latitude.unwrap() - 51.5074).abs() < 1e-6);
+        assert!((report.longitude.unwrap() + 0.1278).abs() < 1e-6);
+        assert_eq!(report.msl_altitude, Some(85.4));
+        assert!((report.ground_speed.unwrap() - 18.52).abs() < 1e-3);
+        assert_eq!(report.fix_mode, FixStatus::Fix3D);
+
+        // No fix yet, every field is empty.
+        let report = parse_cgpsinfo_response("+CGPSINFO: ,,,,,,,,\r\nOK\r\n").unwrap();
+        assert!(report.run_status && !report.fix_status);
+        assert_eq!(report.latitude, None);
+        assert_eq!(report.fix_mode, FixStatus::NotFix);
+
+        // Failure cases
+        assert!(
+            parse_cgpsinfo_response("+CGPSINFO: 5130.444000,N\r\n").is_err(),
+            "Expected error for insufficient CGPSINFO fields"
+        );
+        assert!(
+            parse_cgpsinfo_response("+CGPSINFO: 5130.4,X,00007.6,W,150823,120000.0,,,\r\n")
+                .is_err(),
+            "Expected error for invalid hemisphere"
+        );
+        assert!(
+            parse_cgpsinfo_response("OK\r\n").is_err(),
+            "Expected error for missing CGPSINFO"
+        );
+    }
+
+    #[test]
+    fn test_parse_qgpsloc_response() {
+        let response =
+            "+QGPSLOC: 120000.000,51.50740,-0.12780,0.9,85.4,3,90.00,18.5,10.0,150823,10\r\nOK\r\n";
+        let report = parse_qgpsloc_response(response).unwrap();
+        assert_eq!(report.utc_time, "20230815120000.000");
+        assert_eq!(report.latitude, Some(51.5074));
+        assert_eq!(report.longitude, Some(-0.1278));
+        assert_eq!(report.hdop, Some(0.9));
+        assert_eq!(report.ground_speed, Some(18.5));
+        assert_eq!(report.fix_mode, FixStatus::Fix3D);
+        assert_eq!(report.gnss_used, Some(10));
+
+        // Failure cases
+        assert!(
+            parse_qgpsloc_response("+CME ERROR: 516\r\n").is_err(),
+            "Expected error for no fix"
+        );
+        assert!(
+            parse_qgpsloc_response("+QGPSLOC: 120000.000,51.50740\r\n").is_err(),
+            "Expected error for insufficient QGPSLOC fields"
+        );
+    }
+
+    #[test]
+    fn test_smsc_timestamp_to_unix() {
+        let timestamp = |year, month, day, timezone| SmscTimestamp {
+            year,
+            month,
+            day,
+            hour: 12,
+            minute: 30,
+            second: 15,
+            timezone,
+        };
+
+        // 2025-03-01 12:30:15 UTC, just after a leap day would have been.
+        assert_eq!(
+            smsc_timestamp_to_unix(&timestamp(25, 3, 1, 0)),
+            Some(1740832215)
+        );
+        assert_eq!(
+            smsc_timestamp_to_unix(&timestamp(24, 2, 29, 0)),
+            Some(1709209815)
+        );
+
+        // UTC+1 (4 quarters) is an hour ahead, UTC-5 (20 quarters with the sign bit) is behind.
+        assert_eq!(
+            smsc_timestamp_to_unix(&timestamp(25, 3, 1, 4)),
+            Some(1740828615)
+        );
+        assert_eq!(
+            smsc_timestamp_to_unix(&timestamp(25, 3, 1, 100)),
+            Some(1740850215)
+        );
+
+        assert_eq!(smsc_timestamp_to_unix(&timestamp(25, 0, 1, 0)), None);
+        assert_eq!(smsc_timestamp_to_unix(&timestamp(25, 13, 1, 0)), None);
+    }
 }
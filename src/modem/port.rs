@@ -0,0 +1,105 @@
+use crate::config::{ModemConfig, UsbDeviceConfig};
+use crate::modem::simulator::{SimulatedModem, SimulatorOptions};
+use anyhow::{anyhow, Context, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_serial::{SerialPortBuilderExt, SerialPortType, UsbPortInfo};
+use tracing::log::{debug, info};
+
+/// The connection to a modem, either a serial port or a simulated modem.
+pub trait ModemPort: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ModemPort for T {}
+
+/// Open the modem's port. This is done again on every reconnect, as USB modems
+/// can disappear and come back, possibly under another device name.
+pub fn open_port(config: &ModemConfig) -> Result<Box<dyn ModemPort>> {
+    if let Some(options) = SimulatorOptions::from_device(&config.device) {
+        let options = options
+            .with_context(|| format!("Invalid simulator device for modem '{}'!", config.id))?;
+        info!("Modem '{}' is simulated with {options:?}", config.id);
+        return Ok(Box::new(SimulatedModem::spawn(options)));
+    }
+
+    let device = match &config.usb {
+        Some(usb) => find_usb_device(usb)?,
+        None => config.device.clone(),
+    };
+    debug!("Opening serial port {device} for modem '{}'", config.id);
+
+    let port = tokio_serial::new(&device, config.baud_rate)
+        .open_native_async()
+        .with_context(|| format!("Failed to open serial port for modem '{}'!", config.id))?;
+    Ok(Box::new(port))
+}
+
+/// Find the device path of the USB serial port matching the config.
+fn find_usb_device(usb: &UsbDeviceConfig) -> Result<String> {
+    tokio_serial::available_ports()
+        .context("Failed to list serial ports")?
+        .into_iter()
+        .find(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => usb_device_matches(usb, info),
+            _ => false,
+        })
+        .map(|port| port.port_name)
+        .ok_or_else(|| {
+            anyhow!(
+                "No USB serial port found for {:04x}:{:04x} (serial: {:?}, interface: {:?})",
+                usb.vid,
+                usb.pid,
+                usb.serial,
+                usb.interface
+            )
+        })
+}
+
+fn usb_device_matches(usb: &UsbDeviceConfig, info: &UsbPortInfo) -> bool {
+    info.vid == usb.vid
+        && info.pid == usb.pid
+        && usb
+            .serial
+            .as_ref()
+            .is_none_or(|serial| info.serial_number.as_ref() == Some(serial))
+        && usb
+            .interface
+            .is_none_or(|interface| info.interface == Some(interface))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port_info(serial: &str, interface: u8) -> UsbPortInfo {
+        UsbPortInfo {
+            vid: 0x2c7c,
+            pid: 0x0125,
+            serial_number: Some(serial.to_string()),
+            manufacturer: Some("Quectel".to_string()),
+            product: Some("EG25-G".to_string()),
+            interface: Some(interface),
+        }
+    }
+
+    #[test]
+    fn test_usb_device_matches() {
+        let mut usb = UsbDeviceConfig {
+            vid: 0x2c7c,
+            pid: 0x0125,
+            serial: None,
+            interface: None,
+        };
+        assert!(usb_device_matches(&usb, &port_info("A", 2)));
+
+        usb.pid = 0x0306;
+        assert!(!usb_device_matches(&usb, &port_info("A", 2)));
+
+        // Modems expose several ports, so the interface picks the AT command one.
+        usb.pid = 0x0125;
+        usb.interface = Some(2);
+        assert!(usb_device_matches(&usb, &port_info("A", 2)));
+        assert!(!usb_device_matches(&usb, &port_info("A", 3)));
+
+        usb.serial = Some("B".to_string());
+        assert!(!usb_device_matches(&usb, &port_info("A", 2)));
+        assert!(usb_device_matches(&usb, &port_info("B", 2)));
+    }
+}
@@ -0,0 +1,106 @@
+//! The AT command dialect of each modem family. Messaging, calls and registration use
+//! standard 3GPP commands, so this only covers the vendor specific parts (mostly GNSS).
+
+use crate::config::ModemProfile;
+use crate::modem::parsers::{
+    parse_cgnsinf_response, parse_cgpsinfo_response, parse_cgpsstatus_response,
+    parse_qgpsloc_response,
+};
+use anyhow::Result;
+use sms_types::gnss::{FixStatus, PositionReport};
+
+impl ModemProfile {
+    /// Commands sent after the standard initialization commands, with their expected response.
+    pub fn init_commands(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            ModemProfile::Simcom2G => &[],
+
+            // Registration on LTE is only reported by +CEREG.
+            ModemProfile::SimcomLte | ModemProfile::Quectel => &[("AT+CEREG=2", "OK")],
+        }
+    }
+
+    /// Commands to power on GNSS and set the position report interval (0 = disabled).
+    /// An empty expected response accepts errors, as these fail if GNSS is already running.
+    pub fn gnss_init_commands(&self, report_interval: u32) -> Vec<(String, &'static str)> {
+        match self {
+            ModemProfile::Simcom2G => vec![
+                ("AT+CGNSPWR=1".to_string(), "OK"),              // Power on
+                ("AT+CGPSRST=0".to_string(), "OK"),              // Cold start
+                (format!("AT+CGNSURC={report_interval}"), "OK"), // Set navigation URC report interval
+            ],
+            ModemProfile::SimcomLte => vec![
+                ("AT+CGPS=1".to_string(), ""),                    // Power on
+                (format!("AT+CGPSINFO={report_interval}"), "OK"), // Set position report interval
+            ],
+
+            // Quectel modems have no position reports, only NMEA sentences.
+            ModemProfile::Quectel => vec![("AT+QGPS=1".to_string(), "")],
+        }
+    }
+
+    /// The header of unsolicited GNSS position reports, if the modem sends them.
+    pub fn gnss_report_header(&self) -> Option<&'static str> {
+        match self {
+            ModemProfile::Simcom2G => Some("+UGNSINF"),
+            ModemProfile::SimcomLte => Some("+CGPSINFO:"),
+            ModemProfile::Quectel => None,
+        }
+    }
+
+    /// The command to get the current GNSS location.
+    pub fn gnss_location_command(&self) -> &'static str {
+        match self {
+            ModemProfile::Simcom2G => "AT+CGNSINF",
+            ModemProfile::SimcomLte => "AT+CGPSINFO",
+            ModemProfile::Quectel => "AT+QGPSLOC=2",
+        }
+    }
+
+    /// The response prefix of gnss_location_command.
+    pub fn gnss_location_prefix(&self) -> &'static str {
+        match self {
+            ModemProfile::Simcom2G => "+CGNSINF:",
+            ModemProfile::SimcomLte => "+CGPSINFO:",
+            ModemProfile::Quectel => "+QGPSLOC:",
+        }
+    }
+
+    /// The command to get the GNSS fix status, which is read from the location if
+    /// the modem has no separate status command.
+    pub fn gnss_status_command(&self) -> &'static str {
+        match self {
+            ModemProfile::Simcom2G => "AT+CGPSSTATUS?",
+            _ => self.gnss_location_command(),
+        }
+    }
+
+    /// The response prefix of gnss_status_command.
+    pub fn gnss_status_prefix(&self) -> &'static str {
+        match self {
+            ModemProfile::Simcom2G => "+CGPSSTATUS:",
+            _ => self.gnss_location_prefix(),
+        }
+    }
+
+    /// Parse a GNSS location response, or an unsolicited position report.
+    pub fn parse_gnss_location(&self, response: &str, unsolicited: bool) -> Result<PositionReport> {
+        match self {
+            ModemProfile::Simcom2G => parse_cgnsinf_response(response, unsolicited),
+            ModemProfile::SimcomLte => parse_cgpsinfo_response(response),
+            ModemProfile::Quectel => parse_qgpsloc_response(response),
+        }
+    }
+
+    /// Parse a GNSS status response.
+    pub fn parse_gnss_status(&self, response: &str) -> Result<FixStatus> {
+        match self {
+            ModemProfile::Simcom2G => parse_cgpsstatus_response(response),
+            ModemProfile::SimcomLte => Ok(parse_cgpsinfo_response(response)?.fix_mode),
+
+            // Quectel modems give an error (516) instead of a location until there's a fix.
+            ModemProfile::Quectel if response.contains("+CME ERROR: 516") => Ok(FixStatus::NotFix),
+            ModemProfile::Quectel => Ok(parse_qgpsloc_response(response)?.fix_mode),
+        }
+    }
+}
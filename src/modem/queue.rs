@@ -0,0 +1,98 @@
+use crate::modem::commands::{CommandPriority, OutgoingCommand};
+use std::collections::VecDeque;
+
+const LANES: usize = 3;
+
+/// How many commands from higher lanes can go ahead of a waiting lane before it gets a turn.
+const MAX_SKIPS: u32 = 4;
+
+/// Commands waiting for the modem, served by priority. A lane that keeps getting passed
+/// over is served after MAX_SKIPS commands, so a busy lane can't starve the others.
+pub struct CommandQueue {
+    lanes: [VecDeque<OutgoingCommand>; LANES],
+    skipped: [u32; LANES],
+}
+impl CommandQueue {
+    pub fn new() -> Self {
+        Self {
+            lanes: Default::default(),
+            skipped: [0; LANES],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn push(&mut self, command: OutgoingCommand) {
+        self.lanes[Self::lane(command.priority)].push_back(command);
+    }
+
+    pub fn pop(&mut self) -> Option<OutgoingCommand> {
+        // The lowest starved lane goes first, otherwise the highest lane with anything queued.
+        let lane = (0..LANES)
+            .rev()
+            .find(|&lane| !self.lanes[lane].is_empty() && self.skipped[lane] >= MAX_SKIPS)
+            .or_else(|| (0..LANES).find(|&lane| !self.lanes[lane].is_empty()))?;
+
+        for other in 0..LANES {
+            if other == lane || self.lanes[other].is_empty() {
+                self.skipped[other] = 0;
+            } else if other > lane {
+                self.skipped[other] += 1;
+            }
+        }
+        self.lanes[lane].pop_front()
+    }
+
+    /// Remove every queued command, eg: to reject them when the modem goes offline.
+    pub fn drain(&mut self) -> impl Iterator<Item = OutgoingCommand> + '_ {
+        self.skipped = [0; LANES];
+        self.lanes.iter_mut().flat_map(|lane| lane.drain(..))
+    }
+
+    fn lane(priority: CommandPriority) -> usize {
+        match priority {
+            CommandPriority::Interactive => 0,
+            CommandPriority::Single => 1,
+            CommandPriority::Bulk => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modem::types::ModemRequest;
+    use tokio::sync::oneshot;
+
+    fn command(sequence: u32, priority: CommandPriority) -> OutgoingCommand {
+        let (tx, _rx) = oneshot::channel();
+        OutgoingCommand::new(sequence, tx, ModemRequest::Probe, priority, None)
+    }
+
+    #[test]
+    fn test_priority_and_fairness() {
+        let mut queue = CommandQueue::new();
+        queue.push(command(1, CommandPriority::Bulk));
+        queue.push(command(2, CommandPriority::Single));
+        queue.push(command(3, CommandPriority::Interactive));
+        for sequence in 4..10 {
+            queue.push(command(sequence, CommandPriority::Interactive));
+        }
+        assert_eq!(queue.len(), 9);
+
+        // Both lower lanes get a turn after MAX_SKIPS interactive commands, bulk first.
+        let order: Vec<u32> = std::iter::from_fn(|| queue.pop())
+            .map(|command| command.sequence)
+            .collect();
+        assert_eq!(order, vec![3, 4, 5, 6, 1, 2, 7, 8, 9]);
+
+        // Skips don't carry over once a lane has emptied.
+        queue.push(command(10, CommandPriority::Interactive));
+        queue.push(command(11, CommandPriority::Bulk));
+        assert_eq!(queue.pop().unwrap().sequence, 10);
+        assert_eq!(queue.drain().count(), 1);
+        assert!(queue.pop().is_none());
+    }
+}
@@ -1,95 +1,157 @@
 #![cfg_attr(not(feature = "http-server"), allow(dead_code))]
 
-use crate::modem::commands::{next_command_sequence, OutgoingCommand};
-use crate::modem::types::{ModemRequest, ModemResponse};
+use crate::modem::commands::{next_command_sequence, CommandPriority, OutgoingCommand};
+use crate::modem::types::{ModemRequest, ModemResponse, ModemStatus, QueueStats};
 use anyhow::Result;
 use anyhow::{anyhow, bail};
 use sms_pdu::pdu::PduAddress;
 use sms_pdu::{gsm_encoding, pdu};
 use sms_types::sms::SmsOutgoingMessage;
 use std::time::Duration;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tracing::log::{debug, error, warn};
 
 const SEND_TIMEOUT: Duration = Duration::from_secs(90);
 
-fn create_sms_requests(message: &SmsOutgoingMessage) -> Result<Vec<ModemRequest>> {
-    // Parse message number into PduAddress for sending.
-    let destination = message
-        .to
-        .parse::<PduAddress>()
-        .map_err(anyhow::Error::msg)?;
-    let validity_period = message.get_validity_period();
-
-    let requests = gsm_encoding::GsmMessageData::encode_message(&message.content)
-        .into_iter()
-        .map(|data| {
-            let pdu = pdu::SubmitPdu {
-                sca: None,
-                first_octet: pdu::PduFirstOctet {
-                    mti: pdu::MessageType::SmsSubmit,
-                    rd: false,
-                    vpf: pdu::VpFieldValidity::Relative,
-                    srr: true,
-                    udhi: data.udh,
-                    rp: false,
-                },
-                message_id: 0,
-                destination: destination.clone(),
-                dcs: pdu::DataCodingScheme::Standard {
-                    compressed: false,
-                    class: message
-                        .flash
-                        .unwrap_or(false)
-                        .then_some(pdu::MessageClass::Silent),
-                    encoding: data.encoding,
-                },
-                validity_period,
-                user_data: data.bytes,
-                user_data_len: data.user_data_len,
-            };
-
-            let (bytes, size) = pdu.as_bytes();
-            ModemRequest::SendSMS {
-                pdu: hex::encode(bytes),
-                len: size,
-            }
+/// The encoded parts of an outgoing message, and the references of those the modem has accepted.
+/// A partly sent message must be resumed with the same PDUs, as the recipient joins the parts
+/// by the concatenation reference they share, which is random each time a message is encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmsParts {
+    pub pdus: Vec<String>,
+    pub references: Vec<u8>,
+}
+impl SmsParts {
+    pub fn encode(message: &SmsOutgoingMessage) -> Result<Self> {
+        // Parse message number into PduAddress for sending.
+        let destination = message
+            .to
+            .parse::<PduAddress>()
+            .map_err(anyhow::Error::msg)?;
+        let validity_period = message.get_validity_period();
+
+        let pdus = gsm_encoding::GsmMessageData::encode_message(&message.content)
+            .into_iter()
+            .map(|data| {
+                let pdu = pdu::SubmitPdu {
+                    sca: None,
+                    first_octet: pdu::PduFirstOctet {
+                        mti: pdu::MessageType::SmsSubmit,
+                        rd: false,
+                        vpf: pdu::VpFieldValidity::Relative,
+                        srr: true,
+                        udhi: data.udh,
+                        rp: false,
+                    },
+                    message_id: 0,
+                    destination: destination.clone(),
+                    dcs: pdu::DataCodingScheme::Standard {
+                        compressed: false,
+                        class: message
+                            .flash
+                            .unwrap_or(false)
+                            .then_some(pdu::MessageClass::Silent),
+                        encoding: data.encoding,
+                    },
+                    validity_period,
+                    user_data: data.bytes,
+                    user_data_len: data.user_data_len,
+                };
+                hex::encode(pdu.as_bytes().0)
+            })
+            .collect();
+
+        Ok(Self {
+            pdus,
+            references: Vec::new(),
         })
-        .collect::<Vec<ModemRequest>>();
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.references.len() >= self.pdus.len()
+    }
 
-    Ok(requests)
+    /// The send requests for the parts that haven't been accepted yet.
+    fn remaining_requests(&self) -> impl Iterator<Item = ModemRequest> + '_ {
+        // There's no SMSC address, so the TPDU length is everything after its length octet.
+        self.pdus
+            .iter()
+            .skip(self.references.len())
+            .map(|pdu| ModemRequest::SendSMS {
+                len: pdu.len() / 2 - 1,
+                pdu: pdu.clone(),
+            })
+    }
 }
 
 #[derive(Clone)]
 pub struct ModemSender {
     command_tx: mpsc::Sender<OutgoingCommand>,
+    status_rx: watch::Receiver<ModemStatus>,
+    queue_rx: watch::Receiver<QueueStats>,
 }
 impl ModemSender {
-    pub fn new(command_tx: mpsc::Sender<OutgoingCommand>) -> Self {
-        Self { command_tx }
+    pub fn new(
+        command_tx: mpsc::Sender<OutgoingCommand>,
+        status_rx: watch::Receiver<ModemStatus>,
+        queue_rx: watch::Receiver<QueueStats>,
+    ) -> Self {
+        Self {
+            command_tx,
+            status_rx,
+            queue_rx,
+        }
+    }
+
+    /// Get the last status reported by the ModemWorker.
+    pub fn status(&self) -> ModemStatus {
+        self.status_rx.borrow().clone()
+    }
+
+    /// Get the command queue depth and last wait time reported by the ModemWorker.
+    pub fn queue_stats(&self) -> QueueStats {
+        *self.queue_rx.borrow()
+    }
+
+    /// Wait until the ModemWorker reports that it's online, returning immediately if it already is.
+    pub async fn wait_for_online(&self) -> Result<()> {
+        self.status_rx
+            .clone()
+            .wait_for(|status| *status == ModemStatus::Online)
+            .await
+            .map(|_| ())
+            .map_err(|_| anyhow!("ModemWorker status channel is closed"))
     }
 
-    /// Send an SMSOutgoingMessage, and get a resulting ModemResponse.
-    /// Returns: Result<(sent_all, Option<last_response>)>
+    /// Send the parts of a message the modem hasn't accepted yet, adding each accepted part's
+    /// reference to parts. Sending stops at the first part that fails, as there's no use in
+    /// continuing a broken concatenation, so it can be resumed from that part later.
+    /// Returns: Result<last_response>
     pub async fn send_sms(
         &self,
-        message: &SmsOutgoingMessage,
-    ) -> Result<(bool, Option<ModemResponse>)> {
-        // Send each send request for message, returning the last message.
-        let mut last_response_opt = None;
-        for request in create_sms_requests(message)? {
-            let response = self.send_request(request, message.timeout).await?;
-
-            // FIXME: If one of the message parts return an error response, then return immediately
-            //  as there's no use in continuing to send message parts for a broken concatenation.
-            if matches!(response, ModemResponse::Error(_)) {
-                return Ok((false, Some(response)));
+        parts: &mut SmsParts,
+        timeout: Option<u32>,
+    ) -> Result<ModemResponse> {
+        // Multipart messages go in the bulk lane, so they can't hold up everything else.
+        let priority = if parts.pdus.len() > 1 {
+            CommandPriority::Bulk
+        } else {
+            CommandPriority::Single
+        };
+
+        let requests = parts.remaining_requests().collect::<Vec<_>>();
+        let mut last_response = None;
+        for request in requests {
+            let response = self.queue_request(request, priority, timeout).await?;
+            match response {
+                ModemResponse::SendResult(reference_id) => parts.references.push(reference_id),
+                ModemResponse::Error(_) => return Ok(response),
+                _ => bail!("Got invalid ModemResponse back from sending SMS message!"),
             }
-            last_response_opt.replace(response);
+            last_response = Some(response);
         }
 
-        // Sent all requests, last response
-        Ok((true, last_response_opt))
+        last_response.ok_or_else(|| anyhow!("Missing any valid SendSMS response!"))
     }
 
     /// Send a modem request and get some result.
@@ -97,12 +159,22 @@ impl ModemSender {
         &self,
         request: ModemRequest,
         timeout: Option<u32>,
+    ) -> Result<ModemResponse> {
+        let priority = request.get_default_priority();
+        self.queue_request(request, priority, timeout).await
+    }
+
+    async fn queue_request(
+        &self,
+        request: ModemRequest,
+        priority: CommandPriority,
+        timeout: Option<u32>,
     ) -> Result<ModemResponse> {
         let sequence = next_command_sequence();
         let (tx, rx) = oneshot::channel();
 
-        debug!("Queuing command sequence {sequence}: {request:?}");
-        let cmd = OutgoingCommand::new(sequence, tx, request, timeout);
+        debug!("Queuing command sequence {sequence} ({priority:?}): {request:?}");
+        let cmd = OutgoingCommand::new(sequence, tx, request, priority, timeout);
 
         // Try to queue without blocking.
         match self.command_tx.try_send(cmd) {
@@ -0,0 +1,1238 @@
+//! A virtual modem for running without any hardware, selected with a `sim://` device.
+//! It speaks the same AT command dialect over an in-memory stream, so everything above
+//! the serial port (line buffer, state machine, parsers) is exercised as normal.
+
+use anyhow::{anyhow, bail, Context, Result};
+use sms_pdu::gsm_encoding::GsmMessageData;
+use sms_pdu::pdu::{PduAddress, SubmitPdu};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::sync::mpsc;
+use tracing::log::{debug, warn};
+
+pub const SIMULATOR_SCHEME: &str = "sim://";
+
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+const CTRL_Z: u8 = 0x1A;
+const ESC: u8 = 0x1B;
+const STORED_SENDER: &str = "+447700900000";
+const STORAGE_SIZE: usize = 50;
+const CALLER: &str = "+447700900999";
+const SUBSCRIBER_NUMBER: &str = "+447700900001";
+const PIN_ATTEMPTS: u8 = 3;
+const REGISTRATION_LOCATION: &str = "\"1A2B\",\"0000C3D4\",7";
+const GNSS_LOCATION: &str =
+    "+CGNSINF: 1,1,20230815120000.000,51.5074,-0.1278,85.4,0.0,0.0,1,0.9,1.2,0.8,,,10,4,,,42";
+const GNSS_LOCATION_LTE: &str =
+    "+CGPSINFO: 5130.444000,N,00007.668000,W,150823,120000.0,85.4,0.0,0.0";
+const GNSS_LOCATION_QUECTEL: &str =
+    "+QGPSLOC: 120000.000,51.50740,-0.12780,0.9,85.4,3,0.00,0.0,0.0,150823,10";
+
+/// Behaviour of the simulated modem, set with query parameters on the device,
+/// eg: `sim://?fail_every=3&report=none`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorOptions {
+    /// Deliver every sent message back as an incoming message from its destination.
+    pub loopback: bool,
+
+    /// Status of the delivery report sent for each message, or None to not send any.
+    pub report: Option<u8>,
+
+    /// Delay before sending delivery reports and looped back messages.
+    pub delay: Duration,
+
+    /// Reject every nth sent message with a `+CMS ERROR`.
+    pub fail_every: Option<u32>,
+
+    /// Never respond to every nth sent message, so the command times out.
+    pub timeout_every: Option<u32>,
+
+    /// Report a power down after every nth sent message, taking the modem offline until it reconnects.
+    pub power_down_every: Option<u32>,
+
+    /// Number of received messages already in modem storage at startup.
+    pub stored: u32,
+
+    /// Number of times an incoming call from CALLER rings, once caller ID is enabled.
+    pub rings: u32,
+
+    /// Registration status to report once registration notifications are enabled, eg: 5 for roaming.
+    pub registration: Option<u8>,
+
+    /// PIN the SIM is locked with at startup, wrong PINs block it after PIN_ATTEMPTS.
+    pub pin: Option<String>,
+
+    /// Start with the SIM already blocked, needing its PUK.
+    pub puk: bool,
+}
+impl Default for SimulatorOptions {
+    fn default() -> Self {
+        Self {
+            loopback: true,
+            report: Some(0),
+            delay: Duration::from_secs(1),
+            fail_every: None,
+            timeout_every: None,
+            power_down_every: None,
+            stored: 0,
+            rings: 0,
+            registration: None,
+            pin: None,
+            puk: false,
+        }
+    }
+}
+impl SimulatorOptions {
+    /// Parse options from a device string, returning None if it isn't a simulator device.
+    pub fn from_device(device: &str) -> Option<Result<Self>> {
+        device.strip_prefix(SIMULATOR_SCHEME).map(Self::parse)
+    }
+
+    fn parse(query: &str) -> Result<Self> {
+        let mut options = Self::default();
+        let query = query.trim_start_matches('/').trim_start_matches('?');
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Simulator option '{pair}' is missing a value"))?;
+
+            let invalid = || format!("Invalid value '{value}' for simulator option '{key}'");
+            let every = || -> Result<Option<u32>> {
+                match value.parse().with_context(invalid)? {
+                    0 => Ok(None),
+                    n => Ok(Some(n)),
+                }
+            };
+            match key {
+                "loopback" => options.loopback = value.parse().with_context(invalid)?,
+                "report" => {
+                    options.report = match value {
+                        "none" => None,
+                        _ => Some(value.parse().with_context(invalid)?),
+                    }
+                }
+                "delay" => {
+                    options.delay = Duration::from_millis(value.parse().with_context(invalid)?)
+                }
+                "fail_every" => options.fail_every = every()?,
+                "timeout_every" => options.timeout_every = every()?,
+                "power_down_every" => options.power_down_every = every()?,
+                "stored" => options.stored = value.parse().with_context(invalid)?,
+                "rings" => options.rings = value.parse().with_context(invalid)?,
+                "registration" => options.registration = Some(value.parse().with_context(invalid)?),
+                "pin" => options.pin = Some(value.to_string()),
+                "puk" => options.puk = value.parse().with_context(invalid)?,
+                _ => bail!("Unknown simulator option '{key}'"),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The parts of a SMS-SUBMIT PDU needed to build the resulting deliver and status report PDUs.
+#[derive(Debug, PartialEq)]
+struct SubmittedMessage {
+    /// The encoded destination address, including its length and type bytes.
+    destination: Vec<u8>,
+    dcs: u8,
+    udhi: bool,
+    user_data_len: u8,
+    user_data: Vec<u8>,
+}
+impl TryFrom<&[u8]> for SubmittedMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let byte = |offset: usize| {
+            bytes
+                .get(offset)
+                .copied()
+                .ok_or_else(|| anyhow!("Submitted PDU is truncated at byte {offset}"))
+        };
+
+        // Skip the service centre address, first octet and message reference.
+        let mut offset = byte(0)? as usize + 1;
+        let first_octet = byte(offset)?;
+        offset += 2;
+
+        // Address length is in semi-octets, plus the length and type bytes.
+        let address_len = (byte(offset)? as usize).div_ceil(2) + 2;
+        let destination = bytes
+            .get(offset..offset + address_len)
+            .ok_or_else(|| anyhow!("Submitted PDU has a truncated destination"))?
+            .to_vec();
+        offset += address_len;
+
+        // Skip the protocol identifier, then the validity period based on its format.
+        let dcs = byte(offset + 1)?;
+        offset += 2;
+        offset += match (first_octet >> 3) & 0b11 {
+            0b00 => 0,
+            0b10 => 1,
+            _ => 7,
+        };
+
+        Ok(Self {
+            destination,
+            dcs,
+            udhi: first_octet & 0x40 != 0,
+            user_data_len: byte(offset)?,
+            user_data: bytes.get(offset + 1..).unwrap_or_default().to_vec(),
+        })
+    }
+}
+
+/// Build the message stored in modem storage at the given index, from STORED_SENDER.
+fn stored_message(index: u32) -> Result<SubmittedMessage> {
+    let sender = STORED_SENDER
+        .parse::<PduAddress>()
+        .map_err(anyhow::Error::msg)?;
+    let data = GsmMessageData::encode_message(&format!("Stored message {index}"))
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Stored message {index} has no parts"))?;
+
+    let (bytes, _) = SubmitPdu::make_simple_message(sender, data).as_bytes();
+    SubmittedMessage::try_from(bytes.as_slice())
+}
+
+/// Encode the current time as a 7 byte service centre timestamp, in UTC.
+fn smsc_timestamp() -> [u8; 7] {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // Convert days since the epoch into a civil date.
+    let days = (now / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let seconds = now % 86400;
+    let semi_octet = |value: u64| (((value % 10) << 4) | ((value / 10) % 10)) as u8;
+    [
+        semi_octet((year % 100) as u64),
+        semi_octet(month as u64),
+        semi_octet(day as u64),
+        semi_octet(seconds / 3600),
+        semi_octet(seconds / 60 % 60),
+        semi_octet(seconds % 60),
+        0,
+    ]
+}
+
+/// Build a SMS-DELIVER PDU, as if the submitted message was sent to us by its destination.
+fn deliver_pdu(message: &SubmittedMessage) -> Vec<u8> {
+    let mut pdu = vec![0x00, 0x04 | if message.udhi { 0x40 } else { 0x00 }];
+    pdu.extend_from_slice(&message.destination);
+    pdu.extend_from_slice(&[0x00, message.dcs]);
+    pdu.extend_from_slice(&smsc_timestamp());
+    pdu.push(message.user_data_len);
+    pdu.extend_from_slice(&message.user_data);
+    pdu
+}
+
+/// Build a SMS-STATUS-REPORT PDU for a submitted message.
+fn status_report_pdu(message: &SubmittedMessage, reference: u8, status: u8) -> Vec<u8> {
+    let timestamp = smsc_timestamp();
+    let mut pdu = vec![0x00, 0x06, reference];
+    pdu.extend_from_slice(&message.destination);
+    pdu.extend_from_slice(&timestamp);
+    pdu.extend_from_slice(&timestamp);
+    pdu.push(status);
+    pdu
+}
+
+/// Format an unsolicited result code that has a PDU on the following line.
+/// The length excludes the (empty) service centre address.
+fn pdu_urc(header: &str, pdu: &[u8]) -> Vec<u8> {
+    format!(
+        "\r\n{header}{}\r\n{}\r\n",
+        pdu.len() - 1,
+        hex::encode_upper(pdu)
+    )
+    .into_bytes()
+}
+
+pub struct SimulatedModem {
+    options: SimulatorOptions,
+    stream: DuplexStream,
+    urc_tx: mpsc::UnboundedSender<Vec<u8>>,
+    urc_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+
+    /// Length of the PDU expected after the prompt of an in progress AT+CMGS.
+    pending_submit: Option<usize>,
+    next_reference: u8,
+    sent: u32,
+
+    /// If the USSD balance menu is waiting for a choice.
+    ussd_menu: bool,
+
+    /// Wrong PINs left before the SIM is blocked, while it's locked.
+    pin_attempts: Option<u8>,
+
+    /// Received messages in modem storage by index, as SMS-DELIVER PDUs.
+    storage: BTreeMap<u32, Vec<u8>>,
+
+    /// The +CREG result code mode set with AT+CREG=<n>.
+    creg_mode: u8,
+}
+impl SimulatedModem {
+    /// Start a simulated modem, returning the stream to use in place of a serial port.
+    pub fn spawn(options: SimulatorOptions) -> DuplexStream {
+        let (port, stream) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let (urc_tx, urc_rx) = mpsc::unbounded_channel();
+
+        let storage = (1..=options.stored)
+            .filter_map(|index| match stored_message(index) {
+                Ok(message) => Some((index, deliver_pdu(&message))),
+                Err(e) => {
+                    warn!("Simulated modem couldn't store message {index}: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        let pin_attempts = match (options.puk, &options.pin) {
+            (true, _) => Some(0),
+            (false, pin) => pin.as_ref().map(|_| PIN_ATTEMPTS),
+        };
+        let modem = Self {
+            options,
+            stream,
+            urc_tx,
+            urc_rx,
+            pending_submit: None,
+            next_reference: 0,
+            sent: 0,
+            ussd_menu: false,
+            pin_attempts,
+            storage,
+            creg_mode: 0,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = modem.run().await {
+                warn!("Simulated modem stopped: {e}");
+            }
+        });
+
+        port
+    }
+
+    async fn run(mut self) -> Result<()> {
+        let mut input = Vec::new();
+        let mut read_buffer = [0u8; 1024];
+        loop {
+            tokio::select! {
+                result = self.stream.read(&mut read_buffer) => {
+                    match result? {
+                        0 => return Ok(()),
+                        n => input.extend_from_slice(&read_buffer[..n]),
+                    }
+                    self.process_input(&mut input).await?;
+                },
+                Some(urc) = self.urc_rx.recv() => self.stream.write_all(&urc).await?,
+            }
+        }
+    }
+
+    /// Handle every complete command or PDU in input, leaving any partial one.
+    async fn process_input(&mut self, input: &mut Vec<u8>) -> Result<()> {
+        loop {
+            if self.pending_submit.is_some() {
+                let Some(end) = input.iter().position(|&b| b == CTRL_Z || b == ESC) else {
+                    return Ok(());
+                };
+                let terminator = input[end];
+                let pdu: Vec<u8> = input.drain(..=end).take(end).collect();
+
+                let expected_len = self.pending_submit.take().unwrap_or_default();
+                let response = if terminator == ESC {
+                    b"\r\nOK\r\n".to_vec()
+                } else {
+                    self.submit(&pdu, expected_len)
+                };
+                self.stream.write_all(&response).await?;
+                continue;
+            }
+
+            let Some(end) = input.iter().position(|&b| b == b'\r' || b == b'\n') else {
+                return Ok(());
+            };
+            let line: Vec<u8> = input.drain(..=end).take(end).collect();
+            let command = String::from_utf8_lossy(&line);
+            let command = command.trim();
+            if command.is_empty() {
+                continue;
+            }
+
+            debug!("Simulated modem received: {command:?}");
+            let response = self.command(command);
+            self.stream.write_all(response.as_bytes()).await?;
+        }
+    }
+
+    fn command(&mut self, command: &str) -> String {
+        const OK: &str = "\r\nOK\r\n";
+        let upper = command.to_ascii_uppercase();
+
+        if let Some(len) = upper.strip_prefix("AT+CMGS=") {
+            return match len.trim().parse() {
+                Ok(len) => {
+                    self.pending_submit = Some(len);
+                    "\r\n> ".to_string()
+                }
+                Err(_) => "\r\nERROR\r\n".to_string(),
+            };
+        }
+
+        if upper == "AT+CPIN?" {
+            let status = match self.pin_attempts {
+                None => "READY",
+                Some(0) => "SIM PUK",
+                Some(_) => "SIM PIN",
+            };
+            return format!("\r\n+CPIN: {status}\r\n{OK}");
+        }
+        if let Some(pin) = command.strip_prefix("AT+CPIN=") {
+            return self.unlock(pin.trim_matches('"'));
+        }
+        if let Some(args) = upper.strip_prefix("AT+CUSD=") {
+            return self.ussd(args);
+        }
+        if upper.starts_with("AT+CMGL=") {
+            return self.list_stored();
+        }
+        if let Some(index) = upper.strip_prefix("AT+CMGD=") {
+            return match index.trim().parse() {
+                Ok(index) => {
+                    self.storage.remove(&index);
+                    OK.to_string()
+                }
+                Err(_) => "\r\nERROR\r\n".to_string(),
+            };
+        }
+        if upper == "AT+CPMS=\"ME\",\"ME\",\"ME\"" {
+            let used = self.storage.len();
+            return format!(
+                "\r\n+CPMS: {used},{STORAGE_SIZE},{used},{STORAGE_SIZE},{used},{STORAGE_SIZE}\r\n{OK}"
+            );
+        }
+
+        if let Some(mode) = upper.strip_prefix("AT+CREG=") {
+            return match mode.trim().parse() {
+                Ok(mode @ 0..=2) => {
+                    self.creg_mode = mode;
+                    OK.to_string()
+                }
+                _ => "\r\nERROR\r\n".to_string(),
+            };
+        }
+        if upper == "AT+CREG?" {
+            // The query reply starts with the mode, and only includes the location in mode 2.
+            let (mode, status) = (self.creg_mode, self.options.registration.unwrap_or(1));
+            return match (mode, status) {
+                (2, 1 | 5) => format!("\r\n+CREG: {mode},{status},{REGISTRATION_LOCATION}\r\n{OK}"),
+                _ => format!("\r\n+CREG: {mode},{status}\r\n{OK}"),
+            };
+        }
+        if let Some(mode) = upper.strip_prefix("AT+CGREG=") {
+            if let (Some(status), "2") = (self.options.registration, mode.trim()) {
+                self.queue_urcs(vec![
+                    format!("\r\n+CREG: {status},{REGISTRATION_LOCATION}\r\n").into_bytes(),
+                    format!("\r\n+CGREG: {status},{REGISTRATION_LOCATION}\r\n").into_bytes(),
+                ]);
+            }
+            return OK.to_string();
+        }
+        if upper == "AT+CLIP=1" {
+            let ring = format!("\r\nRING\r\n\r\n+CLIP: \"{CALLER}\",145,\"\",0,\"\",0\r\n");
+            self.queue_urcs(vec![ring.into_bytes(); self.options.rings as usize]);
+            return OK.to_string();
+        }
+
+        let response = match upper.as_str() {
+            "AT+CSQ" => "+CSQ: 20,99",
+            "AT+COPS?" => "+COPS: 0,0,\"Simulated\"",
+            "AT+CSPN?" => "+CSPN: \"Simulated\",0",
+            "AT+CBC" => "+CBC: 0,100,4200",
+            "AT+CGSN" => "990000000000001",
+            "AT+CIMI" => "001010000000001",
+            "AT+CCID" => "89001010000000000001",
+            "AT+CGMI" => "sms-server",
+            "AT+CGMM" => "Simulated Modem",
+            "AT+CGMR" => "Simulator",
+            "AT+CNUM" => return format!("\r\n+CNUM: \"\",\"{SUBSCRIBER_NUMBER}\",145\r\n{OK}"),
+            "AT+CGPSSTATUS?" => "+CGPSSTATUS: Location 3D Fix",
+            "AT+CGNSINF" => GNSS_LOCATION,
+            "AT+CGPSINFO" => GNSS_LOCATION_LTE,
+            "AT+QGPSLOC=2" => GNSS_LOCATION_QUECTEL,
+            "AT" | "ATZ" | "ATE0" | "ATH" => return OK.to_string(),
+
+            // Accept any setting, eg: AT+CNMI=2,2,0,1,0
+            _ if upper.starts_with("AT+") && upper.contains('=') => return OK.to_string(),
+            _ => {
+                warn!("Simulated modem doesn't support command: {command:?}");
+                return "\r\nERROR\r\n".to_string();
+            }
+        };
+        format!("\r\n{response}\r\n{OK}")
+    }
+
+    /// Enter the SIM PIN, blocking the SIM once it's been wrong PIN_ATTEMPTS times.
+    fn unlock(&mut self, pin: &str) -> String {
+        match self.pin_attempts {
+            None => "\r\nOK\r\n".to_string(),
+            Some(0) => "\r\n+CME ERROR: 12\r\n".to_string(),
+            Some(_) if self.options.pin.as_deref() == Some(pin) => {
+                self.pin_attempts = None;
+                "\r\nOK\r\n".to_string()
+            }
+            Some(attempts) => {
+                self.pin_attempts = Some(attempts - 1);
+                "\r\n+CME ERROR: 16\r\n".to_string()
+            }
+        }
+    }
+
+    /// Accept a USSD request, queueing the network's reply. Only the *100# balance menu exists.
+    fn ussd(&mut self, args: &str) -> String {
+        const MENU: &str = "1. Bundles\r\n2. Exit";
+
+        let reply = match args.split('"').nth(1) {
+            // Cancelling an open session is reported by the network.
+            None if args.trim() == "2" => self.ussd_menu.then_some((2, None)),
+            None => None,
+            Some("*100#") => Some((1, Some(format!("Balance: 5.00\r\n{MENU}")))),
+            Some("1") if self.ussd_menu => Some((0, Some("You have no bundles".to_string()))),
+            Some("2") if self.ussd_menu => Some((0, Some("Goodbye".to_string()))),
+            Some(_) if self.ussd_menu => Some((1, Some(format!("Invalid choice\r\n{MENU}")))),
+            Some(_) => Some((4, None)),
+        };
+
+        if let Some((status, text)) = reply {
+            self.ussd_menu = status == 1;
+            let urc = match text {
+                Some(text) => format!("\r\n+CUSD: {status},\"{text}\",15\r\n"),
+                None => format!("\r\n+CUSD: {status}\r\n"),
+            };
+            self.queue_urcs(vec![urc.into_bytes()]);
+        }
+        "\r\nOK\r\n".to_string()
+    }
+
+    /// List every stored message as received and unread, in PDU mode.
+    fn list_stored(&self) -> String {
+        let mut response = String::new();
+        for (index, pdu) in &self.storage {
+            response.push_str(&format!(
+                "\r\n+CMGL: {index},0,,{}\r\n{}",
+                pdu.len() - 1,
+                hex::encode_upper(pdu)
+            ));
+        }
+        response.push_str("\r\n\r\nOK\r\n");
+        response
+    }
+
+    /// Handle a submitted PDU, returning the immediate response and queueing any reports.
+    fn submit(&mut self, pdu_hex: &[u8], expected_len: usize) -> Vec<u8> {
+        self.sent += 1;
+        let nth = |every: Option<u32>| every.is_some_and(|every| self.sent.is_multiple_of(every));
+
+        if nth(self.options.timeout_every) {
+            debug!("Simulated modem ignoring sent message #{}", self.sent);
+            return Vec::new();
+        }
+        if nth(self.options.fail_every) {
+            debug!("Simulated modem failing sent message #{}", self.sent);
+            return b"\r\n+CMS ERROR: 500\r\n".to_vec();
+        }
+
+        let message = match hex::decode(pdu_hex.trim_ascii())
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| {
+                // The length given to AT+CMGS excludes the service centre address.
+                let sca_len = bytes.first().map_or(0, |&len| len as usize + 1);
+                if bytes.len().saturating_sub(sca_len) != expected_len {
+                    bail!("PDU length doesn't match AT+CMGS length {expected_len}");
+                }
+                SubmittedMessage::try_from(bytes.as_slice())
+            }) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Simulated modem received an invalid PDU: {e}");
+                return b"\r\n+CMS ERROR: 304\r\n".to_vec();
+            }
+        };
+
+        let reference = self.next_reference;
+        self.next_reference = self.next_reference.wrapping_add(1);
+
+        let mut urcs = Vec::new();
+        if let Some(status) = self.options.report {
+            urcs.push(pdu_urc(
+                "+CDS: ",
+                &status_report_pdu(&message, reference, status),
+            ));
+        }
+        if self.options.loopback {
+            urcs.push(pdu_urc("+CMT: ,", &deliver_pdu(&message)));
+        }
+        if nth(self.options.power_down_every) {
+            urcs.push(b"\r\nNORMAL POWER DOWN\r\n".to_vec());
+        }
+        self.queue_urcs(urcs);
+
+        format!("\r\n+CMGS: {reference}\r\n\r\nOK\r\n").into_bytes()
+    }
+
+    /// Send URCs after the configured delay, so they arrive after the command response.
+    fn queue_urcs(&self, urcs: Vec<Vec<u8>>) {
+        if urcs.is_empty() {
+            return;
+        }
+
+        let urc_tx = self.urc_tx.clone();
+        let delay = self.options.delay;
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            for urc in urcs {
+                let _ = urc_tx.send(urc);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{InitCommand, ModemConfig, ModemProfile, WatchdogAction};
+    use crate::modem::sender::{ModemSender, SmsParts};
+    use crate::modem::types::{
+        ModemIncomingMessage, ModemMessage, ModemRequest, ModemResponse, ModemStatus, QueueStats,
+    };
+    use crate::modem::worker::ModemWorker;
+    use sms_types::gnss::FixStatus;
+    use sms_types::modem::{RegistrationDomain, SimStatus};
+    use sms_types::sms::SmsOutgoingMessage;
+    use tokio::sync::watch;
+
+    #[test]
+    fn test_options() {
+        assert_eq!(
+            SimulatorOptions::from_device("sim://").unwrap().unwrap(),
+            SimulatorOptions::default()
+        );
+        assert!(SimulatorOptions::from_device("/dev/ttyS0").is_none());
+
+        let options = SimulatorOptions::from_device(
+            "sim://?loopback=false&report=none&delay=0&fail_every=3&timeout_every=0",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(!options.loopback);
+        assert_eq!(options.report, None);
+        assert_eq!(options.delay, Duration::ZERO);
+        assert_eq!(options.fail_every, Some(3));
+        assert_eq!(options.timeout_every, None);
+
+        assert_eq!(
+            SimulatorOptions::from_device("sim://?stored=2")
+                .unwrap()
+                .unwrap()
+                .stored,
+            2
+        );
+        assert_eq!(
+            SimulatorOptions::from_device("sim://?rings=3")
+                .unwrap()
+                .unwrap()
+                .rings,
+            3
+        );
+        assert_eq!(
+            SimulatorOptions::from_device("sim://?registration=5")
+                .unwrap()
+                .unwrap()
+                .registration,
+            Some(5)
+        );
+        assert_eq!(
+            SimulatorOptions::from_device("sim://?pin=1234&puk=true")
+                .unwrap()
+                .unwrap(),
+            SimulatorOptions {
+                pin: Some("1234".to_string()),
+                puk: true,
+                ..SimulatorOptions::default()
+            }
+        );
+        assert!(SimulatorOptions::from_device("sim://?report=64")
+            .unwrap()
+            .is_ok_and(|options| options.report == Some(64)));
+        assert!(SimulatorOptions::from_device("sim://?unknown=1")
+            .unwrap()
+            .is_err());
+        assert!(SimulatorOptions::from_device("sim://?fail_every=x")
+            .unwrap()
+            .is_err());
+    }
+
+    fn spawn(config: ModemConfig) -> (ModemSender, mpsc::UnboundedReceiver<ModemMessage>) {
+        let options = SimulatorOptions::from_device(&config.device)
+            .unwrap()
+            .unwrap();
+
+        let (main_tx, main_rx) = mpsc::unbounded_channel();
+        let (status_tx, status_rx) = watch::channel(ModemStatus::Startup);
+        let (queue_tx, queue_rx) = watch::channel(QueueStats::default());
+        let (command_tx, command_rx) = mpsc::channel(config.cmd_channel_buffer_size);
+
+        let port = Box::new(SimulatedModem::spawn(options));
+        let worker = ModemWorker::new(port, main_tx, status_tx, queue_tx, config).unwrap();
+        tokio::spawn(worker.initialize_and_run(command_rx));
+
+        (ModemSender::new(command_tx, status_rx, queue_rx), main_rx)
+    }
+
+    async fn start(device: &str) -> (ModemSender, mpsc::UnboundedReceiver<ModemMessage>) {
+        let (sender, main_rx) = spawn(ModemConfig {
+            device: device.to_string(),
+            ..ModemConfig::default()
+        });
+        tokio::time::timeout(Duration::from_secs(5), sender.wait_for_online())
+            .await
+            .expect("Simulated modem didn't come online")
+            .unwrap();
+        (sender, main_rx)
+    }
+
+    fn message(content: &str) -> SmsOutgoingMessage {
+        SmsOutgoingMessage::simple_message("+447700900123", content)
+    }
+
+    fn encode(content: &str) -> SmsParts {
+        SmsParts::encode(&message(content)).unwrap()
+    }
+
+    async fn next_message(
+        main_rx: &mut mpsc::UnboundedReceiver<ModemMessage>,
+    ) -> ModemIncomingMessage {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), main_rx.recv())
+                .await
+                .expect("Timed out waiting for simulated modem")
+                .unwrap()
+                .message;
+
+            if !matches!(message, ModemIncomingMessage::ModemStatusUpdate { .. }) {
+                return message;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_and_receive() {
+        let (sender, mut main_rx) = start("sim://?delay=0").await;
+
+        let response = sender
+            .send_request(ModemRequest::GetSignalStrength, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            response,
+            ModemResponse::SignalStrength { rssi: 20, ber: 99 }
+        ));
+
+        let mut parts = encode("Hello simulator");
+        let response = sender.send_sms(&mut parts, None).await.unwrap();
+        assert!(matches!(response, ModemResponse::SendResult(0)));
+        assert_eq!(parts.references, vec![0]);
+
+        match next_message(&mut main_rx).await {
+            ModemIncomingMessage::DeliveryReport(report) => {
+                assert_eq!(report.phone_number, "+447700900123");
+                assert_eq!(report.reference_id, 0);
+                assert_eq!(report.status, 0);
+            }
+            other => panic!("Expected a delivery report, got {other:?}"),
+        }
+        match next_message(&mut main_rx).await {
+            ModemIncomingMessage::IncomingSMS(incoming) => {
+                assert_eq!(incoming.phone_number, "+447700900123");
+                assert_eq!(incoming.content, "Hello simulator");
+                assert!(incoming.user_data_header.is_none());
+            }
+            other => panic!("Expected an incoming message, got {other:?}"),
+        }
+
+        // Long messages are looped back as each of their parts. The length fills the final
+        // septet exactly, as sms-pdu decodes any trailing fill bits as an extra '@'.
+        let content = format!("{}ab", "A long message. ".repeat(15));
+        let mut parts = encode(&content);
+        sender.send_sms(&mut parts, None).await.unwrap();
+        assert_eq!(parts.references, vec![1, 2]);
+
+        let mut parts = Vec::new();
+        while parts.len() < 2 {
+            if let ModemIncomingMessage::IncomingSMS(incoming) = next_message(&mut main_rx).await {
+                let header = incoming.user_data_header.expect("Missing multipart header");
+                assert_eq!(header.total, 2);
+                parts.push((header.index, incoming.content));
+            }
+        }
+        parts.sort();
+        assert_eq!(
+            parts.into_iter().map(|(_, part)| part).collect::<String>(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failure_injection() {
+        let (sender, _main_rx) = start("sim://?delay=0&fail_every=2&report=none").await;
+
+        let mut parts = encode("First");
+        let response = sender.send_sms(&mut parts, None).await.unwrap();
+        assert!(matches!(response, ModemResponse::SendResult(0)));
+        assert!(parts.is_complete());
+
+        let mut parts = encode("Second");
+        let response = sender.send_sms(&mut parts, None).await.unwrap();
+        assert!(matches!(response, ModemResponse::Error(_)));
+        assert!(!parts.is_complete());
+
+        // References are only used by accepted messages.
+        let response = sender.send_sms(&mut encode("Third"), None).await.unwrap();
+        assert!(matches!(response, ModemResponse::SendResult(1)));
+    }
+
+    #[tokio::test]
+    async fn test_resume_multipart() {
+        let (sender, mut main_rx) = start("sim://?delay=0&fail_every=2&report=none").await;
+
+        // The second part fails, so only the first is sent.
+        let content = format!("{}ab", "A long message. ".repeat(15));
+        let mut parts = encode(&content);
+        let response = sender.send_sms(&mut parts, None).await.unwrap();
+        assert!(matches!(response, ModemResponse::Error(_)));
+        assert_eq!(parts.references, vec![0]);
+
+        // Sending again carries on from the failed part, rather than repeating the first.
+        let response = sender.send_sms(&mut parts, None).await.unwrap();
+        assert!(matches!(response, ModemResponse::SendResult(1)));
+        assert_eq!(parts.references, vec![0, 1]);
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            if let ModemIncomingMessage::IncomingSMS(incoming) = next_message(&mut main_rx).await {
+                let header = incoming.user_data_header.expect("Missing multipart header");
+                received.push((header.index, header.message_reference, incoming.content));
+            }
+        }
+        received.sort();
+        assert_eq!(received[0].1, received[1].1);
+        assert_eq!(
+            received
+                .into_iter()
+                .map(|(_, _, part)| part)
+                .collect::<String>(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stored_messages() {
+        let mut port = SimulatedModem::spawn(SimulatorOptions {
+            stored: 2,
+            ..SimulatorOptions::default()
+        });
+        let mut command = async |command: &str| {
+            port.write_all(command.as_bytes()).await.unwrap();
+            let mut response = Vec::new();
+            while !response.ends_with(b"OK\r\n") {
+                let mut buf = [0u8; 1024];
+                let n = port.read(&mut buf).await.unwrap();
+                response.extend_from_slice(&buf[..n]);
+            }
+            String::from_utf8(response).unwrap()
+        };
+
+        let listed = command("AT+CMGL=4\r\n").await;
+        assert!(listed.contains("+CMGL: 1,0,,") && listed.contains("+CMGL: 2,0,,"));
+        assert!(command("AT+CPMS=\"ME\",\"ME\",\"ME\"\r\n")
+            .await
+            .contains("+CPMS: 2,50"));
+
+        command("AT+CMGD=1\r\n").await;
+        let listed = command("AT+CMGL=4\r\n").await;
+        assert!(!listed.contains("+CMGL: 1,") && listed.contains("+CMGL: 2,"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_stats() {
+        let (sender, _main_rx) =
+            start("sim://?delay=0&timeout_every=1&report=none&loopback=false").await;
+        assert_eq!(sender.queue_stats(), QueueStats::default());
+
+        // Every message times out, so the second waits for the first.
+        let (mut first, mut second) = (encode("First"), encode("Second"));
+        let sends = futures::future::join(
+            sender.send_sms(&mut first, Some(1)),
+            sender.send_sms(&mut second, Some(1)),
+        );
+        let queued = async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            sender.queue_stats()
+        };
+        let (_, queued) = tokio::join!(sends, queued);
+        assert_eq!(queued.depth, 1);
+
+        let stats = sender.queue_stats();
+        assert_eq!(stats.depth, 0);
+        assert!(stats.last_wait >= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_import_stored() {
+        // Timing out sends makes the watchdog reinitialize the modem, importing storage again.
+        let (sender, mut main_rx) = spawn(ModemConfig {
+            device: "sim://?stored=2&delay=0&timeout_every=1&report=none&loopback=false"
+                .to_string(),
+            watchdog_max_timeouts: 2,
+            watchdog_action: WatchdogAction::Reinitialize,
+            ..ModemConfig::default()
+        });
+
+        // Only the acknowledged message is deleted from modem storage.
+        for index in 1..=2 {
+            match next_message(&mut main_rx).await {
+                ModemIncomingMessage::StoredSMS(incoming, stored_tx) => {
+                    assert_eq!(incoming.phone_number, STORED_SENDER);
+                    assert_eq!(incoming.content, format!("Stored message {index}"));
+                    if index == 1 {
+                        stored_tx.send(()).unwrap();
+                    }
+                }
+                other => panic!("Expected a stored message, got {other:?}"),
+            }
+        }
+        tokio::time::timeout(Duration::from_secs(5), sender.wait_for_online())
+            .await
+            .expect("Simulated modem didn't come online")
+            .unwrap();
+
+        for _ in 0..2 {
+            let _ = sender.send_sms(&mut encode("Ignored"), Some(1)).await;
+        }
+        match next_message(&mut main_rx).await {
+            ModemIncomingMessage::StoredSMS(incoming, _) => {
+                assert_eq!(incoming.content, "Stored message 2");
+            }
+            other => panic!("Expected a stored message, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incoming_call() {
+        let (sender, mut main_rx) = start("sim://?delay=200&rings=3").await;
+
+        // Every ring repeats the caller ID, but they're all the same call.
+        match next_message(&mut main_rx).await {
+            ModemIncomingMessage::IncomingCall(phone_number) => {
+                assert_eq!(phone_number.as_deref(), Some(CALLER))
+            }
+            other => panic!("Expected an incoming call, got {other:?}"),
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), next_message(&mut main_rx))
+                .await
+                .is_err(),
+            "Expected a single incoming call"
+        );
+
+        let response = sender.send_request(ModemRequest::HangUp, None).await;
+        assert!(matches!(response, Ok(ModemResponse::CallEnded)));
+    }
+
+    #[tokio::test]
+    async fn test_registration() {
+        let (sender, mut main_rx) = start("sim://?delay=200&registration=5").await;
+
+        for domain in [
+            RegistrationDomain::CircuitSwitched,
+            RegistrationDomain::PacketSwitched,
+        ] {
+            match next_message(&mut main_rx).await {
+                ModemIncomingMessage::NetworkStatusChange(registration) => {
+                    assert_eq!(registration.domain, domain);
+                    assert!(registration.is_roaming());
+                    assert_eq!(registration.cell_id.as_deref(), Some("0000C3D4"));
+                    assert_eq!(registration.technology, Some(7));
+                }
+                other => panic!("Expected a registration change, got {other:?}"),
+            }
+        }
+
+        // The command response shares its prefix with the notification, but starts with the mode.
+        let response = sender
+            .send_request(ModemRequest::GetNetworkStatus, None)
+            .await;
+        assert!(matches!(
+            response,
+            Ok(ModemResponse::NetworkStatus {
+                registration: 5,
+                technology: Some(7)
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sim_pin() {
+        let config = |device: &str, sim_pin: Option<&str>| ModemConfig {
+            device: device.to_string(),
+            sim_pin: sim_pin.map(str::to_string),
+            ..ModemConfig::default()
+        };
+        let sim_status = async |config: ModemConfig| {
+            let (_sender, mut main_rx) = spawn(config);
+            let message = tokio::time::timeout(Duration::from_secs(5), main_rx.recv())
+                .await
+                .expect("Timed out waiting for simulated modem")
+                .unwrap()
+                .message;
+
+            match message {
+                ModemIncomingMessage::ModemStatusUpdate {
+                    current,
+                    sim_status,
+                    ..
+                } => (current, sim_status),
+                other => panic!("Expected a status update, got {other:?}"),
+            }
+        };
+
+        assert_eq!(
+            sim_status(config("sim://?pin=1234", Some("1234"))).await,
+            (ModemStatus::Online, Some(SimStatus::Ready))
+        );
+
+        // A rejected PIN isn't retried, and no PIN at all leaves the SIM untouched.
+        assert_eq!(
+            sim_status(config("sim://?pin=1234", Some("0000"))).await,
+            (ModemStatus::Offline, Some(SimStatus::PinRequired))
+        );
+        assert_eq!(
+            sim_status(config("sim://?pin=1234", None)).await,
+            (ModemStatus::Offline, Some(SimStatus::PinRequired))
+        );
+        assert_eq!(
+            sim_status(config("sim://?puk=true", Some("1234"))).await,
+            (ModemStatus::Offline, Some(SimStatus::PukRequired))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sim_blocked() {
+        let mut port = SimulatedModem::spawn(SimulatorOptions {
+            pin: Some("1234".to_string()),
+            ..SimulatorOptions::default()
+        });
+        let mut command = async |command: &str| {
+            port.write_all(command.as_bytes()).await.unwrap();
+            let mut response = Vec::new();
+            while !response.ends_with(b"OK\r\n") && !response.ends_with(b"ERROR: 16\r\n") {
+                let mut buf = [0u8; 1024];
+                let n = port.read(&mut buf).await.unwrap();
+                response.extend_from_slice(&buf[..n]);
+            }
+            String::from_utf8(response).unwrap()
+        };
+
+        for _ in 0..PIN_ATTEMPTS {
+            assert!(command("AT+CPIN?\r\n").await.contains("SIM PIN"));
+            assert!(command("AT+CPIN=\"0000\"\r\n").await.contains("ERROR"));
+        }
+        assert!(command("AT+CPIN?\r\n").await.contains("SIM PUK"));
+    }
+
+    #[tokio::test]
+    async fn test_profiles() {
+        for profile in [
+            ModemProfile::Simcom2G,
+            ModemProfile::SimcomLte,
+            ModemProfile::Quectel,
+        ] {
+            let (sender, _main_rx) = spawn(ModemConfig {
+                device: "sim://?delay=0".to_string(),
+                profile,
+                gnss_enabled: true,
+                init_commands: vec![InitCommand {
+                    command: "AT+CSQ".to_string(),
+                    expected: "+CSQ:".to_string(),
+                }],
+                ..ModemConfig::default()
+            });
+            tokio::time::timeout(Duration::from_secs(5), sender.wait_for_online())
+                .await
+                .expect("Simulated modem didn't come online")
+                .unwrap();
+
+            match sender
+                .send_request(ModemRequest::GetGNSSLocation, None)
+                .await
+            {
+                Ok(ModemResponse::GNSSLocation(report)) => {
+                    assert!((report.latitude.unwrap() - 51.5074).abs() < 1e-6);
+                    assert!((report.longitude.unwrap() + 0.1278).abs() < 1e-6);
+                }
+                other => panic!("Expected a {profile:?} location, got {other:?}"),
+            }
+            assert!(matches!(
+                sender.send_request(ModemRequest::GetGNSSStatus, None).await,
+                Ok(ModemResponse::GNSSStatus(FixStatus::Fix3D))
+            ));
+        }
+
+        // Initialization stops at an extra command without its expected response.
+        let (_sender, mut main_rx) = spawn(ModemConfig {
+            device: "sim://?delay=0".to_string(),
+            init_commands: vec![InitCommand {
+                command: "AT+CSQ".to_string(),
+                expected: "+CREG:".to_string(),
+            }],
+            ..ModemConfig::default()
+        });
+        let message = tokio::time::timeout(Duration::from_secs(5), main_rx.recv())
+            .await
+            .expect("Timed out waiting for simulated modem")
+            .unwrap()
+            .message;
+        assert!(matches!(
+            message,
+            ModemIncomingMessage::ModemStatusUpdate {
+                current: ModemStatus::Offline,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_watchdog() {
+        let config = |watchdog_action| ModemConfig {
+            device: "sim://?delay=0&timeout_every=1&report=none&loopback=false".to_string(),
+            watchdog_max_timeouts: 2,
+            watchdog_action,
+            ..ModemConfig::default()
+        };
+        // Both messages time out, so the modem is reinitialized and carries on.
+        let (sender, _main_rx) = spawn(config(WatchdogAction::Reinitialize));
+        tokio::time::timeout(Duration::from_secs(5), sender.wait_for_online())
+            .await
+            .expect("Simulated modem didn't come online")
+            .unwrap();
+        for _ in 0..2 {
+            let _ = sender.send_sms(&mut encode("Ignored"), Some(1)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(sender.status(), ModemStatus::Online);
+        assert!(matches!(
+            sender
+                .send_request(ModemRequest::GetSignalStrength, None)
+                .await,
+            Ok(ModemResponse::SignalStrength { .. })
+        ));
+
+        let (sender, mut main_rx) = spawn(config(WatchdogAction::Offline));
+        tokio::time::timeout(Duration::from_secs(5), sender.wait_for_online())
+            .await
+            .expect("Simulated modem didn't come online")
+            .unwrap();
+        for _ in 0..2 {
+            let _ = sender.send_sms(&mut encode("Ignored"), Some(1)).await;
+        }
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), main_rx.recv())
+                .await
+                .expect("Expected the watchdog to take the modem offline")
+                .unwrap()
+                .message;
+            if let ModemIncomingMessage::ModemStatusUpdate {
+                current: ModemStatus::Offline,
+                ..
+            } = message
+            {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identity() {
+        let (sender, _main_rx) = start("sim://?delay=0").await;
+        let identity = async |request: ModemRequest| sender.send_request(request, None).await;
+
+        assert!(matches!(
+            identity(ModemRequest::GetIMEI).await,
+            Ok(ModemResponse::Identity(imei)) if imei == "990000000000001"
+        ));
+        assert!(matches!(
+            identity(ModemRequest::GetICCID).await,
+            Ok(ModemResponse::Identity(iccid)) if iccid == "89001010000000000001"
+        ));
+        assert!(matches!(
+            identity(ModemRequest::GetSubscriberNumber).await,
+            Ok(ModemResponse::SubscriberNumber(Some(number))) if number == SUBSCRIBER_NUMBER
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ussd() {
+        let (sender, mut main_rx) = start("sim://?delay=0").await;
+        let ussd = async |request: ModemRequest| match sender.send_request(request, None).await {
+            Ok(ModemResponse::USSDResult(message)) => message,
+            other => panic!("Expected a USSD response, got {other:?}"),
+        };
+        let send = |code: &str| ModemRequest::SendUSSD {
+            code: code.to_string(),
+        };
+
+        // Menus span multiple lines, and continue with the next code.
+        let menu = ussd(send("*100#")).await;
+        assert!(menu.is_action_required());
+        assert_eq!(
+            menu.text.as_deref(),
+            Some("Balance: 5.00\n1. Bundles\n2. Exit")
+        );
+        let reply = ussd(send("1")).await;
+        assert_eq!(reply.status, 0);
+        assert_eq!(reply.text.as_deref(), Some("You have no bundles"));
+
+        let reply = ussd(send("*999#")).await;
+        assert_eq!(reply.status, 4);
+        assert_eq!(reply.text, None);
+
+        // Cancelling an open session is reported by the network as a notification.
+        assert!(ussd(send("*100#")).await.is_action_required());
+        ussd(ModemRequest::CancelUSSD).await;
+        match next_message(&mut main_rx).await {
+            ModemIncomingMessage::IncomingUSSD(message) => assert_eq!(message.status, 2),
+            other => panic!("Expected a USSD notification, got {other:?}"),
+        }
+    }
+}
@@ -1,12 +1,12 @@
+use crate::config::ModemProfile;
 use crate::modem::buffer::LineEvent;
 use crate::modem::commands::{CommandContext, CommandState, OutgoingCommand};
 use crate::modem::handlers::ModemEventHandlers;
-use crate::modem::types::{
-    ModemEvent, ModemIncomingMessage, ModemResponse, UnsolicitedMessageKind,
-};
+use crate::modem::types::{ModemEvent, ModemMessage, ModemResponse, UnsolicitedMessageKind};
 use crate::modem::worker::WorkerEvent;
 use anyhow::{bail, Result};
 use std::mem::take;
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::mpsc;
 use tracing::log::{debug, error, warn};
@@ -50,6 +50,7 @@ enum StateMachineState {
     Command(CommandExecution),
     UnsolicitedMessage {
         message_kind: UnsolicitedMessageKind,
+        content: String,
         interrupted_command: Option<CommandExecution>,
     },
 }
@@ -57,12 +58,24 @@ enum StateMachineState {
 pub struct ModemStateMachine {
     state: StateMachineState,
     handlers: ModemEventHandlers,
+    modem_id: Arc<str>,
+    profile: ModemProfile,
+
+    /// Commands that timed out since the modem last completed one, used by the watchdog.
+    consecutive_timeouts: u32,
 }
 impl ModemStateMachine {
-    pub fn new(worker_event_tx: mpsc::UnboundedSender<WorkerEvent>) -> Self {
+    pub fn new(
+        worker_event_tx: mpsc::UnboundedSender<WorkerEvent>,
+        modem_id: Arc<str>,
+        profile: ModemProfile,
+    ) -> Self {
         Self {
             state: StateMachineState::Idle,
-            handlers: ModemEventHandlers::new(worker_event_tx),
+            handlers: ModemEventHandlers::new(worker_event_tx, profile),
+            modem_id,
+            profile,
+            consecutive_timeouts: 0,
         }
     }
 
@@ -72,6 +85,11 @@ impl ModemStateMachine {
 
     pub fn reset_to_idle(&mut self) {
         self.state = StateMachineState::Idle;
+        self.consecutive_timeouts = 0;
+    }
+
+    pub fn consecutive_timeouts(&self) -> u32 {
+        self.consecutive_timeouts
     }
 
     pub async fn fail_active_command(&mut self, reason: &str) {
@@ -135,7 +153,11 @@ impl ModemStateMachine {
             _ => unreachable!(),
         };
 
-        warn!("Command {} timed out!", command.sequence);
+        self.consecutive_timeouts += 1;
+        warn!(
+            "Command {} timed out! ({} in a row)",
+            command.sequence, self.consecutive_timeouts
+        );
         command
             .respond(ModemResponse::Error("Command timed out!".to_string()))
             .await
@@ -144,7 +166,7 @@ impl ModemStateMachine {
 
     pub async fn transition_state(
         &mut self,
-        main_tx: &mpsc::UnboundedSender<ModemIncomingMessage>,
+        main_tx: &mpsc::UnboundedSender<ModemMessage>,
         line_event: LineEvent,
     ) -> Result<()> {
         debug!("ModemStateMachine transition_state: LineEvent: {line_event:?}");
@@ -171,7 +193,7 @@ impl ModemStateMachine {
 
     async fn process_event(
         &mut self,
-        main_tx: &mpsc::UnboundedSender<ModemIncomingMessage>,
+        main_tx: &mpsc::UnboundedSender<ModemMessage>,
         modem_event: ModemEvent,
     ) -> Result<StateMachineState> {
         match (take(&mut self.state), modem_event) {
@@ -179,11 +201,24 @@ impl ModemStateMachine {
             (
                 StateMachineState::UnsolicitedMessage {
                     message_kind,
+                    mut content,
                     interrupted_command,
-                    ..
                 },
-                ModemEvent::Data(content),
+                ModemEvent::Data(line),
             ) => {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content.push_str(&line);
+
+                if !message_kind.is_complete(&content) {
+                    return Ok(StateMachineState::UnsolicitedMessage {
+                        message_kind,
+                        content,
+                        interrupted_command,
+                    });
+                }
+
                 self.handle_unsolicited(main_tx, &message_kind, &content)
                     .await;
                 Ok(match interrupted_command {
@@ -203,12 +238,13 @@ impl ModemStateMachine {
                 let sequence = execution.context.sequence;
                 debug!("Unsolicited message header received during command {sequence}: {header:?}");
 
-                if !message_kind.has_next_line() {
+                if !message_kind.has_next_line() && message_kind.is_complete(&header) {
                     self.handle_unsolicited(main_tx, &message_kind, &header)
                         .await;
                     Ok(StateMachineState::Command(execution))
                 } else {
                     Ok(StateMachineState::UnsolicitedMessage {
+                        content: Self::initial_content(&message_kind, header),
                         message_kind,
                         interrupted_command: Some(execution),
                     })
@@ -223,12 +259,13 @@ impl ModemStateMachine {
             ) => {
                 debug!("Unsolicited message header received while idle: {header:?}");
 
-                if !message_kind.has_next_line() {
+                if !message_kind.has_next_line() && message_kind.is_complete(&header) {
                     self.handle_unsolicited(main_tx, &message_kind, &header)
                         .await;
                     Ok(StateMachineState::Idle)
                 } else {
                     Ok(StateMachineState::UnsolicitedMessage {
+                        content: Self::initial_content(&message_kind, header),
                         message_kind,
                         interrupted_command: None,
                     })
@@ -306,7 +343,12 @@ impl ModemStateMachine {
                 execution.context.response_buffer.push_str(&content);
                 execution.context.response_buffer.push('\n');
 
-                if execution.context.state.is_complete(&content) {
+                if execution
+                    .context
+                    .state
+                    .is_complete(&content, &execution.context.response_buffer)
+                {
+                    self.consecutive_timeouts = 0;
                     match self
                         .handlers
                         .command_responder(
@@ -341,7 +383,7 @@ impl ModemStateMachine {
 
     async fn handle_unsolicited(
         &self,
-        main_tx: &mpsc::UnboundedSender<ModemIncomingMessage>,
+        main_tx: &mpsc::UnboundedSender<ModemMessage>,
         message_kind: &UnsolicitedMessageKind,
         content: &str,
     ) {
@@ -352,18 +394,37 @@ impl ModemStateMachine {
         {
             Ok(message) => {
                 if let Some(message) = message {
-                    let _ = main_tx.send(message);
+                    let _ = main_tx.send(ModemMessage {
+                        modem_id: self.modem_id.clone(),
+                        message,
+                    });
                 }
             }
             Err(e) => error!("Couldn't handle incoming SMS message with error: {e:?}"),
         }
     }
 
+    /// Notifications with data on the next line start empty, otherwise the header is the first line.
+    fn initial_content(message_kind: &UnsolicitedMessageKind, header: String) -> String {
+        if message_kind.has_next_line() {
+            String::new()
+        } else {
+            header
+        }
+    }
+
     fn classify_line(&self, content: &str) -> ModemEvent {
         let trimmed = content.trim();
 
+        // Some command responses share their prefix with a notification, eg: +CUSD and +CREG.
+        if let StateMachineState::Command(exec) = &self.state {
+            if trimmed.starts_with(exec.command.request.expected_response_prefix(self.profile)) {
+                return ModemEvent::CommandResponse(trimmed.to_string());
+            }
+        }
+
         // Prioritise unsolicited messages regardless of current state.
-        if let Some(message_kind) = UnsolicitedMessageKind::from_header(trimmed) {
+        if let Some(message_kind) = UnsolicitedMessageKind::from_header(trimmed, self.profile) {
             return ModemEvent::UnsolicitedMessage {
                 message_kind,
                 header: trimmed.to_string(),
@@ -374,7 +435,7 @@ impl ModemStateMachine {
         if let StateMachineState::Command(exec) = &self.state {
             if trimmed == "OK"
                 || trimmed == "ERROR"
-                || trimmed.starts_with(exec.command.request.expected_response_prefix())
+                || trimmed.starts_with(exec.command.request.expected_response_prefix(self.profile))
                 || trimmed.starts_with("+CME ERROR:")
                 || trimmed.starts_with("+CMS ERROR:")
             {
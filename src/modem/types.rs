@@ -1,11 +1,16 @@
 #![cfg_attr(not(feature = "http-server"), allow(dead_code))]
 
+use crate::config::ModemProfile;
+use crate::modem::commands::CommandPriority;
+use crate::modem::parsers::is_cusd_complete;
 use serde::{Deserialize, Serialize};
 use sms_types::gnss::{FixStatus, PositionReport};
-use sms_types::modem::ModemStatusUpdateState;
+use sms_types::modem::{ModemStatusUpdateState, NetworkRegistration, SimStatus, UssdMessage};
 use sms_types::sms::{SmsIncomingMessage, SmsPartialDeliveryReport};
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::oneshot;
 
 #[derive(Debug, Clone)]
 pub enum ModemRequest {
@@ -16,22 +21,51 @@ pub enum ModemRequest {
     GetServiceProvider,
     GetBatteryLevel,
 
+    // Hardware and SIM identity.
+    GetIMEI,
+    GetIMSI,
+    GetICCID,
+    GetManufacturer,
+    GetModel,
+    GetFirmwareVersion,
+    GetSubscriberNumber,
+
+    // A USSD code, or a reply to a USSD menu.
+    SendUSSD { code: String },
+    CancelUSSD,
+
+    // Hang up (reject) the current incoming call.
+    HangUp,
+
+    // Plain AT, sent by the watchdog to check the modem is still responding.
+    Probe,
+
     // These only work if GNSS is enabled in modem config.
     GetGNSSStatus,
     GetGNSSLocation,
 }
 impl ModemRequest {
     const TIMEOUT_SMS: Duration = Duration::from_secs(30);
+    const TIMEOUT_USSD: Duration = Duration::from_secs(30);
     const TIMEOUT_DEFAULT: Duration = Duration::from_secs(5);
 
     pub fn get_default_timeout(&self) -> Duration {
         match self {
             ModemRequest::SendSMS { .. } => Self::TIMEOUT_SMS,
+            ModemRequest::SendUSSD { .. } => Self::TIMEOUT_USSD,
             _ => Self::TIMEOUT_DEFAULT,
         }
     }
 
-    pub fn expected_response_prefix(&self) -> &'static str {
+    /// The queue lane for the request, multipart messages are queued as bulk by the sender.
+    pub fn get_default_priority(&self) -> CommandPriority {
+        match self {
+            ModemRequest::SendSMS { .. } => CommandPriority::Single,
+            _ => CommandPriority::Interactive,
+        }
+    }
+
+    pub fn expected_response_prefix(&self, profile: ModemProfile) -> &'static str {
         match self {
             ModemRequest::SendSMS { .. } => "+CMGS:",
             ModemRequest::GetNetworkStatus => "+CREG:",
@@ -39,8 +73,17 @@ impl ModemRequest {
             ModemRequest::GetNetworkOperator => "+COPS:",
             ModemRequest::GetServiceProvider => "+CSPN:",
             ModemRequest::GetBatteryLevel => "+CBC:",
-            ModemRequest::GetGNSSStatus => "+CGPSSTATUS:",
-            ModemRequest::GetGNSSLocation => "+CGNSINF:",
+            ModemRequest::GetIMEI => "+CGSN:",
+            ModemRequest::GetIMSI => "+CIMI:",
+            ModemRequest::GetICCID => "+CCID:",
+            ModemRequest::GetManufacturer => "+CGMI:",
+            ModemRequest::GetModel => "+CGMM:",
+            ModemRequest::GetFirmwareVersion => "+CGMR:",
+            ModemRequest::GetSubscriberNumber => "+CNUM:",
+            ModemRequest::SendUSSD { .. } => "+CUSD:",
+            ModemRequest::CancelUSSD | ModemRequest::HangUp | ModemRequest::Probe => "OK",
+            ModemRequest::GetGNSSStatus => profile.gnss_status_prefix(),
+            ModemRequest::GetGNSSLocation => profile.gnss_location_prefix(),
         }
     }
 }
@@ -51,7 +94,7 @@ pub enum ModemResponse {
     SendResult(u8),
     NetworkStatus {
         registration: u8,
-        technology: u8,
+        technology: Option<u8>,
     },
     SignalStrength {
         rssi: i32,
@@ -68,8 +111,13 @@ pub enum ModemResponse {
         charge: u8,
         voltage: f32,
     },
+    Identity(String),
+    SubscriberNumber(Option<String>),
     GNSSStatus(FixStatus),
     GNSSLocation(PositionReport),
+    USSDResult(UssdMessage),
+    CallEnded,
+    Alive,
     Error(String),
 }
 impl Display for ModemResponse {
@@ -79,7 +127,12 @@ impl Display for ModemResponse {
             ModemResponse::NetworkStatus {
                 registration,
                 technology,
-            } => write!(f, "NetworkStatus: Reg: {registration}, Tech: {technology}"),
+            } => match technology {
+                Some(technology) => {
+                    write!(f, "NetworkStatus: Reg: {registration}, Tech: {technology}")
+                }
+                None => write!(f, "NetworkStatus: Reg: {registration}"),
+            },
             ModemResponse::SignalStrength { rssi, ber } => {
                 write!(f, "SignalStrength: {rssi} dBm ({ber})")
             }
@@ -95,8 +148,15 @@ impl Display for ModemResponse {
                 f,
                 "BatteryLevel. Status: {status}, Charge: {charge}, Voltage: {voltage}"
             ),
+            ModemResponse::Identity(identity) => write!(f, "Identity: {identity}"),
+            ModemResponse::SubscriberNumber(phone_number) => {
+                write!(f, "SubscriberNumber: {phone_number:?}")
+            }
             ModemResponse::GNSSStatus(status) => write!(f, "GNSS-Status: {status:?}"),
             ModemResponse::GNSSLocation(location) => write!(f, "GNSS-Location: {location:?}"),
+            ModemResponse::USSDResult(message) => write!(f, "USSD: {message:?}"),
+            ModemResponse::CallEnded => write!(f, "CallEnded"),
+            ModemResponse::Alive => write!(f, "Alive"),
             ModemResponse::Error(message) => write!(f, "Error: {message}"),
         }
     }
@@ -122,6 +182,16 @@ impl From<ModemStatus> for ModemStatusUpdateState {
     }
 }
 
+/// How busy a modem's command queue is, updated by the worker as commands are queued and started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Commands waiting for the modem, not counting the one in progress.
+    pub depth: usize,
+
+    /// How long the last command to start waited for the modem.
+    pub last_wait: Duration,
+}
+
 #[derive(Debug)]
 pub enum ModemEvent {
     UnsolicitedMessage {
@@ -140,22 +210,35 @@ pub enum UnsolicitedMessageKind {
     NetworkStatusChange,
     ShuttingDown,
     GNSSPositionReport,
+    IncomingUSSD,
+    Ring,
+    IncomingCall,
 }
 impl UnsolicitedMessageKind {
-    pub fn from_header(header: &str) -> Option<Self> {
+    pub fn from_header(header: &str, profile: ModemProfile) -> Option<Self> {
         if header.starts_with("+CMT") {
             Some(UnsolicitedMessageKind::IncomingSMS)
         } else if header.starts_with("+CDS") {
             Some(UnsolicitedMessageKind::DeliveryReport)
-        } else if header.starts_with("+CGREG:") {
+        } else if header.starts_with("+CREG:")
+            || header.starts_with("+CGREG:")
+            || header.starts_with("+CEREG:")
+        {
             Some(UnsolicitedMessageKind::NetworkStatusChange)
-        } else if header.starts_with("+UGNSINF") {
+        } else if profile
+            .gnss_report_header()
+            .is_some_and(|report_header| header.starts_with(report_header))
+        {
             Some(UnsolicitedMessageKind::GNSSPositionReport)
+        } else if header.starts_with("+CUSD:") {
+            Some(UnsolicitedMessageKind::IncomingUSSD)
+        } else if header.starts_with("+CLIP:") {
+            Some(UnsolicitedMessageKind::IncomingCall)
         } else {
             match header {
-                "NORMAL POWER DOWN" | "POWER DOWN" | "SHUTDOWN" | "POWERING DOWN" => {
-                    Some(UnsolicitedMessageKind::ShuttingDown)
-                }
+                "RING" => Some(UnsolicitedMessageKind::Ring),
+                "NORMAL POWER DOWN" | "POWER DOWN" | "POWERED DOWN" | "SHUTDOWN"
+                | "POWERING DOWN" => Some(UnsolicitedMessageKind::ShuttingDown),
                 _ => None,
             }
         }
@@ -164,22 +247,50 @@ impl UnsolicitedMessageKind {
     /// Check if the notification contains additional data on a new line.
     /// Most send a header line first, newline then the actual data, some don't.
     pub fn has_next_line(&self) -> bool {
+        !matches!(
+            self,
+            UnsolicitedMessageKind::ShuttingDown
+                | UnsolicitedMessageKind::NetworkStatusChange
+                | UnsolicitedMessageKind::GNSSPositionReport
+                | UnsolicitedMessageKind::IncomingUSSD
+                | UnsolicitedMessageKind::Ring
+                | UnsolicitedMessageKind::IncomingCall
+        )
+    }
+
+    /// Check if the content read so far is the whole notification, as USSD text can span lines.
+    pub fn is_complete(&self, content: &str) -> bool {
         match self {
-            UnsolicitedMessageKind::ShuttingDown => false,
-            UnsolicitedMessageKind::GNSSPositionReport => false,
+            UnsolicitedMessageKind::IncomingUSSD => is_cusd_complete(content),
             _ => true,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum ModemIncomingMessage {
     IncomingSMS(SmsIncomingMessage),
+
+    /// A message imported from modem storage. The sender is notified once it's been stored,
+    /// and the modem only deletes its copy then, dropping it leaves the message in storage.
+    StoredSMS(SmsIncomingMessage, oneshot::Sender<()>),
     DeliveryReport(SmsPartialDeliveryReport),
     ModemStatusUpdate {
         previous: ModemStatus,
         current: ModemStatus,
+        sim_status: Option<SimStatus>,
     },
-    NetworkStatusChange(u8),
+    NetworkStatusChange(NetworkRegistration),
     GNSSPositionReport(PositionReport),
+    IncomingUSSD(UssdMessage),
+
+    /// The caller's number, if it wasn't withheld.
+    IncomingCall(Option<String>),
+}
+
+/// A ModemIncomingMessage, along with the id of the modem it came from.
+#[derive(Debug)]
+pub struct ModemMessage {
+    pub modem_id: Arc<str>,
+    pub message: ModemIncomingMessage,
 }
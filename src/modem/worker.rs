@@ -1,14 +1,21 @@
-use crate::config::ModemConfig;
+use crate::config::{ModemConfig, WatchdogAction};
 use crate::modem::buffer::LineBuffer;
-use crate::modem::commands::OutgoingCommand;
+use crate::modem::commands::{next_command_sequence, CommandPriority, OutgoingCommand};
+use crate::modem::handlers::decode_incoming_sms;
+use crate::modem::parsers::{parse_cmgl_response, parse_cpin_response};
+use crate::modem::port::{open_port, ModemPort};
+use crate::modem::queue::CommandQueue;
 use crate::modem::state_machine::ModemStateMachine;
-use crate::modem::types::{ModemIncomingMessage, ModemResponse, ModemStatus};
-use anyhow::{anyhow, Context, Result};
-use std::time::Duration;
+use crate::modem::types::{
+    ModemIncomingMessage, ModemMessage, ModemRequest, ModemResponse, ModemStatus, QueueStats,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use sms_types::modem::SimStatus;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::interval;
-use tokio_serial::SerialStream;
 use tracing::log::{debug, error, info, warn};
 
 macro_rules! init_cmd {
@@ -24,20 +31,32 @@ pub enum WorkerEvent {
 }
 
 pub struct ModemWorker {
-    port: SerialStream,
+    id: Arc<str>,
+    port: Box<dyn ModemPort>,
     status: ModemStatus,
     state_machine: ModemStateMachine,
-    main_tx: mpsc::UnboundedSender<ModemIncomingMessage>,
+    main_tx: mpsc::UnboundedSender<ModemMessage>,
+    status_tx: watch::Sender<ModemStatus>,
+    queue_tx: watch::Sender<QueueStats>,
     worker_event_rx: mpsc::UnboundedReceiver<WorkerEvent>,
     config: ModemConfig,
 
+    /// The SIM status from the last initialization, and the one last sent in a status update.
+    sim_status: Option<SimStatus>,
+    reported_sim_status: Option<SimStatus>,
+
+    /// Set once the SIM needs its PUK or rejected our PIN, as retrying could block it for good.
+    sim_locked_out: bool,
+
     #[cfg(feature = "gpio")]
     power_pin: Option<rppal::gpio::OutputPin>,
 }
 impl ModemWorker {
     pub fn new(
-        port: SerialStream,
-        main_tx: mpsc::UnboundedSender<ModemIncomingMessage>,
+        port: Box<dyn ModemPort>,
+        main_tx: mpsc::UnboundedSender<ModemMessage>,
+        status_tx: watch::Sender<ModemStatus>,
+        queue_tx: watch::Sender<QueueStats>,
         config: ModemConfig,
     ) -> Result<Self> {
         let (worker_event_tx, worker_event_rx) = mpsc::unbounded_channel();
@@ -54,13 +73,20 @@ impl ModemWorker {
             None
         };
 
+        let id: Arc<str> = config.id.as_str().into();
         Ok(Self {
+            state_machine: ModemStateMachine::new(worker_event_tx, id.clone(), config.profile),
+            id,
             port,
             status: ModemStatus::Startup,
-            state_machine: ModemStateMachine::new(worker_event_tx),
             main_tx,
+            status_tx,
+            queue_tx,
             worker_event_rx,
             config,
+            sim_status: None,
+            reported_sim_status: None,
+            sim_locked_out: false,
 
             #[cfg(feature = "gpio")]
             power_pin,
@@ -84,11 +110,11 @@ impl ModemWorker {
 
         match self.initialize_modem().await {
             Ok(()) => {
-                info!("Modem initialized successfully!");
+                info!("Modem '{}' initialized successfully!", self.id);
                 self.set_status(ModemStatus::Online);
             }
             Err(e) => {
-                error!("Failed to initialize modem: {e}");
+                error!("Failed to initialize modem '{}': {e}", self.id);
                 self.set_status(ModemStatus::Offline);
             }
         }
@@ -108,11 +134,45 @@ impl ModemWorker {
         let mut timeout_interval = interval(Duration::from_secs(1));
         let mut reconnect_interval = interval(Duration::from_secs(30));
 
+        // The watchdog probes a quiet modem, then recovers it after too many command timeouts.
+        let watchdog_enabled = self.config.watchdog_interval > 0;
+        let watchdog_period = Duration::from_secs(self.config.watchdog_interval.max(1));
+        let mut watchdog_interval = interval(watchdog_period);
+        let mut last_activity = Instant::now();
+
+        // Commands are taken off the channel into priority lanes, up to the channel's size.
+        let mut queue = CommandQueue::new();
+        let queue_capacity = self.config.cmd_channel_buffer_size;
+
         debug!("Starting ModemWorker status loop");
         let mut read_buffer = vec![0u8; self.config.read_buffer_size];
         loop {
             match self.status {
                 ModemStatus::Online => {
+                    while queue.len() < queue_capacity {
+                        match command_rx.try_recv() {
+                            Ok(cmd) => Self::queue_command(&mut queue, cmd),
+                            Err(_) => break,
+                        }
+                    }
+                    if self.state_machine.can_accept_command() {
+                        if let Some(cmd) = queue.pop() {
+                            let waited = cmd.queued_at.elapsed();
+                            debug!(
+                                "Starting command sequence {} ({:?}) after waiting {:?}, {} still queued",
+                                cmd.sequence,
+                                cmd.priority,
+                                waited,
+                                queue.len()
+                            );
+                            self.queue_tx.send_modify(|stats| stats.last_wait = waited);
+                            if let Err(e) = self.state_machine.start_command(cmd).await {
+                                error!("Failed to start command: {e}");
+                            }
+                        }
+                    }
+                    self.publish_queue_depth(queue.len() + command_rx.len());
+
                     tokio::select! {
                         biased;
 
@@ -123,12 +183,9 @@ impl ModemWorker {
                             }
                         },
 
-                        // Accept commands when online and state machine is ready
-                        Some(cmd) = command_rx.recv(), if self.state_machine.can_accept_command() => {
-                            debug!("Received new command sequence {}: {:?}", cmd.sequence, cmd.request);
-                            if let Err(e) = self.state_machine.start_command(cmd).await {
-                                error!("Failed to start command: {e}");
-                            }
+                        // Queue commands while there's room, leaving the rest in the channel.
+                        Some(cmd) = command_rx.recv(), if queue.len() < queue_capacity => {
+                            Self::queue_command(&mut queue, cmd);
                         },
 
                         // Main reader.
@@ -140,6 +197,7 @@ impl ModemWorker {
                                     self.set_status(ModemStatus::Offline);
                                 },
                                 Ok(n) => {
+                                    last_activity = Instant::now();
                                     let main_tx = &self.main_tx;
                                     for line_event in line_buffer.process_data(&read_buffer[..n]) {
                                         if let Err(e) = self.state_machine.transition_state(main_tx, line_event).await {
@@ -168,6 +226,22 @@ impl ModemWorker {
                             if timed_out {
                                 line_buffer.clear();
                             }
+                            let timeouts = self.state_machine.consecutive_timeouts();
+                            if watchdog_enabled && timeouts >= self.config.watchdog_max_timeouts.max(1) {
+                                self.recover_unresponsive().await;
+                                line_buffer.clear();
+                                last_activity = Instant::now();
+                            }
+                        },
+
+                        // Probe the modem if it's been quiet for a whole watchdog period.
+                        _ = watchdog_interval.tick(), if watchdog_enabled => {
+                            let idle = self.state_machine.can_accept_command();
+                            if idle && last_activity.elapsed() >= watchdog_period {
+                                if let Err(e) = self.send_probe().await {
+                                    error!("Failed to send watchdog probe: {e}");
+                                }
+                            }
                         }
                     }
                 }
@@ -185,6 +259,11 @@ impl ModemWorker {
                         .await;
 
                     // Reject any pending commands
+                    for mut cmd in queue.drain() {
+                        let _ = cmd
+                            .respond(ModemResponse::Error("Modem is shutting down".to_string()))
+                            .await;
+                    }
                     while let Ok(mut cmd) = command_rx.try_recv() {
                         let _ = cmd
                             .respond(ModemResponse::Error("Modem is shutting down".to_string()))
@@ -198,6 +277,13 @@ impl ModemWorker {
                     line_buffer.clear();
                 }
                 ModemStatus::Offline => {
+                    for mut cmd in queue.drain() {
+                        let _ = cmd
+                            .respond(ModemResponse::Error("Modem is offline".to_string()))
+                            .await;
+                    }
+                    self.publish_queue_depth(0);
+
                     tokio::select! {
                         // Still process worker events when offline
                         Some(event) = self.worker_event_rx.recv() => {
@@ -232,6 +318,82 @@ impl ModemWorker {
         }
     }
 
+    fn publish_queue_depth(&self, depth: usize) {
+        self.queue_tx
+            .send_if_modified(|stats| std::mem::replace(&mut stats.depth, depth) != depth);
+    }
+
+    fn queue_command(queue: &mut CommandQueue, cmd: OutgoingCommand) {
+        debug!(
+            "Received new command sequence {} ({:?}), queue depth {}: {:?}",
+            cmd.sequence,
+            cmd.priority,
+            queue.len(),
+            cmd.request
+        );
+        queue.push(cmd);
+    }
+
+    /// Send an AT probe through the state machine, so a missing reply counts as a command timeout.
+    async fn send_probe(&mut self) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let sequence = next_command_sequence();
+        debug!("Sending watchdog probe #{sequence} to modem '{}'", self.id);
+
+        self.state_machine
+            .start_command(OutgoingCommand::new(
+                sequence,
+                response_tx,
+                ModemRequest::Probe,
+                CommandPriority::Interactive,
+                None,
+            ))
+            .await?;
+        tokio::spawn(async move {
+            if let Ok(response) = response_rx.await {
+                debug!("Watchdog probe #{sequence} response: {response}");
+            }
+        });
+        Ok(())
+    }
+
+    /// Recover a modem that's still connected but has stopped responding to commands.
+    async fn recover_unresponsive(&mut self) {
+        let action = self.config.watchdog_action;
+        warn!(
+            "Modem '{}' is unresponsive after {} command timeouts in a row, recovering with {action:?}",
+            self.id,
+            self.state_machine.consecutive_timeouts()
+        );
+        self.state_machine
+            .fail_active_command("Modem is unresponsive")
+            .await;
+        self.state_machine.reset_to_idle();
+
+        match action {
+            WatchdogAction::Reinitialize => match self.initialize_modem().await {
+                Ok(()) => info!("Modem '{}' reinitialized by the watchdog", self.id),
+                Err(e) => {
+                    error!("Failed to reinitialize modem '{}': {e}", self.id);
+                    self.set_status(ModemStatus::Offline);
+                }
+            },
+            WatchdogAction::PowerCycle => {
+                #[cfg(feature = "gpio")]
+                self.toggle_gpio_power().await;
+
+                #[cfg(not(feature = "gpio"))]
+                warn!(
+                    "Can't power cycle modem '{}' without the gpio feature!",
+                    self.id
+                );
+
+                self.set_status(ModemStatus::Offline);
+            }
+            WatchdogAction::Offline => self.set_status(ModemStatus::Offline),
+        }
+    }
+
     async fn handle_worker_event(&mut self, event: WorkerEvent) -> Result<()> {
         match event {
             WorkerEvent::SetStatus(status) => self.set_status(status),
@@ -250,18 +412,24 @@ impl ModemWorker {
     }
 
     fn set_status(&mut self, status: ModemStatus) {
-        debug!("ModemWorker Status: {status:?}");
-        if self.status == status {
+        debug!("ModemWorker '{}' Status: {status:?}", self.id);
+        if self.status == status && self.sim_status == self.reported_sim_status {
             return;
         }
 
         let previous = self.status.clone();
         self.status.clone_from(&status);
+        self.status_tx.send_replace(status.clone());
+        self.reported_sim_status = self.sim_status;
 
         // Send message outside of modem for webhooks etc.
-        let message = ModemIncomingMessage::ModemStatusUpdate {
-            previous,
-            current: status.clone(),
+        let message = ModemMessage {
+            modem_id: self.id.clone(),
+            message: ModemIncomingMessage::ModemStatusUpdate {
+                previous,
+                current: status.clone(),
+                sim_status: self.sim_status,
+            },
         };
         match self.main_tx.send(message) {
             Ok(_) => debug!("Sent ModemOnlineStatusUpdate, Status: {status:?}"),
@@ -272,11 +440,22 @@ impl ModemWorker {
     }
 
     async fn try_reconnect(&mut self) -> Result<bool> {
-        if self.status != ModemStatus::Offline {
+        if self.status != ModemStatus::Offline || self.sim_locked_out {
             return Ok(false);
         }
 
-        match self.test_connection().await {
+        // Reopen the port, as a USB modem may have re-enumerated since it went offline.
+        // The old port is dropped first so the device isn't held open twice.
+        self.port = Box::new(tokio::io::empty());
+        let connected = match open_port(&self.config) {
+            Ok(port) => {
+                self.port = port;
+                self.test_connection().await
+            }
+            Err(e) => Err(e),
+        };
+
+        match connected {
             Ok(_) => {
                 debug!("Basic connection test passed, initializing modem...");
 
@@ -289,12 +468,15 @@ impl ModemWorker {
                     }
                     Err(e) => {
                         error!("Reconnection failed during initialization: {e}");
+
+                        // Still offline, but this reports any change in SIM status.
+                        self.set_status(ModemStatus::Offline);
                         Ok(false)
                     }
                 }
             }
             Err(e) => {
-                debug!("Basic connection test failed: {e}");
+                debug!("Basic connection test failed: {e:#}");
 
                 #[cfg(feature = "gpio")]
                 if self.config.gpio_repower {
@@ -309,54 +491,201 @@ impl ModemWorker {
 
     async fn initialize_modem(&mut self) -> Result<()> {
         info!("Sending modem initialization commands");
+        for (command, expected) in [
+            init_cmd!("ATZ\r\n", "OK"),  // Reset
+            init_cmd!("ATE0\r\n", "OK"), // Disable echo
+        ] {
+            self.send_initialization_command(&command, &expected)
+                .await?;
+        }
+
+        // Everything else depends on the SIM, and fails with unhelpful errors while it's locked.
+        self.unlock_sim().await?;
+
         let mut initialization_commands: Vec<(Vec<u8>, Vec<u8>)> = vec![
-            init_cmd!("ATZ\r\n", "OK"),                              // Reset
-            init_cmd!("ATE0\r\n", "OK"),                             // Disable echo
             init_cmd!("AT+CMGF=0\r\n", "OK"), // Set SMS message format to PDU
             init_cmd!("AT+CSCS=\"GSM\"\r\n", "OK"), // Use GSM 7-bit alphabet
             init_cmd!("AT+CNMI=2,2,0,1,0\r\n", "OK"), // Receive all incoming SMS messages and delivery reports
             init_cmd!("AT+CSMP=49,167,0,0\r\n", "OK"), // Receive delivery receipts from sent messages
             init_cmd!("AT+CPMS=\"ME\",\"ME\",\"ME\"\r\n", "+CPMS:"), // Store all messages in memory only
+            init_cmd!("AT+CREG=2\r\n", "OK"), // Report registration changes, with location
+            init_cmd!("AT+CGREG=2\r\n", "OK"), // Report packet domain registration changes, with location
+            init_cmd!("AT+CLIP=1\r\n", "OK"),  // Report caller ID for incoming calls
         ];
 
+        // Vendor specific commands for the modem family, eg: LTE registration reports.
+        let profile = self.config.profile;
+        for (command, expected) in profile.init_commands() {
+            initialization_commands.push(init_cmd!(format!("{command}\r\n"), expected));
+        }
+
         // If GNSS is enabled power it on and start its receiver.
         if self.config.gnss_enabled {
             debug!(
                 "The GNSS module is enabled with a report interval of {}! Powering on...",
                 self.config.gnss_report_interval
             );
-            initialization_commands.push(init_cmd!("AT+CGNSPWR=1\r\n", "OK")); // Power on
-            initialization_commands.push(init_cmd!("AT+CGPSRST=0\r\n", "OK")); // Cold start
-
-            // Create GNSS report interval command (0 = disabled).
-            let interval_command = format!("AT+CGNSURC={}\r\n", self.config.gnss_report_interval)
-                .as_bytes()
-                .to_vec();
-            initialization_commands.push((interval_command, b"OK".to_vec())); // Set navigation URC report interval
+            if profile.gnss_report_header().is_none() && self.config.gnss_report_interval > 0 {
+                warn!("The {profile:?} modem profile doesn't support GNSS position reports, ignoring the report interval!");
+            }
+            for (command, expected) in profile.gnss_init_commands(self.config.gnss_report_interval)
+            {
+                initialization_commands.push(init_cmd!(format!("{command}\r\n"), expected));
+            }
+        }
+
+        // Any extra commands from the config go last, so they can override the defaults.
+        for init_command in &self.config.init_commands {
+            initialization_commands.push(init_cmd!(
+                format!("{}\r\n", init_command.command),
+                init_command.expected
+            ));
         }
 
         for (command, expected) in initialization_commands {
-            let command_str = String::from_utf8_lossy(&command);
-            debug!("Sending initialization command: {command_str:?}");
+            self.send_initialization_command(&command, &expected)
+                .await?;
+        }
 
-            self.port.write_all(&command).await?;
+        debug!("Modem initialization completed successfully!");
+
+        // Messages received while nothing was listening for URCs are left in modem storage.
+        if let Err(e) = self.import_stored_messages().await {
+            warn!(
+                "Failed to import stored messages from modem '{}': {e}",
+                self.id
+            );
+        }
+        Ok(())
+    }
+
+    async fn send_initialization_command(&mut self, command: &[u8], expected: &[u8]) -> Result<()> {
+        let command_str = String::from_utf8_lossy(command);
+        debug!("Sending initialization command: {command_str:?}");
+
+        self.port.write_all(command).await?;
+
+        let response = self.read_response_until_ok().await?;
+        let response_str = String::from_utf8_lossy(&response);
+        let expected_str = String::from_utf8_lossy(expected);
+
+        debug!("Response: {}", response_str.trim());
+        if !response_str.contains(&*expected_str) {
+            return Err(anyhow!(
+                "Initialization command '{:?}' failed. Expected: '{}', Got: '{}'",
+                command_str,
+                expected_str,
+                response_str.trim()
+            ));
+        }
+        Ok(())
+    }
 
+    /// Make sure the SIM is ready, entering the configured PIN if it's locked. The PIN is
+    /// only ever entered once, as each wrong attempt counts towards blocking the SIM.
+    async fn unlock_sim(&mut self) -> Result<()> {
+        let mut status = self.read_sim_status().await?;
+        if status == SimStatus::PinRequired {
+            let Some(pin) = self.config.read_sim_pin()? else {
+                bail!("The SIM is locked, set a sim_pin or sim_pin_file to unlock it");
+            };
+
+            info!("Unlocking the SIM for modem '{}'", self.id);
+            self.port
+                .write_all(format!("AT+CPIN=\"{pin}\"\r\n").as_bytes())
+                .await?;
             let response = self.read_response_until_ok().await?;
             let response_str = String::from_utf8_lossy(&response);
-            let expected_str = String::from_utf8_lossy(&expected);
-
-            debug!("Response: {}", response_str.trim());
-            if !response_str.contains(&*expected_str) {
-                return Err(anyhow!(
-                    "Initialization command '{:?}' failed. Expected: '{}', Got: '{}'",
-                    command_str,
-                    expected_str,
+            if !response_str.contains("OK") {
+                self.sim_locked_out = true;
+                bail!(
+                    "The SIM rejected its PIN, not retrying to avoid blocking it: '{}'",
                     response_str.trim()
-                ));
+                );
             }
+            status = self.read_sim_status().await?;
         }
 
-        debug!("Modem initialization completed successfully!");
+        match status {
+            SimStatus::Ready => Ok(()),
+            SimStatus::PukRequired => {
+                self.sim_locked_out = true;
+                bail!("The SIM is blocked and must be unblocked with its PUK, not retrying")
+            }
+            status => bail!("The SIM isn't ready: {status:?}"),
+        }
+    }
+
+    async fn read_sim_status(&mut self) -> Result<SimStatus> {
+        self.port.write_all(b"AT+CPIN?\r\n").await?;
+        let response = self.read_response_until_ok().await?;
+        let status = parse_cpin_response(&String::from_utf8_lossy(&response));
+
+        // Failed checks are cleared, rather than reporting a stale status.
+        self.sim_status = status.as_ref().ok().copied();
+        status
+    }
+
+    /// List received messages held in modem storage, passing each on as an incoming
+    /// message and then deleting it once stored so the storage never fills up. Messages
+    /// that couldn't be stored are left in place, to be imported again next time.
+    async fn import_stored_messages(&mut self) -> Result<()> {
+        self.port.write_all(b"AT+CMGL=4\r\n").await?;
+        let response = self.read_response_until_ok().await?;
+        let response_str = String::from_utf8_lossy(&response);
+        if !response_str.contains("OK") {
+            bail!("Listing stored messages failed: '{}'", response_str.trim());
+        }
+
+        let stored = parse_cmgl_response(&response_str)?;
+        if !stored.is_empty() {
+            info!(
+                "Importing {} stored messages from modem '{}'",
+                stored.len(),
+                self.id
+            );
+        }
+        for (index, status, pdu) in stored {
+            // Only received messages (REC UNREAD, REC READ), not drafts or sent messages.
+            if status > 1 {
+                debug!("Skipping stored message {index} with status {status}");
+                continue;
+            }
+
+            let incoming = match decode_incoming_sms(&pdu) {
+                Ok(incoming) => incoming,
+                Err(e) => {
+                    warn!("Couldn't decode stored message {index}, leaving it in storage: {e}");
+                    continue;
+                }
+            };
+            let (stored_tx, stored_rx) = oneshot::channel();
+            self.main_tx
+                .send(ModemMessage {
+                    modem_id: self.id.clone(),
+                    message: ModemIncomingMessage::StoredSMS(incoming, stored_tx),
+                })
+                .context("Failed to send stored message")?;
+
+            // Wait for the receiver however long it takes, so a message it's still
+            // storing is never imported again. It's dropped if storing fails.
+            if stored_rx.await.is_err() {
+                warn!("Stored message {index} couldn't be stored, leaving it in storage");
+                continue;
+            }
+
+            self.port
+                .write_all(format!("AT+CMGD={index}\r\n").as_bytes())
+                .await?;
+            let response = self.read_response_until_ok().await?;
+            let response_str = String::from_utf8_lossy(&response);
+            if !response_str.contains("OK") {
+                bail!(
+                    "Deleting stored message {index} failed: '{}'",
+                    response_str.trim()
+                );
+            }
+        }
         Ok(())
     }
 
@@ -2,166 +2,179 @@
 
 use crate::config::DatabaseConfig;
 use crate::sms::encryption::SMSEncryption;
+use crate::sms::outbox::{OutboxEntry, PartialSend};
+use crate::sms::storage::{
+    DeliveryReportCandidate, MultipartFragment, ReencryptedRow, SMSStorage, ENCRYPTED_COLUMNS,
+};
+use crate::webhooks::{WebhookDeadLetter, WebhookDelivery};
 use anyhow::{Context, Result};
-use sms_types::sms::{SmsDeliveryReport, SmsMessage};
-use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
-use sqlx::{Row, SqlitePool};
-use std::time::Duration;
-use tracing::log::debug;
-
-const SCHEMA_SQL: &str = include_str!("schemas/sqlite.sql");
-
-fn build_pagination_query(
-    base_query: &str,
-    order_by: &str,
-    limit: Option<u64>,
-    offset: Option<u64>,
-    reverse: bool,
-) -> String {
-    let order_direction = if reverse { "ASC" } else { "DESC" };
-    let mut query = format!("{base_query} ORDER BY {order_by} {order_direction}");
-
-    if let Some(limit_val) = limit {
-        query.push_str(&format!(" LIMIT {limit_val}"));
+use sms_types::modem::IncomingCall;
+use sms_types::sms::{
+    SmsDeliveryReport, SmsIncomingMessage, SmsMessage, SmsMessagePart, SmsMultipartHeader,
+    SmsOutgoingMessage, SmsPartialDeliveryReport, SmsScheduledMessage,
+};
+use tracing::log::{debug, info};
+
+const TOKEN_BACKFILL_BATCH_SIZE: u32 = 500;
+
+/// Connect to the storage backend for database_url, which is Postgres for
+/// postgres:// URLs and otherwise an SQLite file path.
+#[cfg_attr(not(feature = "db-postgres"), allow(unused_variables))]
+async fn connect_storage(
+    database_url: &str,
+    modem_ids: Vec<String>,
+) -> Result<Box<dyn SMSStorage>> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        #[cfg(feature = "db-postgres")]
+        return Ok(Box::new(
+            crate::sms::storage::PostgresStorage::connect(database_url, modem_ids).await?,
+        ));
+
+        #[cfg(not(feature = "db-postgres"))]
+        anyhow::bail!("A Postgres database_url requires building with the db-postgres feature");
     }
 
-    if let Some(offset_val) = offset {
-        query.push_str(&format!(" OFFSET {offset_val}"));
-    }
+    #[cfg(feature = "db-sqlite")]
+    return Ok(Box::new(
+        crate::sms::storage::SqliteStorage::connect(database_url).await?,
+    ));
 
-    query
+    #[cfg(not(feature = "db-sqlite"))]
+    anyhow::bail!("An SQLite database_url requires building with the db-sqlite feature");
 }
 
+/// Encrypts content and builds search tokens before handing off to the storage backend.
 pub struct SMSDatabase {
-    pool: SqlitePool,
+    storage: Box<dyn SMSStorage>,
     encryption: SMSEncryption,
+    store_raw_pdu: bool,
 }
-impl SMSDatabase {
-    pub async fn connect(config: DatabaseConfig) -> Result<Self> {
-        let connection_options = SqliteConnectOptions::new()
-            .filename(&config.database_url)
-            .create_if_missing(true)
-            .journal_mode(SqliteJournalMode::Wal)
-            .synchronous(SqliteSynchronous::Normal)
-            .busy_timeout(Duration::from_secs(30));
-
-        let pool = SqlitePoolOptions::new()
-            .max_connections(20)
-            .min_connections(5)
-            .acquire_timeout(Duration::from_secs(30))
-            .idle_timeout(None)
-            .max_lifetime(None)
-            .test_before_acquire(true)
-            .after_connect(|conn, _meta| {
-                Box::pin(async move {
-                    // Optimise connection.
-                    sqlx::query("PRAGMA foreign_keys = ON")
-                        .execute(&mut *conn)
-                        .await?;
-                    sqlx::query("PRAGMA cache_size = -64000")
-                        .execute(&mut *conn)
-                        .await?; // 64MB Cache
-                    sqlx::query("PRAGMA temp_store = memory")
-                        .execute(&mut *conn)
-                        .await?;
-                    Ok(())
-                })
-            })
-            .connect_with(connection_options)
-            .await
-            .context("Failed to connect to database")?;
 
+impl SMSDatabase {
+    /// Connect for a server with the given modems, as a Postgres database can be shared with others.
+    pub async fn connect(config: &DatabaseConfig, modem_ids: Vec<String>) -> Result<Self> {
         let db = Self {
-            pool,
-            encryption: SMSEncryption::new(config.encryption_key),
+            storage: connect_storage(&config.database_url, modem_ids).await?,
+            encryption: SMSEncryption::from_config(config)?,
+            store_raw_pdu: config.store_raw_pdu,
         };
-        db.init_tables().await?;
+        db.backfill_message_tokens().await?;
         Ok(db)
     }
 
-    async fn init_tables(&self) -> Result<()> {
-        sqlx::raw_sql(SCHEMA_SQL)
-            .execute(&self.pool)
-            .await
-            .context("Failed to init tables")?;
-
-        debug!("SMSDatabase tables initialized successfully!");
-        Ok(())
-    }
-
     pub async fn insert_message(&self, message: &SmsMessage, is_final: bool) -> Result<i64> {
         let encrypted_content = self.encryption.encrypt(&message.message_content)?;
-        let result = if is_final {
-            sqlx::query(
-                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status, completed_at) VALUES (?, ?, ?, ?, ?, unixepoch())"
-            )
-        } else {
-            sqlx::query(
-                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status) VALUES (?, ?, ?, ?, ?)"
+        let encrypted_raw_pdu = self.encrypt_raw_pdu(message.raw_pdu.as_deref())?;
+        let tokens = self.encryption.tokenize(&message.message_content);
+        self.storage
+            .insert_message(
+                message,
+                encrypted_content,
+                encrypted_raw_pdu,
+                tokens,
+                is_final,
             )
-        }
-            .bind(&message.phone_number)
-            .bind(encrypted_content)
-            .bind(message.message_reference)
-            .bind(message.is_outgoing)
-            .bind(message.status)
-            .execute(&self.pool)
             .await
-            .context("Failed to insert SmsMessage")?;
+    }
 
-        Ok(result.last_insert_rowid())
+    fn encrypt_raw_pdu(&self, raw_pdu: Option<&str>) -> Result<Option<String>> {
+        raw_pdu
+            .map(|raw_pdu| self.encryption.encrypt(raw_pdu))
+            .transpose()
     }
 
-    pub async fn insert_send_failure(
-        &self,
-        message_id: i64,
-        error_message: &String,
-    ) -> Result<i64> {
-        let result =
-            sqlx::query("INSERT INTO send_failures (message_id, error_message) VALUES (?, ?)")
-                .bind(message_id)
-                .bind(error_message)
-                .execute(&self.pool)
-                .await
-                .context("Failed to insert send failure")?;
+    /// The raw PDU of incoming messages is only kept if enabled, as it's only needed to debug decoding issues.
+    pub fn store_raw_pdu(&self) -> bool {
+        self.store_raw_pdu
+    }
+
+    /// Build search tokens for any messages stored before the token index existed.
+    async fn backfill_message_tokens(&self) -> Result<()> {
+        let mut last_message_id = 0;
+        let mut indexed = 0;
+        loop {
+            let rows = self
+                .storage
+                .get_untokenized_messages(last_message_id, TOKEN_BACKFILL_BATCH_SIZE)
+                .await?;
+
+            let Some((last, _)) = rows.last() else {
+                break;
+            };
+            last_message_id = *last;
+            indexed += rows.len();
+
+            let messages = rows
+                .into_iter()
+                .map(|(message_id, encrypted)| -> Result<(i64, Vec<String>)> {
+                    let content = self.encryption.decrypt(&encrypted)?;
+                    Ok((message_id, self.encryption.tokenize(&content)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.storage.insert_message_tokens(messages).await?;
+        }
 
-        Ok(result.last_insert_rowid())
+        if indexed > 0 {
+            info!("Built search tokens for {indexed} existing messages");
+        }
+        Ok(())
+    }
+
+    pub async fn insert_send_failure(&self, message_id: i64, error_message: &str) -> Result<i64> {
+        self.storage
+            .insert_send_failure(message_id, error_message)
+            .await
     }
 
     pub async fn insert_delivery_report(
         &self,
         message_id: i64,
-        status: u8,
+        part_index: u8,
+        report: &SmsPartialDeliveryReport,
         is_final: bool,
+        ambiguous: bool,
     ) -> Result<i64> {
-        let result = sqlx::query(
-            "INSERT INTO delivery_reports (message_id, status, is_final) VALUES (?, ?, ?)",
-        )
-        .bind(message_id)
-        .bind(status)
-        .bind(is_final)
-        .execute(&self.pool)
-        .await
-        .context("Failed to insert delivery report")?;
+        self.storage
+            .insert_delivery_report(message_id, part_index, report, is_final, ambiguous)
+            .await
+    }
 
-        Ok(result.last_insert_rowid())
+    pub async fn insert_message_parts(
+        &self,
+        message_id: i64,
+        references: &[u8],
+        stored: usize,
+    ) -> Result<()> {
+        self.storage
+            .insert_message_parts(message_id, references, stored)
+            .await
     }
 
-    pub async fn get_delivery_report_target_message(
+    pub async fn get_delivery_report_candidates(
         &self,
-        phone_number: &String,
+        phone_number: &str,
         reference_id: u8,
-    ) -> Result<Option<i64>> {
-        let result = sqlx::query_scalar(
-            "SELECT message_id FROM messages WHERE completed_at IS NULL AND is_outgoing = 1 AND phone_number = ? AND message_reference = ? ORDER BY message_id DESC LIMIT 1"
-        )
-            .bind(phone_number)
-            .bind(reference_id)
-            .fetch_optional(&self.pool)
+        modem_id: &str,
+    ) -> Result<Vec<DeliveryReportCandidate>> {
+        self.storage
+            .get_delivery_report_candidates(phone_number, reference_id, modem_id)
             .await
-            .context("Failed to query appropriate target message for delivery report")?;
+    }
+
+    pub async fn update_message_part_status(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        status: u8,
+        completed: bool,
+    ) -> Result<()> {
+        self.storage
+            .update_message_part_status(message_id, part_index, status, completed)
+            .await
+    }
 
-        Ok(result)
+    pub async fn get_message_parts(&self, message_id: i64) -> Result<Vec<SmsMessagePart>> {
+        self.storage.get_message_parts(message_id).await
     }
 
     pub async fn update_message_status(
@@ -170,22 +183,56 @@ impl SMSDatabase {
         status: u8,
         completed: bool,
     ) -> Result<()> {
-        let query = if completed {
-            sqlx::query(
-                "UPDATE messages SET status = ?, completed_at = unixepoch() WHERE message_id = ?",
-            )
-        } else {
-            sqlx::query("UPDATE messages SET status = ? WHERE message_id = ?")
-        };
+        self.storage
+            .update_message_status(message_id, status, completed)
+            .await
+    }
 
-        query
-            .bind(status)
-            .bind(message_id)
-            .execute(&self.pool)
+    pub async fn insert_multipart_fragment(
+        &self,
+        modem_id: &str,
+        message: &SmsIncomingMessage,
+        header: &SmsMultipartHeader,
+    ) -> Result<()> {
+        let encrypted_content = self.encryption.encrypt(&message.content)?;
+        let encrypted_raw_pdu = self.encrypt_raw_pdu(message.raw_pdu.as_deref())?;
+        self.storage
+            .insert_multipart_fragment(
+                modem_id,
+                &message.phone_number,
+                header,
+                encrypted_content,
+                message.sent_at,
+                encrypted_raw_pdu,
+            )
             .await
-            .context("Failed to update message status")?;
+    }
 
-        Ok(())
+    pub async fn get_multipart_fragments(&self) -> Result<Vec<MultipartFragment>> {
+        self.storage
+            .get_multipart_fragments()
+            .await?
+            .into_iter()
+            .map(|mut fragment| -> Result<MultipartFragment> {
+                fragment.content = self.encryption.decrypt(&fragment.content)?;
+                fragment.raw_pdu = fragment
+                    .raw_pdu
+                    .map(|raw_pdu| self.encryption.decrypt(&raw_pdu))
+                    .transpose()?;
+                Ok(fragment)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    pub async fn delete_multipart_fragments(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        message_reference: u16,
+    ) -> Result<()> {
+        self.storage
+            .delete_multipart_fragments(modem_id, phone_number, message_reference)
+            .await
     }
 
     pub async fn update_friendly_name(
@@ -193,35 +240,13 @@ impl SMSDatabase {
         phone_number: String,
         friendly_name: Option<String>,
     ) -> Result<()> {
-        match friendly_name {
-            Some(name) => {
-                sqlx::query(
-                    "INSERT INTO friendly_names (phone_number, friendly_name) VALUES (?, ?) ON CONFLICT(phone_number) DO UPDATE SET friendly_name = excluded.friendly_name"
-                )
-                    .bind(&phone_number)
-                    .bind(&name)
-                    .execute(&self.pool)
-                    .await
-                    .context("Failed to insert friendly name")?;
-            }
-            None => {
-                sqlx::query("DELETE FROM friendly_names WHERE phone_number = ?")
-                    .bind(&phone_number)
-                    .execute(&self.pool)
-                    .await
-                    .context("Failed to delete friendly name")?;
-            }
-        }
-
-        Ok(())
+        self.storage
+            .update_friendly_name(&phone_number, friendly_name.as_deref())
+            .await
     }
 
     pub async fn get_friendly_name(&self, phone_number: String) -> Result<Option<String>> {
-        sqlx::query_scalar("SELECT friendly_name FROM friendly_names WHERE phone_number = ?")
-            .bind(phone_number)
-            .fetch_optional(&self.pool)
-            .await
-            .context("Failed to query friendly name")
+        self.storage.get_friendly_name(&phone_number).await
     }
 
     pub async fn get_latest_numbers(
@@ -230,20 +255,9 @@ impl SMSDatabase {
         offset: Option<u64>,
         reverse: bool,
     ) -> Result<Vec<(String, Option<String>)>> {
-        let query = build_pagination_query(
-            "SELECT m.phone_number, f.friendly_name FROM messages m LEFT JOIN friendly_names f ON f.phone_number = m.phone_number GROUP BY m.phone_number",
-            "MAX(m.created_at)",
-            limit,
-            offset,
-            reverse
-        );
-
-        let result: Vec<(String, Option<String>)> = sqlx::query_as(&query)
-            .fetch_all(&self.pool)
+        self.storage
+            .get_latest_numbers(limit, offset, reverse)
             .await
-            .context("Failed to query latest numbers")?;
-
-        Ok(result)
     }
 
     pub async fn get_messages(
@@ -253,35 +267,46 @@ impl SMSDatabase {
         offset: Option<u64>,
         reverse: bool,
     ) -> Result<Vec<SmsMessage>> {
-        let query = build_pagination_query(
-            "SELECT message_id, phone_number, message_content, message_reference, is_outgoing, status, created_at, completed_at FROM messages WHERE phone_number = ?",
-            "created_at",
-            limit,
-            offset,
-            reverse
-        );
-
-        let result = sqlx::query(&query)
-            .bind(phone_number)
-            .fetch_all(&self.pool)
-            .await
-            .context("Failed to query SmsMessage's")?;
+        let messages = self
+            .storage
+            .get_messages(phone_number, limit, offset, reverse)
+            .await?;
 
-        result
+        self.decrypt_messages(messages)
+    }
+
+    /// Find messages containing every word in query, optionally only with phone_number.
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        phone_number: Option<&str>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>> {
+        let tokens = self.encryption.tokenize(query);
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let messages = self
+            .storage
+            .search_messages(&tokens, phone_number, limit, offset, reverse)
+            .await?;
+
+        self.decrypt_messages(messages)
+    }
+
+    fn decrypt_messages(&self, messages: Vec<SmsMessage>) -> Result<Vec<SmsMessage>> {
+        messages
             .into_iter()
-            .map(|row| -> Result<SmsMessage> {
-                Ok(SmsMessage {
-                    message_id: row.get("message_id"),
-                    phone_number: row.get("phone_number"),
-                    message_content: self
-                        .encryption
-                        .decrypt(&row.get::<String, _>("message_content"))?,
-                    message_reference: row.get("message_reference"),
-                    is_outgoing: row.get("is_outgoing"),
-                    created_at: row.get("created_at"),
-                    completed_at: row.get("completed_at"),
-                    status: Some(row.get::<u8, _>("status")),
-                })
+            .map(|mut message| -> Result<SmsMessage> {
+                message.message_content = self.encryption.decrypt(&message.message_content)?;
+                message.raw_pdu = message
+                    .raw_pdu
+                    .map(|raw_pdu| self.encryption.decrypt(&raw_pdu))
+                    .transpose()?;
+                Ok(message)
             })
             .collect::<Result<Vec<_>, _>>()
     }
@@ -293,18 +318,317 @@ impl SMSDatabase {
         offset: Option<u64>,
         reverse: bool,
     ) -> Result<Vec<SmsDeliveryReport>> {
-        let query = build_pagination_query(
-            "SELECT report_id, message_id, status, is_final, created_at FROM delivery_reports WHERE message_id = ?",
-            "created_at",
-            limit,
-            offset,
-            reverse
-        );
-
-        sqlx::query_as(&query)
-            .bind(message_id)
-            .fetch_all(&self.pool)
+        self.storage
+            .get_delivery_reports(message_id, limit, offset, reverse)
             .await
-            .context("Failed to query delivery reports")
+    }
+
+    pub async fn insert_call(
+        &self,
+        modem_id: &str,
+        phone_number: Option<&str>,
+        rejected: bool,
+    ) -> Result<i64> {
+        self.storage
+            .insert_call(modem_id, phone_number, rejected)
+            .await
+    }
+
+    pub async fn get_calls(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<IncomingCall>> {
+        self.storage.get_calls(limit, offset, reverse).await
+    }
+
+    pub async fn insert_outbox_message(&self, message: &SmsOutgoingMessage) -> Result<i64> {
+        let encrypted_content = self.encryption.encrypt(&message.content)?;
+        self.storage
+            .insert_outbox_message(message, encrypted_content)
+            .await
+    }
+
+    /// Get the oldest outbox messages that are due to be attempted.
+    pub async fn get_due_outbox_messages(&self, limit: u32) -> Result<Vec<OutboxEntry>> {
+        self.storage
+            .get_due_outbox_messages(limit)
+            .await?
+            .into_iter()
+            .map(|mut entry| -> Result<OutboxEntry> {
+                entry.message.content = self.encryption.decrypt(&entry.message.content)?;
+                if let Some(partial) = &mut entry.partial {
+                    let pdus = self.encryption.decrypt(&partial.parts.pdus.concat())?;
+                    partial.parts.pdus = pdus.lines().map(String::from).collect();
+                }
+                Ok(entry)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Put back outbox messages that were claimed but not attempted, eg: when every modem went offline.
+    pub async fn release_outbox_messages(&self, outbox_ids: &[i64]) -> Result<()> {
+        self.storage.release_outbox_messages(outbox_ids).await
+    }
+
+    /// Returns the amount of seconds until the next outbox message is due, if there are any queued.
+    pub async fn get_next_outbox_due_in(&self) -> Result<Option<i64>> {
+        self.storage.get_next_outbox_due_in().await
+    }
+
+    /// Record a failed outbox attempt, pushing back the next attempt by delay seconds.
+    /// Attempts are only counted when consumed, so failures while the modem is offline are free.
+    pub async fn update_outbox_attempt(
+        &self,
+        outbox_id: i64,
+        error_message: &str,
+        consumed: bool,
+        delay: u64,
+    ) -> Result<()> {
+        self.storage
+            .update_outbox_attempt(outbox_id, error_message, consumed, delay)
+            .await
+    }
+
+    /// Record the parts of an outbox message that have been sent, so it can be resumed from the same modem.
+    pub async fn set_outbox_partial_send(
+        &self,
+        outbox_id: i64,
+        partial: &PartialSend,
+    ) -> Result<()> {
+        let encrypted_pdus = self.encryption.encrypt(&partial.parts.pdus.join("\n"))?;
+        let references = partial
+            .parts
+            .references
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        self.storage
+            .set_outbox_partial_send(
+                outbox_id,
+                &partial.modem_id,
+                partial.message_id,
+                encrypted_pdus,
+                references,
+            )
+            .await
+    }
+
+    pub async fn delete_outbox_message(&self, outbox_id: i64) -> Result<()> {
+        self.storage.delete_outbox_message(outbox_id).await
+    }
+
+    pub async fn insert_scheduled_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        send_at: u32,
+    ) -> Result<i64> {
+        let encrypted_content = self.encryption.encrypt(&message.content)?;
+        self.storage
+            .insert_scheduled_message(message, encrypted_content, send_at)
+            .await
+    }
+
+    pub async fn get_scheduled_messages(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsScheduledMessage>> {
+        self.storage
+            .get_scheduled_messages(limit, offset, reverse)
+            .await?
+            .into_iter()
+            .map(|mut message| -> Result<SmsScheduledMessage> {
+                message.message_content = self.encryption.decrypt(&message.message_content)?;
+                Ok(message)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Returns true if there was a scheduled message to cancel.
+    pub async fn cancel_scheduled_message(&self, scheduled_id: i64) -> Result<bool> {
+        self.storage.cancel_scheduled_message(scheduled_id).await
+    }
+
+    /// Returns true if there was a scheduled message to reschedule.
+    pub async fn reschedule_scheduled_message(
+        &self,
+        scheduled_id: i64,
+        send_at: u32,
+    ) -> Result<bool> {
+        self.storage
+            .reschedule_scheduled_message(scheduled_id, send_at)
+            .await
+    }
+
+    /// Move all scheduled messages that are due into the outbox, returning how many were moved.
+    pub async fn release_due_scheduled_messages(&self) -> Result<u64> {
+        self.storage.release_due_scheduled_messages().await
+    }
+
+    /// Returns the amount of seconds until the next scheduled message is due, if there are any.
+    pub async fn get_next_scheduled_due_in(&self) -> Result<Option<i64>> {
+        self.storage.get_next_scheduled_due_in().await
+    }
+
+    pub async fn insert_webhook_delivery(
+        &self,
+        url: &str,
+        payload: &str,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<i64> {
+        let encrypted_payload = self.encryption.encrypt(payload)?;
+        self.storage
+            .insert_webhook_delivery(url, encrypted_payload, error_message, delay)
+            .await
+    }
+
+    pub async fn get_due_webhook_deliveries(&self, limit: u32) -> Result<Vec<WebhookDelivery>> {
+        self.storage
+            .get_due_webhook_deliveries(limit)
+            .await?
+            .into_iter()
+            .map(|mut delivery| -> Result<WebhookDelivery> {
+                delivery.payload = self.encryption.decrypt(&delivery.payload)?;
+                Ok(delivery)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    pub async fn update_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<()> {
+        self.storage
+            .update_webhook_delivery(delivery_id, error_message, delay)
+            .await
+    }
+
+    pub async fn delete_webhook_delivery(&self, delivery_id: i64) -> Result<()> {
+        self.storage.delete_webhook_delivery(delivery_id).await
+    }
+
+    /// Move a pending delivery into the dead-letter table, after its final attempt.
+    pub async fn dead_letter_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<()> {
+        self.storage
+            .dead_letter_webhook_delivery(delivery_id, attempts, error_message)
+            .await
+    }
+
+    pub async fn insert_webhook_dead_letter(
+        &self,
+        url: &str,
+        payload: &str,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<i64> {
+        let encrypted_payload = self.encryption.encrypt(payload)?;
+        self.storage
+            .insert_webhook_dead_letter(url, encrypted_payload, attempts, error_message)
+            .await
+    }
+
+    pub async fn get_webhook_dead_letters(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<WebhookDeadLetter>> {
+        self.storage
+            .get_webhook_dead_letters(limit, offset, reverse)
+            .await?
+            .into_iter()
+            .map(|dead_letter| -> Result<WebhookDeadLetter> {
+                let payload = self.encryption.decrypt(&dead_letter.payload)?;
+                Ok(WebhookDeadLetter {
+                    dead_letter_id: dead_letter.dead_letter_id,
+                    url: dead_letter.url,
+                    event: serde_json::from_str(&payload)
+                        .context("Failed to parse webhook dead-letter payload")?,
+                    attempts: dead_letter.attempts,
+                    last_error: dead_letter.last_error,
+                    created_at: dead_letter.created_at,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Move a dead-letter back into the delivery queue for an immediate retry with fresh attempts.
+    /// Returns true if there was a dead-letter to replay.
+    pub async fn replay_webhook_dead_letter(&self, dead_letter_id: i64) -> Result<bool> {
+        self.storage
+            .replay_webhook_dead_letter(dead_letter_id)
+            .await
+    }
+
+    /// Re-encrypt all stored content that isn't using the active key, in batches of batch_size rows.
+    /// Message search tokens are rebuilt at the same time, as they're derived from the active key.
+    /// Returns the total amount of re-encrypted rows.
+    pub async fn reencrypt_all(&self, batch_size: u32) -> Result<u64> {
+        let mut total = 0;
+        for column in ENCRYPTED_COLUMNS {
+            let reencrypted = self.reencrypt_table(column, batch_size).await?;
+
+            info!(
+                "Re-encrypted {reencrypted} rows in {}.{}",
+                column.0, column.2
+            );
+            total += reencrypted;
+        }
+        Ok(total)
+    }
+
+    async fn reencrypt_table(&self, column: (&str, &str, &str), batch_size: u32) -> Result<u64> {
+        let table = column.0;
+        let mut last_id = 0;
+        let mut reencrypted = 0;
+        loop {
+            let rows = self
+                .storage
+                .get_encrypted_rows(column, last_id, batch_size)
+                .await?;
+
+            let Some((last, _)) = rows.last() else {
+                break;
+            };
+            last_id = *last;
+
+            let mut updated = Vec::new();
+            for (id, encrypted) in rows {
+                if !self.encryption.needs_reencrypt(&encrypted) {
+                    continue;
+                }
+
+                let content = self
+                    .encryption
+                    .decrypt(&encrypted)
+                    .with_context(|| format!("Failed to decrypt {table} #{id}"))?;
+
+                updated.push(ReencryptedRow {
+                    id,
+                    content: self.encryption.encrypt(&content)?,
+                    tokens: (table == "messages" && column.2 == "message_content")
+                        .then(|| self.encryption.tokenize(&content)),
+                });
+            }
+
+            reencrypted += updated.len() as u64;
+            self.storage.update_encrypted_rows(column, updated).await?;
+            debug!("Re-encrypted {table} up to #{last_id}");
+        }
+
+        Ok(reencrypted)
     }
 }
@@ -1,42 +1,152 @@
 #![cfg_attr(not(feature = "http-server"), allow(dead_code))]
 
+use crate::config::DatabaseConfig;
 use crate::sms::SMSEncryptionKey;
 use aes_gcm::aead::Aead;
 use aes_gcm::aes::Aes256;
 use aes_gcm::{Aes256Gcm, AesGcm, KeyInit, Nonce};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use base64::engine::general_purpose;
 use base64::Engine;
 use cipher::consts::U12;
 use cipher::Key;
+use hmac::{Hmac, Mac};
 use rand::{rng, RngCore};
+use sha2::Sha256;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Context used to derive the search token key, so it's never the same as the cipher key.
+const TOKEN_KEY_CONTEXT: &[u8] = b"sms-server message search tokens";
+
+/// Prefix of versioned ciphertext, which is followed by the key id. Unprefixed data is legacy
+/// ciphertext from before keys had ids. Base64 never contains a colon, so they can't be confused.
+const ENVELOPE_VERSION: &str = "v1";
+
+/// Tokens are truncated HMACs, which is plenty to avoid collisions within a message database.
+const TOKEN_LENGTH: usize = 16;
+
+/// Lowercase words split on anything that isn't alphanumeric, deduplicated.
+pub fn normalise_words(content: &str) -> BTreeSet<String> {
+    content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
 
 pub struct SMSEncryption {
-    cipher: AesGcm<Aes256, U12>,
+    key_id: u8,
+    ciphers: HashMap<u8, AesGcm<Aes256, U12>>,
+    token_mac: HmacSha256,
 }
 impl SMSEncryption {
-    pub fn new(key: SMSEncryptionKey) -> Self {
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
-        Self { cipher }
+    pub fn new(key_id: u8, key: SMSEncryptionKey) -> Self {
+        let mut derive =
+            <HmacSha256 as Mac>::new_from_slice(&key).expect("HMAC accepts any key length");
+        derive.update(TOKEN_KEY_CONTEXT);
+        let token_mac = <HmacSha256 as Mac>::new_from_slice(&derive.finalize().into_bytes())
+            .expect("HMAC accepts any key length");
+
+        Self {
+            key_id,
+            ciphers: HashMap::from([(key_id, Self::cipher(&key))]),
+            token_mac,
+        }
+    }
+
+    pub fn from_config(config: &DatabaseConfig) -> Result<Self> {
+        let mut encryption = Self::new(config.encryption_key_id, config.encryption_key);
+        for decryption_key in &config.decryption_keys {
+            encryption.add_decryption_key(decryption_key.id, decryption_key.key)?;
+        }
+        Ok(encryption)
     }
 
+    /// Add a previous key that can only be used to decrypt.
+    pub fn add_decryption_key(&mut self, key_id: u8, key: SMSEncryptionKey) -> Result<()> {
+        match self.ciphers.entry(key_id) {
+            Entry::Occupied(_) => bail!("Duplicate encryption key id {key_id}"),
+            Entry::Vacant(entry) => {
+                entry.insert(Self::cipher(&key));
+                Ok(())
+            }
+        }
+    }
+
+    fn cipher(key: &SMSEncryptionKey) -> AesGcm<Aes256, U12> {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+    }
+
+    /// Encrypt with the active key, as `v1:<key id>:<base64 nonce||ciphertext>`.
     pub fn encrypt(&self, plaintext: &str) -> Result<String> {
         let mut nonce_bytes = [0u8; 12];
         rng().fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
 
-        let ciphertext = self
-            .cipher
+        let ciphertext = self.ciphers[&self.key_id]
             .encrypt(nonce, plaintext.as_bytes())
             .map_err(anyhow::Error::msg)?;
 
         let mut encrypted_data = nonce_bytes.to_vec();
         encrypted_data.extend_from_slice(&ciphertext);
 
-        Ok(general_purpose::STANDARD.encode(&encrypted_data))
+        Ok(format!(
+            "{ENVELOPE_VERSION}:{}:{}",
+            self.key_id,
+            general_purpose::STANDARD.encode(&encrypted_data)
+        ))
     }
 
     pub fn decrypt(&self, encrypted_data: &str) -> Result<String> {
+        match Self::parse_envelope(encrypted_data)? {
+            Some((key_id, data)) => {
+                let cipher = self
+                    .ciphers
+                    .get(&key_id)
+                    .ok_or_else(|| anyhow!("Missing encryption key with id {key_id}"))?;
+                Self::decrypt_with(cipher, data)
+            }
+
+            // Legacy ciphertext has no key id, but is authenticated so trying each key is safe.
+            None => std::iter::once(&self.ciphers[&self.key_id])
+                .chain(
+                    self.ciphers
+                        .iter()
+                        .filter(|(key_id, _)| **key_id != self.key_id)
+                        .map(|(_, cipher)| cipher),
+                )
+                .find_map(|cipher| Self::decrypt_with(cipher, encrypted_data).ok())
+                .ok_or_else(|| anyhow!("Failed to decrypt legacy data with any configured key")),
+        }
+    }
+
+    /// If the data was not encrypted with the active key, and should be re-encrypted.
+    pub fn needs_reencrypt(&self, encrypted_data: &str) -> bool {
+        !matches!(Self::parse_envelope(encrypted_data), Ok(Some((key_id, _))) if key_id == self.key_id)
+    }
+
+    /// Returns the key id and data of versioned ciphertext, or None for legacy ciphertext.
+    fn parse_envelope(encrypted_data: &str) -> Result<Option<(u8, &str)>> {
+        let Some(rest) = encrypted_data
+            .strip_prefix(ENVELOPE_VERSION)
+            .and_then(|rest| rest.strip_prefix(':'))
+        else {
+            return Ok(None);
+        };
+
+        let (key_id, data) = rest
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid encrypted data envelope"))?;
+        let key_id = key_id
+            .parse()
+            .context("Invalid encrypted data envelope key id")?;
+        Ok(Some((key_id, data)))
+    }
+
+    fn decrypt_with(cipher: &AesGcm<Aes256, U12>, encrypted_data: &str) -> Result<String> {
         let encrypted_bytes = general_purpose::STANDARD
             .decode(encrypted_data)
             .map_err(anyhow::Error::msg)?;
@@ -48,11 +158,94 @@ impl SMSEncryption {
         let (nonce_bytes, ciphertext) = encrypted_bytes.split_at(12);
         let nonce = Nonce::from_slice(nonce_bytes);
 
-        let plaintext = self
-            .cipher
+        let plaintext = cipher
             .decrypt(nonce, ciphertext)
             .map_err(anyhow::Error::msg)?;
 
         String::from_utf8(plaintext).context("UTF-8 conversion failed")
     }
+
+    /// Blind index tokens for each word in content. The same word always gives the same token,
+    /// so messages can be searched by keyword without storing any of the plaintext.
+    pub fn tokenize(&self, content: &str) -> Vec<String> {
+        normalise_words(content)
+            .into_iter()
+            .map(|word| {
+                let mut mac = self.token_mac.clone();
+                mac.update(word.as_bytes());
+                hex::encode(&mac.finalize().into_bytes()[..TOKEN_LENGTH])
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise_words() {
+        let words = normalise_words("Hello, hello WORLD! Café's open 24/7...");
+        assert_eq!(
+            words.into_iter().collect::<Vec<_>>(),
+            vec!["24", "7", "café", "hello", "open", "s", "world"]
+        );
+        assert!(normalise_words(" ?! ").is_empty());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let encryption = SMSEncryption::new(0, [7u8; 32]);
+        let tokens = encryption.tokenize("Meet at the station");
+        assert_eq!(tokens.len(), 4);
+        assert!(tokens.iter().all(|token| token.len() == TOKEN_LENGTH * 2));
+
+        // Case and punctuation don't change the token, but the key does.
+        assert_eq!(
+            encryption.tokenize("STATION!"),
+            encryption.tokenize("station")
+        );
+        assert_ne!(
+            encryption.tokenize("station"),
+            SMSEncryption::new(0, [8u8; 32]).tokenize("station")
+        );
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = SMSEncryption::new(1, [1u8; 32]);
+        let old_data = old.encrypt("Hello!").unwrap();
+        assert!(old_data.starts_with("v1:1:"));
+        assert!(!old.needs_reencrypt(&old_data));
+
+        let mut new = SMSEncryption::new(2, [2u8; 32]);
+        assert!(new.decrypt(&old_data).is_err());
+        new.add_decryption_key(1, [1u8; 32]).unwrap();
+        assert!(new.add_decryption_key(2, [3u8; 32]).is_err());
+
+        assert_eq!(new.decrypt(&old_data).unwrap(), "Hello!");
+        assert!(new.needs_reencrypt(&old_data));
+
+        let new_data = new.encrypt("Hello!").unwrap();
+        assert!(new_data.starts_with("v1:2:"));
+        assert!(!new.needs_reencrypt(&new_data));
+        assert_eq!(new.decrypt(&new_data).unwrap(), "Hello!");
+    }
+
+    #[test]
+    fn test_legacy_decrypt() {
+        // Unversioned nonce||ciphertext, as stored before keys had ids.
+        let key = [4u8; 32];
+        let nonce = [0u8; 12];
+        let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), b"legacy".as_ref())
+            .unwrap();
+        let legacy = general_purpose::STANDARD.encode([nonce.as_slice(), &ciphertext].concat());
+
+        let mut encryption = SMSEncryption::new(5, [5u8; 32]);
+        assert!(encryption.decrypt(&legacy).is_err());
+        encryption.add_decryption_key(0, key).unwrap();
+        assert_eq!(encryption.decrypt(&legacy).unwrap(), "legacy");
+        assert!(encryption.needs_reencrypt(&legacy));
+    }
 }
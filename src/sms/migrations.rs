@@ -0,0 +1,200 @@
+use anyhow::{bail, Result};
+
+/// A schema migration. Versions must be strictly increasing from 1, and a
+/// migration must never be changed once released. Add a new one instead.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+#[cfg(feature = "db-sqlite")]
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("migrations/sqlite/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "message_modem_id",
+        sql: include_str!("migrations/sqlite/0002_message_modem_id.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "calls",
+        sql: include_str!("migrations/sqlite/0003_calls.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "message_parts",
+        sql: include_str!("migrations/sqlite/0004_message_parts.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "delivery_report_timestamps",
+        sql: include_str!("migrations/sqlite/0005_delivery_report_timestamps.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "multipart_fragments",
+        sql: include_str!("migrations/sqlite/0006_multipart_fragments.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "message_sent_at",
+        sql: include_str!("migrations/sqlite/0007_message_sent_at.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "outbox_partial_send",
+        sql: include_str!("migrations/sqlite/0008_outbox_partial_send.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "message_tokenized",
+        sql: include_str!("migrations/sqlite/0009_message_tokenized.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "delivery_report_part_index",
+        sql: include_str!("migrations/sqlite/0010_delivery_report_part_index.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "outbox_partial_message_id",
+        sql: include_str!("migrations/sqlite/0011_outbox_partial_message_id.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "message_part_sent_at",
+        sql: include_str!("migrations/sqlite/0012_message_part_sent_at.sql"),
+    },
+];
+
+#[cfg(feature = "db-postgres")]
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("migrations/postgres/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "message_modem_id",
+        sql: include_str!("migrations/postgres/0002_message_modem_id.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "calls",
+        sql: include_str!("migrations/postgres/0003_calls.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "message_parts",
+        sql: include_str!("migrations/postgres/0004_message_parts.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "delivery_report_timestamps",
+        sql: include_str!("migrations/postgres/0005_delivery_report_timestamps.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "multipart_fragments",
+        sql: include_str!("migrations/postgres/0006_multipart_fragments.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "message_sent_at",
+        sql: include_str!("migrations/postgres/0007_message_sent_at.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "outbox_partial_send",
+        sql: include_str!("migrations/postgres/0008_outbox_partial_send.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "message_tokenized",
+        sql: include_str!("migrations/postgres/0009_message_tokenized.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "delivery_report_part_index",
+        sql: include_str!("migrations/postgres/0010_delivery_report_part_index.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "outbox_partial_message_id",
+        sql: include_str!("migrations/postgres/0011_outbox_partial_message_id.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "message_part_sent_at",
+        sql: include_str!("migrations/postgres/0012_message_part_sent_at.sql"),
+    },
+];
+
+/// Tracks applied migrations, valid for every backend. There's no default for applied_at, as
+/// Postgres only has `unixepoch()` once the first migration has been applied.
+pub const SCHEMA_VERSION_SQL: &str = "CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at BIGINT NOT NULL
+)";
+
+/// The migrations that still need applying to a database at the current version.
+pub fn pending(migrations: &'static [Migration], current: u32) -> Result<&'static [Migration]> {
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if current > latest {
+        bail!("Database schema version {current} is newer than the latest supported version {latest}, was it used by a newer sms-server?");
+    }
+
+    let applied = migrations
+        .iter()
+        .take_while(|migration| migration.version <= current)
+        .count();
+    Ok(&migrations[applied..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered(migrations: &[Migration]) {
+        for (idx, migration) in migrations.iter().enumerate() {
+            assert_eq!(migration.version as usize, idx + 1, "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn test_migration_order() {
+        #[cfg(feature = "db-sqlite")]
+        assert_ordered(SQLITE_MIGRATIONS);
+
+        #[cfg(feature = "db-postgres")]
+        assert_ordered(POSTGRES_MIGRATIONS);
+    }
+
+    #[test]
+    fn test_pending() {
+        static MIGRATIONS: &[Migration] = &[
+            Migration {
+                version: 1,
+                name: "one",
+                sql: "",
+            },
+            Migration {
+                version: 2,
+                name: "two",
+                sql: "",
+            },
+        ];
+
+        assert_eq!(pending(MIGRATIONS, 0).unwrap().len(), 2);
+        assert_eq!(pending(MIGRATIONS, 1).unwrap()[0].name, "two");
+        assert!(pending(MIGRATIONS, 2).unwrap().is_empty());
+        assert!(pending(MIGRATIONS, 3).is_err());
+    }
+}
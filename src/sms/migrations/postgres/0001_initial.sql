@@ -0,0 +1,98 @@
+-- Matches the SQLite builtin, so queries can be shared between backends.
+CREATE OR REPLACE FUNCTION unixepoch() RETURNS BIGINT AS $$
+    SELECT EXTRACT(EPOCH FROM NOW())::BIGINT
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE IF NOT EXISTS messages (
+    message_id BIGSERIAL PRIMARY KEY,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    message_reference SMALLINT CHECK (message_reference >= 0 AND message_reference <= 255),
+    is_outgoing BOOLEAN NOT NULL,
+    status SMALLINT DEFAULT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch(),
+    completed_at BIGINT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS message_tokens (
+    token TEXT NOT NULL,
+    message_id BIGINT NOT NULL,
+    PRIMARY KEY (token, message_id),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS friendly_names (
+    phone_number TEXT PRIMARY KEY,
+    friendly_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS send_failures (
+    message_id BIGINT PRIMARY KEY,
+    error_message TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch(),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS delivery_reports (
+    report_id BIGSERIAL PRIMARY KEY,
+    message_id BIGINT NOT NULL,
+    status SMALLINT NOT NULL,
+    is_final BOOLEAN NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch(),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+    outbox_id BIGSERIAL PRIMARY KEY,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    flash BOOLEAN NOT NULL DEFAULT FALSE,
+    validity_period SMALLINT CHECK (validity_period >= 0 AND validity_period <= 255),
+    timeout BIGINT DEFAULT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    scheduled_id BIGINT DEFAULT NULL,
+    next_attempt_at BIGINT NOT NULL DEFAULT unixepoch(),
+    created_at BIGINT NOT NULL DEFAULT unixepoch()
+);
+
+CREATE TABLE IF NOT EXISTS scheduled_messages (
+    scheduled_id BIGSERIAL PRIMARY KEY,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    flash BOOLEAN NOT NULL DEFAULT FALSE,
+    validity_period SMALLINT CHECK (validity_period >= 0 AND validity_period <= 255),
+    timeout BIGINT DEFAULT NULL,
+    send_at BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    delivery_id BIGSERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    next_attempt_at BIGINT NOT NULL DEFAULT unixepoch(),
+    created_at BIGINT NOT NULL DEFAULT unixepoch()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    dead_letter_id BIGSERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch()
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_phone_number ON messages(phone_number);
+CREATE INDEX IF NOT EXISTS idx_messages_status ON messages(status);
+CREATE INDEX IF NOT EXISTS idx_messages_is_outgoing ON messages(is_outgoing);
+CREATE INDEX IF NOT EXISTS idx_messages_created_at ON messages(created_at);
+CREATE INDEX IF NOT EXISTS idx_messages_completed_at ON messages(completed_at);
+CREATE INDEX IF NOT EXISTS idx_friendly_name ON friendly_names(friendly_name);
+CREATE INDEX IF NOT EXISTS idx_outbox_next_attempt_at ON outbox(next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_scheduled_messages_send_at ON scheduled_messages(send_at);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_next_attempt_at ON webhook_deliveries(next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_message_tokens_message_id ON message_tokens(message_id);
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS calls (
+    call_id BIGSERIAL PRIMARY KEY,
+    modem_id TEXT NOT NULL,
+    phone_number TEXT,
+    rejected BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL DEFAULT unixepoch()
+);
+
+CREATE INDEX IF NOT EXISTS idx_calls_created_at ON calls(created_at);
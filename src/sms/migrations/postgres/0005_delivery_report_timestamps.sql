@@ -0,0 +1,3 @@
+ALTER TABLE delivery_reports ADD COLUMN submitted_at BIGINT DEFAULT NULL;
+ALTER TABLE delivery_reports ADD COLUMN discharged_at BIGINT DEFAULT NULL;
+ALTER TABLE delivery_reports ADD COLUMN ambiguous BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS multipart_fragments (
+    fragment_id BIGSERIAL PRIMARY KEY,
+    modem_id TEXT NOT NULL,
+    phone_number TEXT NOT NULL,
+    message_reference INTEGER NOT NULL CHECK (message_reference >= 0 AND message_reference <= 65535),
+    part_index SMALLINT NOT NULL CHECK (part_index >= 0 AND part_index <= 255),
+    total_parts SMALLINT NOT NULL CHECK (total_parts >= 0 AND total_parts <= 255),
+    message_content TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT unixepoch(),
+    UNIQUE (modem_id, phone_number, message_reference, part_index)
+);
+
+ALTER TABLE messages ADD COLUMN incomplete BOOLEAN NOT NULL DEFAULT FALSE;
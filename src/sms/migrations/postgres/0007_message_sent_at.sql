@@ -0,0 +1,4 @@
+ALTER TABLE messages ADD COLUMN sent_at BIGINT DEFAULT NULL;
+ALTER TABLE messages ADD COLUMN raw_pdu TEXT DEFAULT NULL;
+ALTER TABLE multipart_fragments ADD COLUMN sent_at BIGINT DEFAULT NULL;
+ALTER TABLE multipart_fragments ADD COLUMN raw_pdu TEXT DEFAULT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE messages ADD COLUMN tokenized BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE messages SET tokenized = TRUE WHERE EXISTS (SELECT 1 FROM message_tokens WHERE message_tokens.message_id = messages.message_id);
+CREATE INDEX IF NOT EXISTS idx_messages_untokenized ON messages(message_id) WHERE NOT tokenized;
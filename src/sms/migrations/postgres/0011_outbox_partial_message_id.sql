@@ -0,0 +1 @@
+ALTER TABLE outbox ADD COLUMN partial_message_id BIGINT DEFAULT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE message_parts ADD COLUMN sent_at BIGINT DEFAULT NULL;
+UPDATE message_parts SET sent_at = messages.created_at FROM messages WHERE messages.message_id = message_parts.message_id;
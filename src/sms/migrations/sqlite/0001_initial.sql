@@ -0,0 +1,93 @@
+CREATE TABLE IF NOT EXISTS messages (
+    message_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    message_reference INTEGER CHECK (message_reference >= 0 AND message_reference <= 255),
+    is_outgoing BOOLEAN NOT NULL,
+    status INTEGER DEFAULT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    completed_at INTEGER DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS message_tokens (
+    token TEXT NOT NULL,
+    message_id INTEGER NOT NULL,
+    PRIMARY KEY (token, message_id),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS friendly_names (
+    phone_number TEXT PRIMARY KEY,
+    friendly_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS send_failures (
+    message_id INTEGER PRIMARY KEY,
+    error_message TEXT NOT NULL,
+    created_at INTEGER DEFAULT (unixepoch()),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS delivery_reports (
+    report_id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    is_final BOOLEAN NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+    outbox_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    flash BOOLEAN NOT NULL DEFAULT 0,
+    validity_period INTEGER CHECK (validity_period >= 0 AND validity_period <= 255),
+    timeout INTEGER DEFAULT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    scheduled_id INTEGER DEFAULT NULL,
+    next_attempt_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE TABLE IF NOT EXISTS scheduled_messages (
+    scheduled_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    phone_number TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    flash BOOLEAN NOT NULL DEFAULT 0,
+    validity_period INTEGER CHECK (validity_period >= 0 AND validity_period <= 255),
+    timeout INTEGER DEFAULT NULL,
+    send_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    delivery_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    next_attempt_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    dead_letter_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_phone_number ON messages(phone_number);
+CREATE INDEX IF NOT EXISTS idx_messages_status ON messages(status);
+CREATE INDEX IF NOT EXISTS idx_messages_is_outgoing ON messages(is_outgoing);
+CREATE INDEX IF NOT EXISTS idx_messages_created_at ON messages(created_at);
+CREATE INDEX IF NOT EXISTS idx_messages_completed_at ON messages(completed_at);
+CREATE INDEX IF NOT EXISTS idx_friendly_name ON friendly_names(friendly_name);
+CREATE INDEX IF NOT EXISTS idx_outbox_next_attempt_at ON outbox(next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_scheduled_messages_send_at ON scheduled_messages(send_at);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_next_attempt_at ON webhook_deliveries(next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_message_tokens_message_id ON message_tokens(message_id);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE messages ADD COLUMN modem_id TEXT DEFAULT NULL;
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS message_parts (
+    message_id INTEGER NOT NULL,
+    part_index INTEGER NOT NULL,
+    message_reference INTEGER NOT NULL CHECK (message_reference >= 0 AND message_reference <= 255),
+    status INTEGER DEFAULT NULL,
+    completed_at INTEGER DEFAULT NULL,
+    PRIMARY KEY (message_id, part_index),
+    FOREIGN KEY (message_id) REFERENCES messages(message_id) ON DELETE CASCADE
+);
+
+INSERT INTO message_parts (message_id, part_index, message_reference, status, completed_at)
+SELECT message_id, 1, message_reference, status, completed_at FROM messages
+WHERE is_outgoing = 1 AND message_reference IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_message_parts_message_reference ON message_parts(message_reference);
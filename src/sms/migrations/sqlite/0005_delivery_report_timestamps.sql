@@ -0,0 +1,3 @@
+ALTER TABLE delivery_reports ADD COLUMN submitted_at INTEGER DEFAULT NULL;
+ALTER TABLE delivery_reports ADD COLUMN discharged_at INTEGER DEFAULT NULL;
+ALTER TABLE delivery_reports ADD COLUMN ambiguous BOOLEAN NOT NULL DEFAULT 0;
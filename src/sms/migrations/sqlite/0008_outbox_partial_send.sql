@@ -0,0 +1,3 @@
+ALTER TABLE outbox ADD COLUMN partial_modem_id TEXT DEFAULT NULL;
+ALTER TABLE outbox ADD COLUMN partial_pdus TEXT DEFAULT NULL;
+ALTER TABLE outbox ADD COLUMN partial_references TEXT DEFAULT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE delivery_reports ADD COLUMN part_index INTEGER DEFAULT NULL;
+UPDATE delivery_reports SET part_index = 1 WHERE message_id NOT IN (SELECT message_id FROM message_parts WHERE part_index > 1);
+UPDATE delivery_reports SET is_final = (status NOT BETWEEN 32 AND 63) WHERE message_id IN (SELECT message_id FROM message_parts WHERE part_index > 1);
@@ -0,0 +1,2 @@
+ALTER TABLE message_parts ADD COLUMN sent_at INTEGER DEFAULT NULL;
+UPDATE message_parts SET sent_at = (SELECT created_at FROM messages WHERE messages.message_id = message_parts.message_id);
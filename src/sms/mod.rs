@@ -2,99 +2,220 @@
 
 mod database;
 mod encryption;
+mod migrations;
 mod multipart;
+pub mod outbox;
+pub mod routing;
+mod storage;
 
-use crate::config::DatabaseConfig;
+pub use database::SMSDatabase;
+
+use crate::config::CallPolicy;
 use crate::events::EventBroadcaster;
-use crate::modem::sender::ModemSender;
+use crate::modem::sender::SmsParts;
 use crate::modem::types::{ModemRequest, ModemResponse};
-use crate::sms::database::SMSDatabase;
 use crate::sms::multipart::SMSMultipartMessages;
-use anyhow::{anyhow, bail, Result};
+use crate::sms::outbox::{OutboxOutcome, OutboxWorker, PartialSend, SMSOutbox};
+use crate::sms::routing::{ModemRouter, RoutedModem};
+use crate::sms::storage::DeliveryReportCandidate;
+use anyhow::{bail, Result};
 use num_traits::cast::FromPrimitive;
 use sms_pdu::pdu::MessageStatus;
 use sms_types::events::Event;
+use sms_types::modem::IncomingCall;
 use sms_types::sms::{
     SmsIncomingMessage, SmsMessage, SmsOutgoingMessage, SmsPartialDeliveryReport,
 };
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
-use tracing::log::{debug, error, warn};
+use tokio::task::JoinHandle;
+use tracing::log::{debug, error, info, warn};
 
 pub type SMSEncryptionKey = [u8; 32];
 
+/// The sent message a delivery report was matched to.
+#[derive(Debug)]
+pub enum DeliveryReportOutcome {
+    /// The report was for a single sent message, which has been updated.
+    Matched(i64),
+
+    /// The report could be for any of these messages, so it's been stored with each of
+    /// them and their status is left unchanged.
+    Ambiguous(Vec<i64>),
+}
+
 #[derive(Clone)]
 pub struct SMSManager {
-    modem: ModemSender,
+    modems: Arc<ModemRouter>,
     database: Arc<SMSDatabase>,
     broadcaster: Option<EventBroadcaster>,
+    outbox: SMSOutbox,
 }
 impl SMSManager {
-    pub async fn connect(
-        config: DatabaseConfig,
-        modem: ModemSender,
+    pub fn new(
+        database: Arc<SMSDatabase>,
+        modems: Arc<ModemRouter>,
         broadcaster: Option<EventBroadcaster>,
-    ) -> Result<Self> {
-        let database = Arc::new(SMSDatabase::connect(config).await?);
-        Ok(Self {
-            modem,
+    ) -> Self {
+        Self {
+            modems,
             database,
             broadcaster,
-        })
+            outbox: SMSOutbox::default(),
+        }
     }
 
-    /// Returns the database row ID and final modem response.
+    /// Start the outbox worker, which also sends anything left queued from before a restart.
+    pub fn start_outbox(&self) -> JoinHandle<()> {
+        let worker = OutboxWorker::new(self.clone());
+        tokio::spawn(worker.run())
+    }
+
+    /// Queue a message in the outbox. If a modem that can send it is online, this waits for the first send attempt.
+    pub async fn queue_sms(&self, message: SmsOutgoingMessage) -> Result<OutboxOutcome> {
+        // There's no point waiting on an attempt that can't happen until a modem is back online.
+        let candidates = self.modems.candidates(&message.to);
+        if !candidates.iter().any(|modem| modem.is_online()) {
+            let outbox_id = self.database.insert_outbox_message(&message).await?;
+            debug!("Queued outgoing message as outbox #{outbox_id} while no modem is online");
+
+            let status = candidates[0].sender.status();
+            return Ok(OutboxOutcome::Queued {
+                outbox_id,
+                reason: format!("Modem '{}' is {status:?}", candidates[0].id),
+            });
+        }
+
+        self.outbox.queue_and_wait(&self.database, &message).await
+    }
+
+    /// Send the remaining parts of a message from the given modem, returning the last modem
+    /// response. The message is stored once its first part is accepted, setting message_id,
+    /// so parts the recipient already has are kept even if the rest never send.
     pub async fn send_sms(
         &self,
-        message: SmsOutgoingMessage,
-    ) -> Result<(Option<i64>, ModemResponse)> {
-        let (success, last_response) = self.modem.send_sms(&message).await?;
-        let last_response =
-            last_response.ok_or_else(|| anyhow!("Missing any valid SendSMS response!"))?;
-        if !success {
-            return Ok((None, last_response));
-        }
+        modem: &RoutedModem,
+        message: &SmsOutgoingMessage,
+        parts: &mut SmsParts,
+        message_id: &mut Option<i64>,
+    ) -> Result<ModemResponse> {
+        let sent_before = parts.references.len();
+        let last_response = modem.sender.send_sms(parts, message.timeout).await;
         debug!("SMSManager last_response: {last_response:?}");
 
-        let mut new_message = SmsMessage::from(&message);
-        let send_failure = match &last_response {
-            ModemResponse::SendResult(reference_id) => {
-                new_message.message_reference.replace(*reference_id);
-                None
-            }
-            ModemResponse::Error(error_message) => {
-                new_message.status = None;
-                Some(error_message)
+        let mut new_message = SmsMessage::from(message);
+        new_message.modem_id = Some(modem.id.to_string());
+        new_message.message_reference = parts.references.last().copied();
+
+        // Store the message along with the parts that delivery reports are expected
+        // for, as each part gets its own.
+        if parts.references.len() > sent_before {
+            let (id, stored) = match *message_id {
+                Some(id) => (id, sent_before),
+                None => (self.database.insert_message(&new_message, false).await?, 0),
+            };
+            *message_id = Some(id);
+
+            if let Err(e) = self
+                .database
+                .insert_message_parts(id, &parts.references, stored)
+                .await
+            {
+                error!("Failed to store message parts! {e:?}");
             }
-            _ => bail!("Got invalid ModemResponse back from sending SMS message!"),
-        };
+        }
 
-        // Store sent message in database
-        let message_id = self
+        let last_response = last_response?;
+        if !parts.is_complete() {
+            return Ok(last_response);
+        }
+
+        // Broadcast event
+        if let Some(broadcaster) = &self.broadcaster {
+            broadcaster.broadcast(Event::OutgoingMessage(
+                new_message.with_message_id(*message_id),
+            ));
+        }
+        Ok(last_response)
+    }
+
+    /// Schedule a message to be moved into the outbox at send_at, returning the scheduled_id.
+    pub async fn schedule_sms(&self, message: SmsOutgoingMessage, send_at: u32) -> Result<i64> {
+        let scheduled_id = self
             .database
-            .insert_message(&new_message, send_failure.is_some())
+            .insert_scheduled_message(&message, send_at)
             .await?;
+        debug!("Scheduled outgoing message #{scheduled_id} for {send_at}");
 
-        // Store send failure if present
-        if let Some(failure) = send_failure {
-            if let Err(e) = self.database.insert_send_failure(message_id, failure).await {
-                error!("Failed to store send failure! {e:?}");
-            }
+        // Wake the worker so it can recalculate when it next needs to check.
+        self.outbox.wake();
+        Ok(scheduled_id)
+    }
+
+    /// Returns true if there was a pending scheduled message to reschedule.
+    pub async fn reschedule_sms(&self, scheduled_id: i64, send_at: u32) -> Result<bool> {
+        let found = self
+            .database
+            .reschedule_scheduled_message(scheduled_id, send_at)
+            .await?;
+
+        if found {
+            self.outbox.wake();
         }
+        Ok(found)
+    }
+
+    /// Store an outgoing message that could not be sent, along with the reason why. A partly
+    /// sent message already has a row with the parts that were sent, so the failure is recorded there.
+    pub async fn store_send_failure(
+        &self,
+        message: &SmsOutgoingMessage,
+        error_message: &str,
+        partial: Option<&PartialSend>,
+    ) -> Result<i64> {
+        let mut new_message = SmsMessage::from(message);
+        let message_id = match partial {
+            Some(PartialSend {
+                modem_id,
+                message_id: Some(message_id),
+                parts,
+            }) => {
+                new_message.modem_id = Some(modem_id.clone());
+                new_message.message_reference = parts.references.last().copied();
+                *message_id
+            }
+            _ => self.database.insert_message(&new_message, true).await?,
+        };
+        self.database
+            .insert_send_failure(message_id, error_message)
+            .await?;
 
-        // Broadcast event
         if let Some(broadcaster) = &self.broadcaster {
             broadcaster.broadcast(Event::OutgoingMessage(
                 new_message.with_message_id(Some(message_id)),
             ));
         }
-        Ok((Some(message_id), last_response))
+        Ok(message_id)
     }
 
-    pub async fn send_command(&self, request: ModemRequest) -> Result<ModemResponse> {
-        self.modem.send_request(request, None).await
+    /// Send a command to a modem by id, or the first configured modem if there's no id.
+    pub async fn send_command(
+        &self,
+        request: ModemRequest,
+        modem_id: Option<&str>,
+    ) -> Result<ModemResponse> {
+        self.modems
+            .get(modem_id)?
+            .sender
+            .send_request(request, None)
+            .await
+    }
+
+    pub fn borrow_modems(&self) -> &Arc<ModemRouter> {
+        &self.modems
     }
 
     pub fn borrow_database(&self) -> &Arc<SMSDatabase> {
@@ -102,20 +223,54 @@ impl SMSManager {
     }
 }
 
-/// The multipart key is (phone_number, message_ref), meaning that even if the
+/// How far a message's send time can be from the service centre timestamp of its delivery report,
+/// allowing for the service centre's clock being off.
+const DELIVERY_REPORT_MATCH_WINDOW: u32 = 15 * 60;
+
+/// Pick the part a delivery report is for, from the incomplete parts sent with its reference. If there's
+/// more than one, it must be the only one sent around the report's service centre timestamp (and before
+/// the discharge time). Otherwise it's ambiguous, and the parts it could be for are returned instead.
+fn select_delivery_report_target<'a>(
+    candidates: &'a [DeliveryReportCandidate],
+    report: &SmsPartialDeliveryReport,
+) -> Result<&'a DeliveryReportCandidate, Vec<&'a DeliveryReportCandidate>> {
+    if let [candidate] = candidates {
+        return Ok(candidate);
+    }
+
+    let matching = candidates
+        .iter()
+        .filter(|candidate| {
+            report.submitted_at.is_none_or(|submitted_at| {
+                candidate.sent_at.abs_diff(submitted_at) <= DELIVERY_REPORT_MATCH_WINDOW
+            }) && report.discharged_at.is_none_or(|discharged_at| {
+                candidate.sent_at <= discharged_at.saturating_add(DELIVERY_REPORT_MATCH_WINDOW)
+            })
+        })
+        .collect::<Vec<_>>();
+    match matching[..] {
+        [candidate] => Ok(candidate),
+        [] => Err(candidates.iter().collect()),
+        _ => Err(matching),
+    }
+}
+
+/// The multipart key is (modem_id, phone_number, message_ref), meaning that even if the
 /// message reference resets delivery could still work (for unique numbers).
-type MultipartReference = (Arc<str>, u8);
+type MultipartReference = (Arc<str>, Arc<str>, u16);
 
 #[derive(Clone)]
 pub struct SMSReceiver {
     manager: SMSManager,
     multipart: Arc<Mutex<HashMap<MultipartReference, SMSMultipartMessages>>>,
+    call_policy: CallPolicy,
 }
 impl SMSReceiver {
-    pub fn new(manager: SMSManager) -> Self {
+    pub fn new(manager: SMSManager, call_policy: CallPolicy) -> Self {
         Self {
             manager,
             multipart: Arc::new(Mutex::new(HashMap::new())),
+            call_policy,
         }
     }
 
@@ -123,17 +278,57 @@ impl SMSReceiver {
     /// Option for multipart messages, as individual parts aren't stored only compiled result.
     pub async fn handle_incoming_sms(
         &mut self,
-        incoming_message: SmsIncomingMessage,
+        modem_id: &Arc<str>,
+        mut incoming_message: SmsIncomingMessage,
     ) -> Option<Result<i64>> {
+        if !self.manager.database.store_raw_pdu() {
+            incoming_message.raw_pdu = None;
+        }
+        let header = incoming_message.user_data_header;
+
         // Handle incoming message, discarding if it's a multipart message and not final.
-        let message = match self.get_incoming_sms_message(incoming_message).await {
+        let message = match self
+            .get_incoming_sms_message(modem_id, incoming_message)
+            .await
+        {
             Some(Ok(message)) => message,
             Some(Err(e)) => return Some(Err(e)),
             None => return None,
         };
 
+        Some(
+            self.store_incoming_message(
+                modem_id,
+                message,
+                header.map(|header| header.message_reference),
+            )
+            .await,
+        )
+    }
+
+    /// Store + emit a received message, removing the stored parts it was compiled from.
+    async fn store_incoming_message(
+        &self,
+        modem_id: &str,
+        mut message: SmsMessage,
+        multipart_reference: Option<u16>,
+    ) -> Result<i64> {
+        message.modem_id = Some(modem_id.to_string());
+
         let row_id_result = self.manager.database.insert_message(&message, false).await;
 
+        // The stored parts of a multipart message are only removed once it's been stored.
+        if let (Some(message_reference), Ok(_)) = (multipart_reference, &row_id_result) {
+            if let Err(e) = self
+                .manager
+                .database
+                .delete_multipart_fragments(modem_id, &message.phone_number, message_reference)
+                .await
+            {
+                error!("Failed to delete stored multipart message parts: {e:?}");
+            }
+        }
+
         // Send incoming event.
         if let Some(broadcaster) = &self.manager.broadcaster {
             broadcaster.broadcast(Event::IncomingMessage(
@@ -141,62 +336,283 @@ impl SMSReceiver {
             ));
         }
 
-        Some(row_id_result)
+        row_id_result
     }
 
     /// Store + emit delivery report.
-    pub async fn handle_delivery_report(&self, report: SmsPartialDeliveryReport) -> Result<i64> {
-        // Find the target message from phone number and message reference. This will be fine unless we send 255
-        // messages to the client before they reply with delivery reports as then there's no way to properly track.
-        let message_id = match self
+    pub async fn handle_delivery_report(
+        &self,
+        modem_id: &str,
+        report: SmsPartialDeliveryReport,
+    ) -> Result<DeliveryReportOutcome> {
+        // Find the target part from phone number and message reference. References wrap after 255, so
+        // there can be several incomplete parts with the same one, which are told apart by the report's
+        // timestamps. References are per modem, so only messages sent from the reporting modem are considered.
+        let candidates = self
             .manager
             .database
-            .get_delivery_report_target_message(&report.phone_number, report.reference_id)
-            .await?
-        {
-            Some(message_id) => message_id,
-            None => bail!("Could not find target message for delivery report!"),
-        };
+            .get_delivery_report_candidates(&report.phone_number, report.reference_id, modem_id)
+            .await?;
+        if candidates.is_empty() {
+            bail!("Could not find target message for delivery report!");
+        }
 
-        // Check if we should expect more delivery reports from this message_id.
+        // Check if we should expect more delivery reports for this part.
         let report_status = report.status;
         let is_final = MessageStatus::from_u8(report_status)
             .map(|status| status.is_success() || status.is_permanent_error())
             .unwrap_or(true);
 
+        let target = match select_delivery_report_target(&candidates, &report) {
+            Ok(target) => target,
+            Err(possible) => {
+                // Rather than guess, the report is kept with every part it could be for.
+                for candidate in &possible {
+                    self.manager
+                        .database
+                        .insert_delivery_report(
+                            candidate.message_id,
+                            candidate.part_index,
+                            &report,
+                            is_final,
+                            true,
+                        )
+                        .await?;
+                }
+
+                let mut message_ids: Vec<i64> = possible
+                    .iter()
+                    .map(|candidate| candidate.message_id)
+                    .collect();
+                message_ids.sort_unstable();
+                message_ids.dedup();
+                if let Some(broadcaster) = &self.manager.broadcaster {
+                    for &message_id in &message_ids {
+                        broadcaster.broadcast(Event::DeliveryReport {
+                            message_id,
+                            report: report.clone(),
+                            ambiguous: true,
+                        });
+                    }
+                }
+                return Ok(DeliveryReportOutcome::Ambiguous(message_ids));
+            }
+        };
+        let (message_id, part_index) = (target.message_id, target.part_index);
+        self.manager
+            .database
+            .update_message_part_status(message_id, part_index, report_status, is_final)
+            .await?;
+
+        // The message is only complete once every part is. A part that failed is kept as
+        // the message status, so it isn't hidden by the other parts being delivered.
+        let parts = self.manager.database.get_message_parts(message_id).await?;
+        let completed = parts.iter().all(|part| part.completed_at.is_some());
+        let message_status = parts
+            .iter()
+            .filter_map(|part| part.status)
+            .find(|&status| {
+                MessageStatus::from_u8(status).is_some_and(|status| status.is_permanent_error())
+            })
+            .unwrap_or(report_status);
+        debug!(
+            "Delivery report for message #{message_id} part {part_index}/{}: {report_status}",
+            parts.len()
+        );
+
+        self.manager
+            .database
+            .insert_delivery_report(message_id, part_index, &report, is_final, false)
+            .await?;
+
         // Send delivery report event.
         if let Some(broadcaster) = &self.manager.broadcaster {
-            broadcaster.broadcast(Event::DeliveryReport { message_id, report });
+            broadcaster.broadcast(Event::DeliveryReport {
+                message_id,
+                report,
+                ambiguous: false,
+            });
         }
 
         self.manager
             .database
-            .insert_delivery_report(message_id, report_status, is_final)
+            .update_message_status(message_id, message_status, completed)
             .await?;
 
-        self.manager
+        Ok(DeliveryReportOutcome::Matched(message_id))
+    }
+
+    /// Apply the call policy to an incoming call, then store + emit it.
+    pub async fn handle_incoming_call(
+        &self,
+        modem_id: &str,
+        phone_number: Option<String>,
+    ) -> Result<i64> {
+        let rejected = self.call_policy == CallPolicy::Reject;
+        if rejected {
+            // Don't hold up other incoming messages waiting for the modem to hang up.
+            let manager = self.manager.clone();
+            let hangup_modem_id = modem_id.to_string();
+            tokio::spawn(async move {
+                match manager
+                    .send_command(ModemRequest::HangUp, Some(&hangup_modem_id))
+                    .await
+                {
+                    Ok(ModemResponse::CallEnded) => {
+                        debug!("Rejected incoming call on modem '{hangup_modem_id}'")
+                    }
+                    Ok(response) => {
+                        warn!("Unexpected response rejecting call on modem '{hangup_modem_id}': {response}")
+                    }
+                    Err(e) => error!("Failed to reject call on modem '{hangup_modem_id}': {e:?}"),
+                }
+            });
+        }
+
+        let call_id_result = self
+            .manager
             .database
-            .update_message_status(message_id, report_status, is_final)
-            .await?;
+            .insert_call(modem_id, phone_number.as_deref(), rejected)
+            .await;
 
-        Ok(message_id)
+        // Send incoming call event.
+        if let Some(broadcaster) = &self.manager.broadcaster {
+            broadcaster.broadcast(Event::IncomingCall(IncomingCall {
+                call_id: call_id_result.as_ref().ok().copied(),
+                modem_id: modem_id.to_string(),
+                phone_number,
+                rejected,
+                created_at: None,
+            }));
+        }
+
+        call_id_result
+    }
+
+    /// Load the parts of multipart messages received before a restart, so they can still be completed.
+    pub async fn restore_multipart(&self) -> Result<()> {
+        let fragments = self.manager.database.get_multipart_fragments().await?;
+        if fragments.is_empty() {
+            return Ok(());
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let count = fragments.len();
+        let mut guard = self.multipart.lock().await;
+        let mut complete = Vec::new();
+        for fragment in fragments {
+            let header = fragment.header;
+            let multipart_ref: MultipartReference = (
+                fragment.modem_id.into(),
+                fragment.phone_number.clone().into(),
+                header.message_reference,
+            );
+
+            let multipart = guard
+                .entry(multipart_ref.clone())
+                .or_insert_with(|| SMSMultipartMessages::with_capacity(header.total as usize));
+            let is_complete = multipart.add_message(
+                SmsIncomingMessage {
+                    phone_number: fragment.phone_number,
+                    user_data_header: Some(header),
+                    content: fragment.content,
+                    sent_at: fragment.sent_at,
+                    raw_pdu: fragment.raw_pdu,
+                },
+                header.index,
+            );
+
+            // Every part was stored before the server stopped, so there's nothing left to wait for.
+            if is_complete {
+                if let Some(multipart) = guard.remove(&multipart_ref) {
+                    complete.push((multipart_ref, multipart));
+                }
+                continue;
+            }
+
+            // Fragments are in the order received, so this leaves the time of the latest part.
+            let age = now.saturating_sub(u64::from(fragment.created_at));
+            multipart.set_updated_ago(Duration::from_secs(age));
+        }
+
+        info!(
+            "Restored {count} parts of {} multipart messages",
+            guard.len() + complete.len()
+        );
+        drop(guard);
+
+        for ((modem_id, _, message_reference), multipart) in complete {
+            let result = match multipart.compile() {
+                Ok(message) => {
+                    self.store_incoming_message(&modem_id, message, Some(message_reference))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(message_id) => debug!("Stored restored multipart message #{message_id}"),
+                Err(e) => error!("Failed to store restored multipart message: {e:?}"),
+            }
+        }
+        Ok(())
     }
 
     /// **Call only from cleanup task!**
-    /// Holds multipart lock and removes all stalled receivers.
+    /// Holds multipart lock and stores all stalled receivers as incomplete messages.
     pub async fn cleanup_stalled_multipart(&mut self) {
         debug!("Cleaning up stalled multipart messages");
         let mut guard = self.multipart.lock().await;
-        guard.retain(|(phone_number, message_reference), messages| {
-            // Show a warning whenever a message group has stalled.
-            let stalled = messages.is_stalled();
-            if stalled {
-                warn!(
-                    "Removing received multipart message '{phone_number}' (#{message_reference}) has stalled!"
-                );
+        let stalled = guard
+            .extract_if(|_, messages| messages.is_stalled())
+            .collect::<Vec<_>>();
+
+        for ((modem_id, phone_number, message_reference), messages) in stalled {
+            warn!(
+                "Received multipart message '{phone_number}' (#{message_reference}) on modem '{modem_id}' has stalled with {}/{} parts, storing it as incomplete!",
+                messages.received_count(),
+                messages.total_size()
+            );
+            if let Err(e) = self
+                .store_incomplete_multipart(&modem_id, &phone_number, message_reference, &messages)
+                .await
+            {
+                error!("Failed to store incomplete multipart message: {e:?}");
             }
-            !stalled
-        });
+        }
+    }
+
+    /// Store + emit the parts received of a stalled multipart message.
+    async fn store_incomplete_multipart(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        message_reference: u16,
+        messages: &SMSMultipartMessages,
+    ) -> Result<i64> {
+        let mut message = messages.compile()?;
+        message.modem_id = Some(modem_id.to_string());
+        message.incomplete = true;
+
+        let message_id = self
+            .manager
+            .database
+            .insert_message(&message, false)
+            .await?;
+        self.manager
+            .database
+            .delete_multipart_fragments(modem_id, phone_number, message_reference)
+            .await?;
+
+        // Send incomplete message event.
+        if let Some(broadcaster) = &self.manager.broadcaster {
+            broadcaster.broadcast(Event::IncompleteMessage {
+                message: message.with_message_id(Some(message_id)),
+                received_parts: messages.received_count() as u8,
+                total_parts: messages.total_size() as u8,
+            });
+        }
+
+        Ok(message_id)
     }
 
     /// Get the final SMSMessage to broadcast/store, which is either just the
@@ -204,6 +620,7 @@ impl SMSReceiver {
     /// Optional result is from the multipart message compile.
     async fn get_incoming_sms_message(
         &mut self,
+        modem_id: &Arc<str>,
         incoming_message: SmsIncomingMessage,
     ) -> Option<Result<SmsMessage>> {
         // If there is no multipart header, skip multipart checks.
@@ -213,9 +630,14 @@ impl SMSReceiver {
         };
 
         let phone_number: Arc<str> = incoming_message.phone_number.clone().into();
-        let multipart_ref: MultipartReference = (phone_number.clone(), header.message_reference);
+        let multipart_ref: MultipartReference = (
+            modem_id.clone(),
+            phone_number.clone(),
+            header.message_reference,
+        );
         debug!("Got multipart reference: {multipart_ref:?}");
 
+        let fragment = incoming_message.clone();
         let mut guard = self.multipart.lock().await;
         match guard.entry(multipart_ref) {
             Entry::Vacant(entry) => {
@@ -230,11 +652,9 @@ impl SMSReceiver {
                     warn!("Got a 1 part multipart message from {phone_number}, that's odd!");
 
                     // Compile message, and don't insert into map since it's complete.
-                    Some(mulipart.compile())
-                } else {
-                    entry.insert(mulipart);
-                    None
+                    return Some(mulipart.compile());
                 }
+                entry.insert(mulipart);
             }
             Entry::Occupied(mut entry) => {
                 // Add message part.
@@ -247,9 +667,77 @@ impl SMSReceiver {
                     let complete = entry.remove();
                     return Some(complete.compile());
                 }
-
-                None
             }
         }
+
+        // Keep the part until the rest arrive, so it isn't lost if the server restarts.
+        match self
+            .manager
+            .database
+            .insert_multipart_fragment(modem_id, &fragment, &header)
+            .await
+        {
+            Ok(()) => None,
+            Err(e) => Some(Err(e.context("Failed to store multipart message part"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_delivery_report_target() {
+        let candidate = |message_id, sent_at| DeliveryReportCandidate {
+            message_id,
+            part_index: 1,
+            sent_at,
+        };
+        let report = |submitted_at, discharged_at| SmsPartialDeliveryReport {
+            phone_number: "+447700900123".to_string(),
+            reference_id: 7,
+            status: 0,
+            submitted_at,
+            discharged_at,
+        };
+        let select = |candidates: &[DeliveryReportCandidate], report| {
+            select_delivery_report_target(candidates, &report)
+                .map(|target| target.message_id)
+                .map_err(|possible| possible.iter().map(|c| c.message_id).collect::<Vec<_>>())
+        };
+
+        // A single candidate is always used, even without timestamps.
+        assert_eq!(select(&[candidate(1, 1000)], report(None, None)), Ok(1));
+
+        // A reused reference is matched by the service centre timestamp.
+        let candidates = [candidate(2, 100_000), candidate(1, 1000)];
+        assert_eq!(
+            select(&candidates, report(Some(100_030), Some(100_060))),
+            Ok(2)
+        );
+        assert_eq!(
+            select(&candidates, report(Some(1010), Some(200_000))),
+            Ok(1)
+        );
+
+        // Without a service centre timestamp, messages sent after the discharge time are ruled out.
+        assert_eq!(select(&candidates, report(None, Some(2000))), Ok(1));
+        assert_eq!(
+            select(&candidates, report(None, Some(200_000))),
+            Err(vec![2, 1])
+        );
+        assert_eq!(select(&candidates, report(None, None)), Err(vec![2, 1]));
+
+        // Only the candidates sent around the same time are ambiguous, or all of them if none were.
+        let candidates = [candidate(3, 1200), candidate(2, 1000), candidate(1, 10)];
+        assert_eq!(
+            select(&candidates, report(Some(1100), None)),
+            Err(vec![3, 2])
+        );
+        assert_eq!(
+            select(&candidates, report(Some(50_000), None)),
+            Err(vec![3, 2, 1])
+        );
     }
 }
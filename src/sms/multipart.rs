@@ -14,6 +14,8 @@ pub struct SMSMultipartMessages {
     first_message: Option<SmsIncomingMessage>,
     text_len: usize,
     text_parts: Vec<Option<String>>,
+    pdu_parts: Vec<Option<String>>,
+    sent_at: Option<u32>,
     received_count: usize,
 }
 impl SMSMultipartMessages {
@@ -24,6 +26,8 @@ impl SMSMultipartMessages {
             first_message: None,
             text_len: 0,
             text_parts: vec![None; total_size],
+            pdu_parts: vec![None; total_size],
+            sent_at: None,
             received_count: 0,
         }
     }
@@ -46,7 +50,14 @@ impl SMSMultipartMessages {
 
             self.text_len += content.len();
             self.text_parts[idx] = Some(content);
+            self.pdu_parts[idx] = message.raw_pdu.clone();
             self.received_count += 1;
+
+            // The parts can be sent a little apart, so the message is dated by the earliest.
+            self.sent_at = match (self.sent_at, message.sent_at) {
+                (Some(sent_at), Some(part_sent_at)) => Some(sent_at.min(part_sent_at)),
+                (sent_at, part_sent_at) => sent_at.or(part_sent_at),
+            };
         }
 
         if self.first_message.is_none() {
@@ -77,10 +88,32 @@ impl SMSMultipartMessages {
 
         let mut message = SmsMessage::from(first_message);
         message.message_content = content;
+        message.sent_at = self.sent_at;
+        message.raw_pdu = self.pdu_parts.iter().any(Option::is_some).then(|| {
+            self.pdu_parts
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
 
         Ok(message)
     }
 
+    pub fn received_count(&self) -> usize {
+        self.received_count
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    /// Backdate the last update, eg: to when a part restored from the database was received.
+    pub fn set_updated_ago(&mut self, age: Duration) {
+        self.last_updated = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+    }
+
     #[inline]
     pub fn is_stalled(&self) -> bool {
         self.last_updated.elapsed() > MULTIPART_MESSAGES_STALLED_DURATION
@@ -90,13 +123,42 @@ impl SMSMultipartMessages {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modem::handlers::decode_incoming_sms;
     const TEST_NUMBER: &str = "+123456789";
 
+    // Two part 7-bit SMS-DELIVER PDUs from +447700900123, encoded by hand to 3GPP TS 23.040.
+    // These are synthetic, not captured from a modem. TODO: Replace PDUS_16BIT_REFERENCE with a
+    // real IE 0x08 capture (with the sender anonymised) once one is available.
+    // The first parts were sent at 2025-10-15 12:00:00 UTC, and the second parts 5s later.
+    // The text starts on the next septet boundary after the header, so the number of fill bits
+    // depends on the header length, and a wrong count garbles the first character of each part.
+
+    // 8-bit concatenation reference (IE 0x00), a 6 octet header and 1 fill bit.
+    const PDUS_8BIT_REFERENCE: [&str; 2] = [
+        "00440C91447700091032000052015121000000220500032A0201906536FB0D32CBDF6D101D5D0699D3F2391D040FCBE92C10",
+        "00440C914477000910320000520151210050001E0500032A0202C26E32888E4ECF41E939888E2E83E6E5F1DB4D7601",
+    ];
+
+    // 16-bit concatenation reference (IE 0x08), a 7 octet header and no fill bits.
+    const PDUS_16BIT_REFERENCE: [&str; 2] = [
+        "00440C91447700091032000052015121000000230608041F4C0201C8329BFD0699E5EF36888E2E83CC69F99C0E8287E5741608",
+        "00440C914477000910320000520151210050001F0608041F4C02026137194447A7E7A0F41C44479741F3F2F8ED26BB00",
+    ];
+
+    // 8-bit concatenation reference after which there's an EMS text formatting IE (0x0A),
+    // an 11 octet header and 3 fill bits.
+    const PDUS_TEXT_FORMATTING: [&str; 2] = [
+        "00440C91447700091032000052015121000000280A00035C02010A03001B104096D9EC37C82C7FB741747419644ECBE774103C2CA7B340",
+        "00440C91447700091032000052015121005000240A00035C02020A0300171008BBC9203A3A3D07A5E7203ABA0C9A97C76F37D905",
+    ];
+
     fn create_test_message(content: &str) -> SmsIncomingMessage {
         SmsIncomingMessage {
             phone_number: TEST_NUMBER.to_string(),
             user_data_header: None,
             content: content.to_string(),
+            sent_at: None,
+            raw_pdu: None,
         }
     }
 
@@ -154,4 +216,50 @@ mod tests {
         let chinese_len = "世界".len();
         assert_eq!(multipart2.text_len, emoji_len + 3 + chinese_len);
     }
+
+    #[test]
+    fn test_stalled_partial() {
+        let mut multipart = SMSMultipartMessages::with_capacity(3);
+        assert!(!multipart.add_message(create_test_message("First @"), 1));
+        assert!(!multipart.add_message(create_test_message("Third"), 3));
+        assert!(!multipart.is_stalled());
+
+        // Restored parts keep the time they were received.
+        multipart.set_updated_ago(MULTIPART_MESSAGES_STALLED_DURATION + Duration::from_secs(1));
+        assert!(multipart.is_stalled());
+
+        assert_eq!(multipart.received_count(), 2);
+        assert_eq!(multipart.total_size(), 3);
+        assert_eq!(multipart.compile().unwrap().message_content, "First Third");
+    }
+
+    #[test]
+    fn test_decoded_pdus() {
+        for (pdus, reference) in [
+            (PDUS_8BIT_REFERENCE, 0x2A),
+            (PDUS_16BIT_REFERENCE, 0x1F4C),
+            (PDUS_TEXT_FORMATTING, 0x5C),
+        ] {
+            let mut multipart = SMSMultipartMessages::with_capacity(2);
+
+            // Delivered out of order.
+            for (i, pdu) in pdus.iter().rev().enumerate() {
+                let incoming = decode_incoming_sms(pdu).unwrap();
+                let header = incoming.user_data_header.expect("Missing multipart header");
+                assert_eq!(header.message_reference, reference);
+                assert_eq!(header.total, 2);
+
+                assert_eq!(multipart.add_message(incoming, header.index), i == 1);
+            }
+
+            let result = multipart.compile().unwrap();
+            assert_eq!(result.phone_number, "+447700900123");
+            assert_eq!(
+                result.message_content,
+                "Hello from the first part, and this is the second."
+            );
+            assert_eq!(result.sent_at, Some(1760529600));
+            assert_eq!(result.raw_pdu, Some(pdus.join("\n")));
+        }
+    }
 }
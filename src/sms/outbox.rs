@@ -0,0 +1,338 @@
+#![cfg_attr(not(feature = "http-server"), allow(dead_code))]
+
+use crate::backoff::backoff;
+use crate::modem::sender::SmsParts;
+use crate::modem::types::ModemResponse;
+use crate::sms::database::SMSDatabase;
+use crate::sms::SMSManager;
+use anyhow::Result;
+use sms_types::events::Event;
+use sms_types::sms::SmsOutgoingMessage;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex, Notify};
+use tracing::log::{debug, error, info, warn};
+
+const OUTBOX_BATCH_SIZE: u32 = 10;
+const OUTBOX_MAX_ATTEMPTS: u32 = 5;
+const OUTBOX_BASE_RETRY_DELAY: u64 = 15; // seconds
+const OUTBOX_MAX_RETRY_DELAY: u64 = 15 * 60; // 15 minutes
+const OUTBOX_IDLE_POLL: Duration = Duration::from_secs(60);
+const OUTBOX_RESULT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// An outgoing message waiting in the outbox table.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub outbox_id: i64,
+    pub message: SmsOutgoingMessage,
+    pub attempts: u32,
+    pub scheduled_id: Option<i64>,
+    pub partial: Option<PartialSend>,
+}
+
+/// A multipart message that failed partway through sending. The rest of its parts are sent
+/// from the same modem, so the recipient can join them with those already delivered.
+#[derive(Debug, Clone)]
+pub struct PartialSend {
+    pub modem_id: String,
+
+    /// The stored message the sent parts belong to, if it could be stored.
+    pub message_id: Option<i64>,
+    pub parts: SmsParts,
+}
+
+/// The result of queueing a message, as known at the time the caller stopped waiting.
+#[derive(Debug)]
+pub enum OutboxOutcome {
+    /// The message was sent and stored.
+    Sent { message_id: i64, reference_id: u8 },
+
+    /// The message is still in the outbox and will be retried.
+    Queued { outbox_id: i64, reason: String },
+
+    /// The message ran out of attempts, and has been stored as a send failure.
+    Failed { reason: String },
+}
+
+/// Handle used to wake the outbox worker, and wait on the first attempt of a queued message.
+#[derive(Clone, Default)]
+pub struct SMSOutbox {
+    notify: Arc<Notify>,
+    waiters: Arc<Mutex<HashMap<i64, oneshot::Sender<OutboxOutcome>>>>,
+}
+impl SMSOutbox {
+    /// Queue a message and wait for the outcome of its first attempt, or until timeout.
+    pub async fn queue_and_wait(
+        &self,
+        database: &SMSDatabase,
+        message: &SmsOutgoingMessage,
+    ) -> Result<OutboxOutcome> {
+        // Hold the waiters lock while inserting, so the worker can't resolve the
+        // message before there is anything waiting on it.
+        let (outbox_id, rx) = {
+            let mut waiters = self.waiters.lock().await;
+            let outbox_id = database.insert_outbox_message(message).await?;
+
+            let (tx, rx) = oneshot::channel();
+            waiters.insert(outbox_id, tx);
+            (outbox_id, rx)
+        };
+        debug!("Queued outgoing message as outbox #{outbox_id}");
+        self.notify.notify_one();
+
+        let timeout = message
+            .timeout
+            .map_or(OUTBOX_RESULT_TIMEOUT, |s| Duration::from_secs(s as u64 + 1));
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(outcome)) => Ok(outcome),
+            _ => {
+                self.waiters.lock().await.remove(&outbox_id);
+                Ok(OutboxOutcome::Queued {
+                    outbox_id,
+                    reason: "Timed out waiting for send attempt".to_string(),
+                })
+            }
+        }
+    }
+
+    /// Wake the worker to check for due messages, eg: after scheduling a message.
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    async fn resolve(&self, outbox_id: i64, outcome: OutboxOutcome) {
+        if let Some(waiter) = self.waiters.lock().await.remove(&outbox_id) {
+            let _ = waiter.send(outcome);
+        }
+    }
+}
+
+/// Drains the outbox table in order, only attempting sends while a modem is online.
+/// Messages remain in the table until sent or out of attempts, so they survive restarts.
+pub struct OutboxWorker {
+    manager: SMSManager,
+}
+impl OutboxWorker {
+    pub fn new(manager: SMSManager) -> Self {
+        Self { manager }
+    }
+
+    pub async fn run(self) {
+        let database = self.manager.borrow_database().clone();
+        loop {
+            // Hold everything while every modem is offline or shutting down.
+            if let Err(e) = self.manager.modems.wait_for_online().await {
+                error!("Outbox worker stopping: {e}");
+                return;
+            }
+
+            // Move any scheduled messages that are now due into the outbox.
+            match database.release_due_scheduled_messages().await {
+                Ok(0) => {}
+                Ok(released) => debug!("Released {released} scheduled messages into outbox"),
+                Err(e) => error!("Failed to release scheduled messages: {e:?}"),
+            }
+
+            let entries = match database.get_due_outbox_messages(OUTBOX_BATCH_SIZE).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("Failed to get due outbox messages: {e:?}");
+                    tokio::time::sleep(OUTBOX_IDLE_POLL).await;
+                    continue;
+                }
+            };
+
+            // Sleep until the next message is due, or a new message is queued.
+            if entries.is_empty() {
+                let due_in = [
+                    database.get_next_outbox_due_in().await,
+                    database.get_next_scheduled_due_in().await,
+                ]
+                .into_iter()
+                .filter_map(|result| match result {
+                    Ok(seconds) => seconds,
+                    Err(e) => {
+                        error!("Failed to get next outbox attempt: {e:?}");
+                        None
+                    }
+                })
+                .map(|seconds| Duration::from_secs(seconds as u64))
+                .fold(OUTBOX_IDLE_POLL, Duration::min);
+
+                tokio::select! {
+                    _ = self.manager.outbox.notify.notified() => {},
+                    _ = tokio::time::sleep(due_in) => {}
+                }
+                continue;
+            }
+
+            debug!("Processing {} due outbox messages", entries.len());
+            let mut entries = entries.into_iter();
+            while let Some(entry) = entries.next() {
+                if !self.manager.modems.any_online() {
+                    // Put back everything that wasn't attempted, so it's sent as soon as a modem is back.
+                    let outbox_ids: Vec<i64> = std::iter::once(entry)
+                        .chain(entries)
+                        .map(|entry| entry.outbox_id)
+                        .collect();
+                    if let Err(e) = database.release_outbox_messages(&outbox_ids).await {
+                        error!("Failed to release outbox messages: {e:?}");
+                    }
+                    break;
+                }
+                self.attempt(entry).await;
+            }
+        }
+    }
+
+    async fn attempt(&self, mut entry: OutboxEntry) {
+        let outbox_id = entry.outbox_id;
+        let database = self.manager.borrow_database();
+
+        // A partly sent message carries on from the first part that failed, rather than
+        // sending the recipient duplicates of the parts they already have.
+        let modems = &self.manager.modems;
+        let (modem, parts, mut message_id) = match &entry.partial {
+            Some(partial) => match modems.get(Some(&partial.modem_id)) {
+                Ok(modem) => (modem, Ok(partial.parts.clone()), partial.message_id),
+                Err(e) => {
+                    warn!("Outbox message #{outbox_id} can't resume sending from a removed modem, resending all parts: {e}");
+                    if let Err(e) = self
+                        .manager
+                        .store_send_failure(&entry.message, &e.to_string(), Some(partial))
+                        .await
+                    {
+                        error!("Failed to store send failure for the sent parts of outbox message #{outbox_id}: {e:?}");
+                    }
+                    entry.partial = None;
+
+                    let modem = modems.route(&entry.message.to);
+                    (modem, SmsParts::encode(&entry.message), None)
+                }
+            },
+            None => (
+                modems.route(&entry.message.to),
+                SmsParts::encode(&entry.message),
+                None,
+            ),
+        };
+
+        // A timed out send may still have been delivered by the modem, so retrying
+        // here can duplicate a message. That's preferable to silently losing it.
+        let mut parts = match parts {
+            Ok(parts) => parts,
+            Err(e) => return self.fail(entry, e.to_string()).await,
+        };
+        let sent_before = parts.references.len();
+        let result = self
+            .manager
+            .send_sms(modem, &entry.message, &mut parts, &mut message_id)
+            .await;
+        let reason = match (result, message_id) {
+            (Ok(ModemResponse::SendResult(reference_id)), Some(message_id))
+                if parts.is_complete() =>
+            {
+                if let Err(e) = database.delete_outbox_message(outbox_id).await {
+                    error!("Failed to remove sent message #{outbox_id} from outbox: {e:?}");
+                }
+                self.broadcast_dispatched(&entry, message_id, true);
+                self.manager
+                    .outbox
+                    .resolve(
+                        outbox_id,
+                        OutboxOutcome::Sent {
+                            message_id,
+                            reference_id,
+                        },
+                    )
+                    .await;
+                return;
+            }
+            (Ok(ModemResponse::Error(message)), _) => message,
+            (Ok(response), _) => format!("Unexpected SendSMS response: {response:?}"),
+            (Err(e), _) => e.to_string(),
+        };
+
+        // Remember the parts the modem accepted, so the next attempt can carry on from there.
+        if parts.references.len() > sent_before {
+            let partial = PartialSend {
+                modem_id: modem.id.to_string(),
+                message_id,
+                parts,
+            };
+            if let Err(e) = database.set_outbox_partial_send(outbox_id, &partial).await {
+                error!("Failed to store sent parts of outbox message #{outbox_id}: {e:?}");
+            }
+            entry.partial = Some(partial);
+        }
+
+        // Only count the attempt if the modem was actually online to try it.
+        let consumed = modem.is_online();
+        let attempts = entry.attempts + u32::from(consumed);
+        if attempts >= OUTBOX_MAX_ATTEMPTS {
+            warn!("Outbox message #{outbox_id} failed after {attempts} attempts: {reason}");
+            return self.fail(entry, reason).await;
+        }
+
+        let delay = if consumed {
+            backoff(OUTBOX_BASE_RETRY_DELAY, OUTBOX_MAX_RETRY_DELAY, attempts)
+        } else {
+            OUTBOX_BASE_RETRY_DELAY
+        };
+        info!("Outbox message #{outbox_id} failed (attempt {attempts}), retrying in {delay}s: {reason}");
+
+        if let Err(e) = database
+            .update_outbox_attempt(outbox_id, &reason, consumed, delay)
+            .await
+        {
+            error!("Failed to update outbox message #{outbox_id}: {e:?}");
+        }
+        self.manager
+            .outbox
+            .resolve(outbox_id, OutboxOutcome::Queued { outbox_id, reason })
+            .await;
+    }
+
+    /// Give up on a message, storing it as a send failure and removing it from the outbox.
+    async fn fail(&self, entry: OutboxEntry, reason: String) {
+        let outbox_id = entry.outbox_id;
+        match self
+            .manager
+            .store_send_failure(&entry.message, &reason, entry.partial.as_ref())
+            .await
+        {
+            Ok(message_id) => self.broadcast_dispatched(&entry, message_id, false),
+            Err(e) => {
+                error!("Failed to store send failure for outbox message #{outbox_id}: {e:?}")
+            }
+        }
+        if let Err(e) = self
+            .manager
+            .borrow_database()
+            .delete_outbox_message(outbox_id)
+            .await
+        {
+            error!("Failed to remove failed message #{outbox_id} from outbox: {e:?}");
+        }
+
+        self.manager
+            .outbox
+            .resolve(outbox_id, OutboxOutcome::Failed { reason })
+            .await;
+    }
+
+    /// Let consumers know a scheduled message has left the outbox, with its stored message_id.
+    fn broadcast_dispatched(&self, entry: &OutboxEntry, message_id: i64, success: bool) {
+        let (Some(scheduled_id), Some(broadcaster)) =
+            (entry.scheduled_id, &self.manager.broadcaster)
+        else {
+            return;
+        };
+        broadcaster.broadcast(Event::ScheduledMessageDispatched {
+            scheduled_id,
+            message_id,
+            success,
+        });
+    }
+}
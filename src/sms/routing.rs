@@ -0,0 +1,241 @@
+use crate::config::RoutingPolicy;
+use crate::modem::sender::ModemSender;
+use crate::modem::types::ModemStatus;
+use anyhow::{anyhow, bail, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A configured modem that messages can be routed to.
+#[derive(Clone)]
+pub struct RoutedModem {
+    pub id: Arc<str>,
+    pub sender: ModemSender,
+    prefixes: Vec<String>,
+}
+impl RoutedModem {
+    pub fn new(id: &str, prefixes: Vec<String>, sender: ModemSender) -> Self {
+        Self {
+            id: id.into(),
+            sender,
+            prefixes,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.sender.status() == ModemStatus::Online
+    }
+
+    /// The length of the longest prefix matching destination, if any do.
+    fn prefix_match(&self, destination: &str) -> Option<usize> {
+        self.prefixes
+            .iter()
+            .filter(|prefix| destination.starts_with(prefix.as_str()))
+            .map(String::len)
+            .max()
+    }
+}
+
+/// Picks which modem each outgoing message is sent from, according to the routing policy.
+pub struct ModemRouter {
+    modems: Vec<RoutedModem>,
+    policy: RoutingPolicy,
+    next: AtomicUsize,
+}
+impl ModemRouter {
+    pub fn new(policy: RoutingPolicy, modems: Vec<RoutedModem>) -> Result<Self> {
+        if modems.is_empty() {
+            bail!("At least one modem must be configured!");
+        }
+        Ok(Self {
+            modems,
+            policy,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// All modems in config order.
+    pub fn modems(&self) -> &[RoutedModem] {
+        &self.modems
+    }
+
+    /// Get a modem by id, or the first configured modem if there's no id.
+    pub fn get(&self, modem_id: Option<&str>) -> Result<&RoutedModem> {
+        match modem_id {
+            Some(modem_id) => self
+                .modems
+                .iter()
+                .find(|modem| &*modem.id == modem_id)
+                .ok_or_else(|| anyhow!("Unknown modem '{modem_id}'")),
+            None => Ok(&self.modems[0]),
+        }
+    }
+
+    /// The modems allowed to send to destination, in order of preference.
+    pub fn candidates(&self, destination: &str) -> Vec<&RoutedModem> {
+        if self.policy != RoutingPolicy::Prefix {
+            return self.modems.iter().collect();
+        }
+
+        let longest = self
+            .modems
+            .iter()
+            .filter_map(|modem| modem.prefix_match(destination))
+            .max();
+
+        let candidates: Vec<_> = match longest {
+            Some(longest) => self
+                .modems
+                .iter()
+                .filter(|modem| modem.prefix_match(destination) == Some(longest))
+                .collect(),
+            None => self
+                .modems
+                .iter()
+                .filter(|modem| modem.prefixes.is_empty())
+                .collect(),
+        };
+
+        // With no matching or catch-all modems, anything is better than nothing.
+        if candidates.is_empty() {
+            self.modems.iter().collect()
+        } else {
+            candidates
+        }
+    }
+
+    /// Choose the modem to send a message to destination with. If none of the candidates
+    /// are online, the most preferred is returned so the attempt fails and is retried.
+    pub fn route(&self, destination: &str) -> &RoutedModem {
+        let candidates = self.candidates(destination);
+        let start = match self.policy {
+            RoutingPolicy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+            RoutingPolicy::Failover | RoutingPolicy::Prefix => 0,
+        } % candidates.len();
+
+        candidates
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(candidates.len())
+            .find(|modem| modem.is_online())
+            .unwrap_or(&candidates[start])
+    }
+
+    pub fn any_online(&self) -> bool {
+        self.modems.iter().any(RoutedModem::is_online)
+    }
+
+    /// Wait until any modem is online, returning immediately if one already is.
+    pub async fn wait_for_online(&self) -> Result<()> {
+        let waiting = self
+            .modems
+            .iter()
+            .map(|modem| Box::pin(modem.sender.wait_for_online()));
+
+        futures::future::select_ok(waiting).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::{mpsc, watch};
+
+    fn modem(id: &str, prefixes: &[&str], status: ModemStatus) -> RoutedModem {
+        let (command_tx, _) = mpsc::channel(1);
+        let (_, status_rx) = watch::channel(status);
+        let (_, queue_rx) = watch::channel(Default::default());
+        RoutedModem::new(
+            id,
+            prefixes.iter().map(|prefix| prefix.to_string()).collect(),
+            ModemSender::new(command_tx, status_rx, queue_rx),
+        )
+    }
+
+    fn route(router: &ModemRouter, destination: &str) -> String {
+        router.route(destination).id.to_string()
+    }
+
+    #[test]
+    fn test_failover() {
+        let router = ModemRouter::new(
+            RoutingPolicy::Failover,
+            vec![
+                modem("a", &[], ModemStatus::Offline),
+                modem("b", &[], ModemStatus::Online),
+                modem("c", &[], ModemStatus::Online),
+            ],
+        )
+        .unwrap();
+        assert_eq!(route(&router, "+44"), "b");
+        assert_eq!(route(&router, "+44"), "b");
+
+        let router = ModemRouter::new(
+            RoutingPolicy::Failover,
+            vec![
+                modem("a", &[], ModemStatus::Offline),
+                modem("b", &[], ModemStatus::Offline),
+            ],
+        )
+        .unwrap();
+        assert_eq!(route(&router, "+44"), "a");
+        assert!(!router.any_online());
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let router = ModemRouter::new(
+            RoutingPolicy::RoundRobin,
+            vec![
+                modem("a", &[], ModemStatus::Online),
+                modem("b", &[], ModemStatus::Offline),
+                modem("c", &[], ModemStatus::Online),
+            ],
+        )
+        .unwrap();
+        assert_eq!(route(&router, "+44"), "a");
+        assert_eq!(route(&router, "+44"), "c");
+        assert_eq!(route(&router, "+44"), "c");
+        assert_eq!(route(&router, "+44"), "a");
+    }
+
+    #[test]
+    fn test_prefix() {
+        let router = ModemRouter::new(
+            RoutingPolicy::Prefix,
+            vec![
+                modem("uk", &["+44"], ModemStatus::Online),
+                modem("uk-mobile", &["+447"], ModemStatus::Online),
+                modem("fr", &["+33"], ModemStatus::Offline),
+                modem("other", &[], ModemStatus::Online),
+            ],
+        )
+        .unwrap();
+        assert_eq!(route(&router, "+441234"), "uk");
+        assert_eq!(route(&router, "+447700"), "uk-mobile");
+        assert_eq!(route(&router, "+1555"), "other");
+
+        // Matching modems are preferred even when offline, so the message waits for them.
+        assert_eq!(route(&router, "+3312"), "fr");
+        assert!(router
+            .candidates("+3312")
+            .iter()
+            .all(|modem| !modem.is_online()));
+    }
+
+    #[test]
+    fn test_get() {
+        let router = ModemRouter::new(
+            RoutingPolicy::Failover,
+            vec![
+                modem("a", &[], ModemStatus::Online),
+                modem("b", &[], ModemStatus::Online),
+            ],
+        )
+        .unwrap();
+        assert_eq!(&*router.get(None).unwrap().id, "a");
+        assert_eq!(&*router.get(Some("b")).unwrap().id, "b");
+        assert!(router.get(Some("c")).is_err());
+        assert!(ModemRouter::new(RoutingPolicy::Failover, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+#![cfg_attr(not(feature = "http-server"), allow(dead_code))]
+
+#[cfg(feature = "db-postgres")]
+mod postgres;
+#[cfg(feature = "db-sqlite")]
+mod sqlite;
+
+#[cfg(feature = "db-postgres")]
+pub use postgres::PostgresStorage;
+#[cfg(feature = "db-sqlite")]
+pub use sqlite::SqliteStorage;
+
+use crate::modem::sender::SmsParts;
+use crate::sms::outbox::{OutboxEntry, PartialSend};
+use crate::webhooks::WebhookDelivery;
+use anyhow::Result;
+use async_trait::async_trait;
+use sms_types::modem::IncomingCall;
+use sms_types::sms::{
+    SmsDeliveryReport, SmsMessage, SmsMessagePart, SmsMultipartHeader, SmsOutgoingMessage,
+    SmsPartialDeliveryReport, SmsScheduledMessage,
+};
+
+/// Every (table, id column, content column) stored encrypted.
+pub const ENCRYPTED_COLUMNS: [(&str, &str, &str); 9] = [
+    ("messages", "message_id", "message_content"),
+    ("messages", "message_id", "raw_pdu"),
+    ("multipart_fragments", "fragment_id", "message_content"),
+    ("multipart_fragments", "fragment_id", "raw_pdu"),
+    ("outbox", "outbox_id", "message_content"),
+    ("outbox", "outbox_id", "partial_pdus"),
+    ("scheduled_messages", "scheduled_id", "message_content"),
+    ("webhook_deliveries", "delivery_id", "payload"),
+    ("webhook_dead_letters", "dead_letter_id", "payload"),
+];
+
+fn build_pagination_query(
+    base_query: &str,
+    order_by: &str,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    reverse: bool,
+) -> String {
+    let order_direction = if reverse { "ASC" } else { "DESC" };
+    let mut query = format!("{base_query} ORDER BY {order_by} {order_direction}");
+
+    if let Some(limit_val) = limit {
+        query.push_str(&format!(" LIMIT {limit_val}"));
+    }
+
+    if let Some(offset_val) = offset {
+        query.push_str(&format!(" OFFSET {offset_val}"));
+    }
+
+    query
+}
+
+/// Build the partial send of an outbox row, if it has one. The PDUs are a single
+/// encrypted value, which the `SMSDatabase` decrypts and splits into each part.
+fn partial_send_from_columns(
+    modem_id: Option<String>,
+    message_id: Option<i64>,
+    encrypted_pdus: Option<String>,
+    references: Option<String>,
+) -> Option<PartialSend> {
+    let references = references?
+        .split(',')
+        .map(str::parse)
+        .collect::<Result<Vec<u8>, _>>()
+        .ok()?;
+
+    Some(PartialSend {
+        modem_id: modem_id?,
+        message_id,
+        parts: SmsParts {
+            pdus: vec![encrypted_pdus?],
+            references,
+        },
+    })
+}
+
+/// A sent message part that an incoming delivery report could be for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryReportCandidate {
+    pub message_id: i64,
+    pub part_index: u8,
+
+    /// When this part was sent, which can be later than the message for a resumed send.
+    pub sent_at: u32,
+}
+
+/// A received part of a multipart message that's still waiting for its other parts.
+pub struct MultipartFragment {
+    pub modem_id: String,
+    pub phone_number: String,
+    pub header: SmsMultipartHeader,
+    pub content: String,
+    pub sent_at: Option<u32>,
+    pub raw_pdu: Option<String>,
+    pub created_at: u32,
+}
+
+/// A dead-lettered webhook delivery, with the payload still encrypted.
+pub struct StoredDeadLetter {
+    pub dead_letter_id: i64,
+    pub url: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub created_at: u32,
+}
+
+/// Replacement content for an encrypted row, and message search tokens if it's a message.
+pub struct ReencryptedRow {
+    pub id: i64,
+    pub content: String,
+    pub tokens: Option<Vec<String>>,
+}
+
+/// A database backend. All content is already encrypted by the `SMSDatabase`, and is returned
+/// still encrypted, so backends only need to store and query it.
+#[async_trait]
+pub trait SMSStorage: Send + Sync {
+    /// Insert a message along with its search tokens.
+    async fn insert_message(
+        &self,
+        message: &SmsMessage,
+        encrypted_content: String,
+        encrypted_raw_pdu: Option<String>,
+        tokens: Vec<String>,
+        is_final: bool,
+    ) -> Result<i64>;
+
+    /// Record why a message couldn't be sent, completing it if it isn't already.
+    async fn insert_send_failure(&self, message_id: i64, error_message: &str) -> Result<i64>;
+
+    async fn insert_delivery_report(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        report: &SmsPartialDeliveryReport,
+        is_final: bool,
+        ambiguous: bool,
+    ) -> Result<i64>;
+
+    /// Store the message reference of each sent part, in the order they were sent,
+    /// skipping the first `stored` parts which have already been stored.
+    async fn insert_message_parts(
+        &self,
+        message_id: i64,
+        references: &[u8],
+        stored: usize,
+    ) -> Result<()>;
+
+    /// Every incomplete part sent to phone_number with reference_id, newest first.
+    async fn get_delivery_report_candidates(
+        &self,
+        phone_number: &str,
+        reference_id: u8,
+        modem_id: &str,
+    ) -> Result<Vec<DeliveryReportCandidate>>;
+
+    async fn update_message_part_status(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        status: u8,
+        completed: bool,
+    ) -> Result<()>;
+
+    async fn get_message_parts(&self, message_id: i64) -> Result<Vec<SmsMessagePart>>;
+
+    async fn update_message_status(
+        &self,
+        message_id: i64,
+        status: u8,
+        completed: bool,
+    ) -> Result<()>;
+
+    /// Store a received multipart message part, ignoring it if the part is already stored.
+    async fn insert_multipart_fragment(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        header: &SmsMultipartHeader,
+        encrypted_content: String,
+        sent_at: Option<u32>,
+        encrypted_raw_pdu: Option<String>,
+    ) -> Result<()>;
+
+    /// Every stored multipart message part, in the order they were received.
+    async fn get_multipart_fragments(&self) -> Result<Vec<MultipartFragment>>;
+
+    async fn delete_multipart_fragments(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        message_reference: u16,
+    ) -> Result<()>;
+
+    async fn update_friendly_name(
+        &self,
+        phone_number: &str,
+        friendly_name: Option<&str>,
+    ) -> Result<()>;
+
+    async fn get_friendly_name(&self, phone_number: &str) -> Result<Option<String>>;
+
+    async fn get_latest_numbers(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<(String, Option<String>)>>;
+
+    async fn get_messages(
+        &self,
+        phone_number: &str,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>>;
+
+    /// Messages that have every token, optionally only with phone_number.
+    async fn search_messages(
+        &self,
+        tokens: &[String],
+        phone_number: Option<&str>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>>;
+
+    async fn get_delivery_reports(
+        &self,
+        message_id: i64,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsDeliveryReport>>;
+
+    async fn insert_call(
+        &self,
+        modem_id: &str,
+        phone_number: Option<&str>,
+        rejected: bool,
+    ) -> Result<i64>;
+
+    async fn get_calls(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<IncomingCall>>;
+
+    async fn insert_outbox_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+    ) -> Result<i64>;
+
+    /// Get the oldest due outbox messages, claiming them where the database can be shared.
+    async fn get_due_outbox_messages(&self, limit: u32) -> Result<Vec<OutboxEntry>>;
+
+    /// Make claimed outbox messages due again without counting an attempt.
+    async fn release_outbox_messages(&self, outbox_ids: &[i64]) -> Result<()>;
+
+    async fn get_next_outbox_due_in(&self) -> Result<Option<i64>>;
+
+    async fn update_outbox_attempt(
+        &self,
+        outbox_id: i64,
+        error_message: &str,
+        consumed: bool,
+        delay: u64,
+    ) -> Result<()>;
+
+    async fn set_outbox_partial_send(
+        &self,
+        outbox_id: i64,
+        modem_id: &str,
+        message_id: Option<i64>,
+        encrypted_pdus: String,
+        references: String,
+    ) -> Result<()>;
+
+    async fn delete_outbox_message(&self, outbox_id: i64) -> Result<()>;
+
+    async fn insert_scheduled_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+        send_at: u32,
+    ) -> Result<i64>;
+
+    async fn get_scheduled_messages(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsScheduledMessage>>;
+
+    async fn cancel_scheduled_message(&self, scheduled_id: i64) -> Result<bool>;
+
+    async fn reschedule_scheduled_message(&self, scheduled_id: i64, send_at: u32) -> Result<bool>;
+
+    async fn release_due_scheduled_messages(&self) -> Result<u64>;
+
+    async fn get_next_scheduled_due_in(&self) -> Result<Option<i64>>;
+
+    async fn insert_webhook_delivery(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<i64>;
+
+    async fn get_due_webhook_deliveries(&self, limit: u32) -> Result<Vec<WebhookDelivery>>;
+
+    async fn update_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<()>;
+
+    async fn delete_webhook_delivery(&self, delivery_id: i64) -> Result<()>;
+
+    async fn dead_letter_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<()>;
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<i64>;
+
+    async fn get_webhook_dead_letters(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<StoredDeadLetter>>;
+
+    async fn replay_webhook_dead_letter(&self, dead_letter_id: i64) -> Result<bool>;
+
+    /// Messages after message_id that haven't been tokenized, as (message_id, encrypted content).
+    async fn get_untokenized_messages(
+        &self,
+        after_message_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>>;
+
+    /// Store the search tokens of messages, and mark them as tokenized even if there are none.
+    async fn insert_message_tokens(&self, messages: Vec<(i64, Vec<String>)>) -> Result<()>;
+
+    /// Rows of an `ENCRYPTED_COLUMNS` entry after id, as (id, encrypted content).
+    async fn get_encrypted_rows(
+        &self,
+        column: (&str, &str, &str),
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>>;
+
+    /// Replace encrypted content (and message tokens) for rows of an `ENCRYPTED_COLUMNS` entry.
+    async fn update_encrypted_rows(
+        &self,
+        column: (&str, &str, &str),
+        rows: Vec<ReencryptedRow>,
+    ) -> Result<()>;
+}
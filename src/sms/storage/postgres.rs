@@ -0,0 +1,1115 @@
+use crate::sms::migrations::{self, POSTGRES_MIGRATIONS, SCHEMA_VERSION_SQL};
+use crate::sms::outbox::OutboxEntry;
+use crate::sms::storage::{
+    build_pagination_query, partial_send_from_columns, DeliveryReportCandidate, MultipartFragment,
+    ReencryptedRow, SMSStorage, StoredDeadLetter,
+};
+use crate::webhooks::WebhookDelivery;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sms_types::modem::IncomingCall;
+use sms_types::sms::{
+    SmsDeliveryReport, SmsMessage, SmsMessagePart, SmsMultipartHeader, SmsOutgoingMessage,
+    SmsPartialDeliveryReport, SmsScheduledMessage,
+};
+use sqlx::postgres::{PgPoolOptions, PgRow};
+use sqlx::{PgConnection, PgPool, Row};
+use std::time::Duration;
+use tracing::log::{debug, info};
+
+// Postgres has no unsigned integers, so values are stored in the next signed size up
+// (SMALLINT for u8, INTEGER for u16, BIGINT for u32 timestamps) and converted back when reading.
+
+/// Advisory lock held while migrating, so servers starting together don't apply the same migration.
+const MIGRATION_LOCK_ID: i64 = 0x736d_732d_7365_7276;
+
+/// How long a claimed outbox message or webhook delivery is hidden from other servers sharing
+/// the database. Each attempt sets next_attempt_at itself, so this only matters if a server
+/// stops before finishing one.
+const CLAIM_LEASE: i64 = 15 * 60; // seconds
+
+fn message_from_row(row: PgRow) -> SmsMessage {
+    SmsMessage {
+        message_id: row.get("message_id"),
+        phone_number: row.get("phone_number"),
+        message_content: row.get("message_content"),
+        message_reference: row
+            .get::<Option<i16>, _>("message_reference")
+            .map(|reference| reference as u8),
+        is_outgoing: row.get("is_outgoing"),
+        created_at: row
+            .get::<Option<i64>, _>("created_at")
+            .map(|created_at| created_at as u32),
+        completed_at: row
+            .get::<Option<i64>, _>("completed_at")
+            .map(|completed_at| completed_at as u32),
+        status: row
+            .get::<Option<i16>, _>("status")
+            .map(|status| status as u8),
+        modem_id: row.get("modem_id"),
+        incomplete: row.get("incomplete"),
+        sent_at: row
+            .get::<Option<i64>, _>("sent_at")
+            .map(|sent_at| sent_at as u32),
+        raw_pdu: row.get("raw_pdu"),
+    }
+}
+
+async fn insert_tokens(conn: &mut PgConnection, message_id: i64, tokens: &[String]) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO message_tokens (token, message_id) SELECT token, $2 FROM UNNEST($1::TEXT[]) AS token ON CONFLICT DO NOTHING"
+    )
+        .bind(tokens)
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await
+        .context("Failed to insert message tokens")?;
+
+    Ok(())
+}
+
+/// Several servers can share a Postgres database, so due outbox messages and webhook deliveries
+/// are claimed before being attempted, and each server only restores the multipart fragments
+/// and resumes the partial sends of its own modems.
+pub struct PostgresStorage {
+    pool: PgPool,
+    modem_ids: Vec<String>,
+}
+impl PostgresStorage {
+    pub async fn connect(database_url: &str, modem_ids: Vec<String>) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(20)
+            .min_connections(1)
+            .acquire_timeout(Duration::from_secs(30))
+            .test_before_acquire(true)
+            .connect(database_url)
+            .await
+            .context("Failed to connect to database")?;
+
+        let storage = Self { pool, modem_ids };
+        storage.migrate().await?;
+        Ok(storage)
+    }
+
+    /// Apply all pending migrations in order, in a single transaction holding the migration lock.
+    /// Postgres DDL is transactional, so a failed migration leaves the schema untouched.
+    async fn migrate(&self) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(MIGRATION_LOCK_ID)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to take migration lock")?;
+
+        sqlx::query(SCHEMA_VERSION_SQL)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to create schema_version table")?;
+
+        let current: i32 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+                .fetch_one(&mut *transaction)
+                .await
+                .context("Failed to query schema version")?;
+
+        let current = current as u32;
+        let pending = migrations::pending(POSTGRES_MIGRATIONS, current)?;
+        if pending.is_empty() {
+            debug!("Database schema is up to date at version {current}");
+            return Ok(());
+        }
+
+        for migration in pending {
+            info!(
+                "Applying database migration {} ({})",
+                migration.version, migration.name
+            );
+
+            sqlx::raw_sql(migration.sql)
+                .execute(&mut *transaction)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to apply migration {} ({})",
+                        migration.version, migration.name
+                    )
+                })?;
+
+            sqlx::query(
+                "INSERT INTO schema_version (version, name, applied_at) VALUES ($1, $2, unixepoch())",
+            )
+            .bind(migration.version as i32)
+            .bind(migration.name)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to record schema version")?;
+        }
+        transaction.commit().await?;
+
+        let latest = pending
+            .last()
+            .map_or(current, |migration| migration.version);
+        info!("Database schema migrated from version {current} to {latest}");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SMSStorage for PostgresStorage {
+    async fn insert_message(
+        &self,
+        message: &SmsMessage,
+        encrypted_content: String,
+        encrypted_raw_pdu: Option<String>,
+        tokens: Vec<String>,
+        is_final: bool,
+    ) -> Result<i64> {
+        let mut transaction = self.pool.begin().await?;
+        let message_id: i64 = if is_final {
+            sqlx::query_scalar(
+                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status, modem_id, incomplete, sent_at, raw_pdu, tokenized, completed_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, TRUE, unixepoch()) RETURNING message_id"
+            )
+        } else {
+            sqlx::query_scalar(
+                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status, modem_id, incomplete, sent_at, raw_pdu, tokenized) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, TRUE) RETURNING message_id"
+            )
+        }
+            .bind(&message.phone_number)
+            .bind(encrypted_content)
+            .bind(message.message_reference.map(i16::from))
+            .bind(message.is_outgoing)
+            .bind(message.status.map(i16::from))
+            .bind(&message.modem_id)
+            .bind(message.incomplete)
+            .bind(message.sent_at.map(i64::from))
+            .bind(encrypted_raw_pdu)
+            .fetch_one(&mut *transaction)
+            .await
+            .context("Failed to insert SmsMessage")?;
+
+        insert_tokens(&mut transaction, message_id, &tokens).await?;
+
+        transaction.commit().await?;
+        Ok(message_id)
+    }
+
+    async fn insert_send_failure(&self, message_id: i64, error_message: &str) -> Result<i64> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO send_failures (message_id, error_message) VALUES ($1, $2)")
+            .bind(message_id)
+            .bind(error_message)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to insert send failure")?;
+
+        sqlx::query("UPDATE messages SET completed_at = unixepoch() WHERE message_id = $1 AND completed_at IS NULL")
+            .bind(message_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to complete failed message")?;
+
+        transaction.commit().await?;
+
+        // send_failures is keyed by message_id, so that's the row's id.
+        Ok(message_id)
+    }
+
+    async fn insert_delivery_report(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        report: &SmsPartialDeliveryReport,
+        is_final: bool,
+        ambiguous: bool,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO delivery_reports (message_id, part_index, status, is_final, submitted_at, discharged_at, ambiguous) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING report_id",
+        )
+        .bind(message_id)
+        .bind(i16::from(part_index))
+        .bind(i16::from(report.status))
+        .bind(is_final)
+        .bind(report.submitted_at.map(i64::from))
+        .bind(report.discharged_at.map(i64::from))
+        .bind(ambiguous)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to insert delivery report")
+    }
+
+    async fn insert_message_parts(
+        &self,
+        message_id: i64,
+        references: &[u8],
+        stored: usize,
+    ) -> Result<()> {
+        let part_indexes: Vec<i16> = (stored as i16 + 1..=references.len() as i16).collect();
+        let references: Vec<i16> = references[stored..]
+            .iter()
+            .copied()
+            .map(i16::from)
+            .collect();
+        sqlx::query(
+            "INSERT INTO message_parts (message_id, part_index, message_reference, sent_at) SELECT $1, part_index, message_reference, unixepoch() FROM UNNEST($2::SMALLINT[], $3::SMALLINT[]) AS parts(part_index, message_reference)"
+        )
+            .bind(message_id)
+            .bind(part_indexes)
+            .bind(references)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert message parts")?;
+
+        Ok(())
+    }
+
+    async fn get_delivery_report_candidates(
+        &self,
+        phone_number: &str,
+        reference_id: u8,
+        modem_id: &str,
+    ) -> Result<Vec<DeliveryReportCandidate>> {
+        let result = sqlx::query(
+            "SELECT p.message_id, p.part_index, COALESCE(p.sent_at, m.created_at) AS sent_at FROM message_parts p JOIN messages m ON m.message_id = p.message_id WHERE p.completed_at IS NULL AND p.message_reference = $1 AND m.is_outgoing = TRUE AND m.phone_number = $2 AND (m.modem_id IS NULL OR m.modem_id = $3) ORDER BY p.message_id DESC"
+        )
+            .bind(i16::from(reference_id))
+            .bind(phone_number)
+            .bind(modem_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query appropriate target messages for delivery report")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| DeliveryReportCandidate {
+                message_id: row.get("message_id"),
+                part_index: row.get::<i16, _>("part_index") as u8,
+                sent_at: row.get::<i64, _>("sent_at") as u32,
+            })
+            .collect())
+    }
+
+    async fn update_message_part_status(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        status: u8,
+        completed: bool,
+    ) -> Result<()> {
+        let query = if completed {
+            sqlx::query(
+                "UPDATE message_parts SET status = $1, completed_at = unixepoch() WHERE message_id = $2 AND part_index = $3",
+            )
+        } else {
+            sqlx::query(
+                "UPDATE message_parts SET status = $1 WHERE message_id = $2 AND part_index = $3",
+            )
+        };
+
+        query
+            .bind(i16::from(status))
+            .bind(message_id)
+            .bind(i16::from(part_index))
+            .execute(&self.pool)
+            .await
+            .context("Failed to update message part status")?;
+
+        Ok(())
+    }
+
+    async fn get_message_parts(&self, message_id: i64) -> Result<Vec<SmsMessagePart>> {
+        let result = sqlx::query(
+            "SELECT message_id, part_index, message_reference, status, completed_at FROM message_parts WHERE message_id = $1 ORDER BY part_index ASC",
+        )
+        .bind(message_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query message parts")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| SmsMessagePart {
+                message_id: row.get("message_id"),
+                part_index: row.get::<i16, _>("part_index") as u8,
+                message_reference: row.get::<i16, _>("message_reference") as u8,
+                status: row
+                    .get::<Option<i16>, _>("status")
+                    .map(|status| status as u8),
+                completed_at: row
+                    .get::<Option<i64>, _>("completed_at")
+                    .map(|completed_at| completed_at as u32),
+            })
+            .collect())
+    }
+
+    async fn update_message_status(
+        &self,
+        message_id: i64,
+        status: u8,
+        completed: bool,
+    ) -> Result<()> {
+        let query = if completed {
+            sqlx::query(
+                "UPDATE messages SET status = $1, completed_at = unixepoch() WHERE message_id = $2",
+            )
+        } else {
+            sqlx::query("UPDATE messages SET status = $1 WHERE message_id = $2")
+        };
+
+        query
+            .bind(i16::from(status))
+            .bind(message_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update message status")?;
+
+        Ok(())
+    }
+
+    async fn insert_multipart_fragment(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        header: &SmsMultipartHeader,
+        encrypted_content: String,
+        sent_at: Option<u32>,
+        encrypted_raw_pdu: Option<String>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO multipart_fragments (modem_id, phone_number, message_reference, part_index, total_parts, message_content, sent_at, raw_pdu) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING"
+        )
+            .bind(modem_id)
+            .bind(phone_number)
+            .bind(i32::from(header.message_reference))
+            .bind(i16::from(header.index))
+            .bind(i16::from(header.total))
+            .bind(encrypted_content)
+            .bind(sent_at.map(i64::from))
+            .bind(encrypted_raw_pdu)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert multipart fragment")?;
+
+        Ok(())
+    }
+
+    async fn get_multipart_fragments(&self) -> Result<Vec<MultipartFragment>> {
+        let result = sqlx::query(
+            "SELECT modem_id, phone_number, message_reference, part_index, total_parts, message_content, sent_at, raw_pdu, created_at FROM multipart_fragments WHERE modem_id = ANY($1) ORDER BY fragment_id ASC"
+        )
+            .bind(&self.modem_ids)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query multipart fragments")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| MultipartFragment {
+                modem_id: row.get("modem_id"),
+                phone_number: row.get("phone_number"),
+                header: SmsMultipartHeader {
+                    message_reference: row.get::<i32, _>("message_reference") as u16,
+                    total: row.get::<i16, _>("total_parts") as u8,
+                    index: row.get::<i16, _>("part_index") as u8,
+                },
+                content: row.get("message_content"),
+                sent_at: row
+                    .get::<Option<i64>, _>("sent_at")
+                    .map(|sent_at| sent_at as u32),
+                raw_pdu: row.get("raw_pdu"),
+                created_at: row.get::<i64, _>("created_at") as u32,
+            })
+            .collect())
+    }
+
+    async fn delete_multipart_fragments(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        message_reference: u16,
+    ) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM multipart_fragments WHERE modem_id = $1 AND phone_number = $2 AND message_reference = $3"
+        )
+            .bind(modem_id)
+            .bind(phone_number)
+            .bind(i32::from(message_reference))
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete multipart fragments")?;
+
+        Ok(())
+    }
+
+    async fn update_friendly_name(
+        &self,
+        phone_number: &str,
+        friendly_name: Option<&str>,
+    ) -> Result<()> {
+        match friendly_name {
+            Some(name) => {
+                sqlx::query(
+                    "INSERT INTO friendly_names (phone_number, friendly_name) VALUES ($1, $2) ON CONFLICT(phone_number) DO UPDATE SET friendly_name = excluded.friendly_name"
+                )
+                    .bind(phone_number)
+                    .bind(name)
+                    .execute(&self.pool)
+                    .await
+                    .context("Failed to insert friendly name")?;
+            }
+            None => {
+                sqlx::query("DELETE FROM friendly_names WHERE phone_number = $1")
+                    .bind(phone_number)
+                    .execute(&self.pool)
+                    .await
+                    .context("Failed to delete friendly name")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_friendly_name(&self, phone_number: &str) -> Result<Option<String>> {
+        sqlx::query_scalar("SELECT friendly_name FROM friendly_names WHERE phone_number = $1")
+            .bind(phone_number)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query friendly name")
+    }
+
+    async fn get_latest_numbers(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<(String, Option<String>)>> {
+        // Postgres requires every selected column to be grouped, there's only ever one friendly name per number.
+        let query = build_pagination_query(
+            "SELECT m.phone_number, f.friendly_name FROM messages m LEFT JOIN friendly_names f ON f.phone_number = m.phone_number GROUP BY m.phone_number, f.friendly_name",
+            "MAX(m.created_at)",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result: Vec<(String, Option<String>)> = sqlx::query_as(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query latest numbers")?;
+
+        Ok(result)
+    }
+
+    async fn get_messages(
+        &self,
+        phone_number: &str,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>> {
+        let query = build_pagination_query(
+            "SELECT message_id, phone_number, message_content, message_reference, is_outgoing, status, created_at, completed_at, modem_id, incomplete, sent_at, raw_pdu FROM messages WHERE phone_number = $1",
+            "created_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .bind(phone_number)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query SmsMessage's")?;
+
+        Ok(result.into_iter().map(message_from_row).collect())
+    }
+
+    async fn search_messages(
+        &self,
+        tokens: &[String],
+        phone_number: Option<&str>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>> {
+        let query = build_pagination_query(
+            "SELECT message_id, phone_number, message_content, message_reference, is_outgoing, status, created_at, completed_at, modem_id, incomplete, sent_at, raw_pdu FROM messages WHERE message_id IN (SELECT message_id FROM message_tokens WHERE token = ANY($1) GROUP BY message_id HAVING COUNT(*) = $2) AND ($3::TEXT IS NULL OR phone_number = $3)",
+            "created_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .bind(tokens)
+            .bind(tokens.len() as i64)
+            .bind(phone_number)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to search SmsMessage's")?;
+
+        Ok(result.into_iter().map(message_from_row).collect())
+    }
+
+    async fn get_delivery_reports(
+        &self,
+        message_id: i64,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsDeliveryReport>> {
+        let query = build_pagination_query(
+            "SELECT report_id, part_index, status, is_final, submitted_at, discharged_at, ambiguous, created_at FROM delivery_reports WHERE message_id = $1",
+            "created_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .bind(message_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query delivery reports")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| SmsDeliveryReport {
+                report_id: row.get("report_id"),
+                part_index: row
+                    .get::<Option<i16>, _>("part_index")
+                    .map(|part_index| part_index as u8),
+                status: row.get::<i16, _>("status") as u8,
+                is_final: row.get("is_final"),
+                submitted_at: row
+                    .get::<Option<i64>, _>("submitted_at")
+                    .map(|submitted_at| submitted_at as u32),
+                discharged_at: row
+                    .get::<Option<i64>, _>("discharged_at")
+                    .map(|discharged_at| discharged_at as u32),
+                ambiguous: row.get("ambiguous"),
+                created_at: Some(row.get::<i64, _>("created_at") as u32),
+            })
+            .collect())
+    }
+
+    async fn insert_call(
+        &self,
+        modem_id: &str,
+        phone_number: Option<&str>,
+        rejected: bool,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO calls (modem_id, phone_number, rejected) VALUES ($1, $2, $3) RETURNING call_id",
+        )
+        .bind(modem_id)
+        .bind(phone_number)
+        .bind(rejected)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to insert call")
+    }
+
+    async fn get_calls(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<IncomingCall>> {
+        let query = build_pagination_query(
+            "SELECT call_id, modem_id, phone_number, rejected, created_at FROM calls",
+            "created_at",
+            limit,
+            offset,
+            reverse,
+        );
+
+        let result = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query calls")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| IncomingCall {
+                call_id: row.get("call_id"),
+                modem_id: row.get("modem_id"),
+                phone_number: row.get("phone_number"),
+                rejected: row.get("rejected"),
+                created_at: Some(row.get::<i64, _>("created_at") as u32),
+            })
+            .collect())
+    }
+
+    async fn insert_outbox_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO outbox (phone_number, message_content, flash, validity_period, timeout) VALUES ($1, $2, $3, $4, $5) RETURNING outbox_id"
+        )
+            .bind(&message.to)
+            .bind(encrypted_content)
+            .bind(message.flash.unwrap_or(false))
+            .bind(message.validity_period.map(i16::from))
+            .bind(message.timeout.map(i64::from))
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to insert outbox message")
+    }
+
+    async fn get_due_outbox_messages(&self, limit: u32) -> Result<Vec<OutboxEntry>> {
+        // Claim the messages by pushing their next attempt past the lease, skipping any
+        // another server is claiming. Partial sends are only resumed from their own modem.
+        let mut result = sqlx::query(
+            "UPDATE outbox SET next_attempt_at = unixepoch() + $2 WHERE outbox_id IN (SELECT outbox_id FROM outbox WHERE next_attempt_at <= unixepoch() AND (partial_modem_id IS NULL OR partial_modem_id = ANY($3)) ORDER BY outbox_id ASC LIMIT $1 FOR UPDATE SKIP LOCKED) RETURNING outbox_id, phone_number, message_content, flash, validity_period, timeout, attempts, scheduled_id, partial_modem_id, partial_message_id, partial_pdus, partial_references"
+        )
+            .bind(i64::from(limit))
+            .bind(CLAIM_LEASE)
+            .bind(&self.modem_ids)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to claim due outbox messages")?;
+        result.sort_by_key(|row| row.get::<i64, _>("outbox_id"));
+
+        Ok(result
+            .into_iter()
+            .map(|row| OutboxEntry {
+                outbox_id: row.get("outbox_id"),
+                message: SmsOutgoingMessage {
+                    to: row.get("phone_number"),
+                    content: row.get("message_content"),
+                    flash: Some(row.get("flash")),
+                    validity_period: row
+                        .get::<Option<i16>, _>("validity_period")
+                        .map(|validity_period| validity_period as u8),
+                    timeout: row
+                        .get::<Option<i64>, _>("timeout")
+                        .map(|timeout| timeout as u32),
+                },
+                attempts: row.get::<i32, _>("attempts") as u32,
+                scheduled_id: row.get("scheduled_id"),
+                partial: partial_send_from_columns(
+                    row.get("partial_modem_id"),
+                    row.get("partial_message_id"),
+                    row.get("partial_pdus"),
+                    row.get("partial_references"),
+                ),
+            })
+            .collect())
+    }
+
+    async fn release_outbox_messages(&self, outbox_ids: &[i64]) -> Result<()> {
+        sqlx::query("UPDATE outbox SET next_attempt_at = unixepoch() WHERE outbox_id = ANY($1)")
+            .bind(outbox_ids)
+            .execute(&self.pool)
+            .await
+            .context("Failed to release outbox messages")?;
+
+        Ok(())
+    }
+
+    async fn get_next_outbox_due_in(&self) -> Result<Option<i64>> {
+        // Unlike SQLite's MAX, GREATEST ignores NULLs so an empty table must be checked for.
+        sqlx::query_scalar(
+            "SELECT CASE WHEN COUNT(*) = 0 THEN NULL ELSE GREATEST(MIN(next_attempt_at) - unixepoch(), 0) END FROM outbox"
+        )
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to query next outbox attempt")
+    }
+
+    async fn update_outbox_attempt(
+        &self,
+        outbox_id: i64,
+        error_message: &str,
+        consumed: bool,
+        delay: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET attempts = attempts + $1, last_error = $2, next_attempt_at = unixepoch() + $3 WHERE outbox_id = $4"
+        )
+            .bind(i32::from(consumed))
+            .bind(error_message)
+            .bind(delay as i64)
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update outbox attempt")?;
+
+        Ok(())
+    }
+
+    async fn set_outbox_partial_send(
+        &self,
+        outbox_id: i64,
+        modem_id: &str,
+        message_id: Option<i64>,
+        encrypted_pdus: String,
+        references: String,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET partial_modem_id = $1, partial_message_id = $2, partial_pdus = $3, partial_references = $4 WHERE outbox_id = $5"
+        )
+            .bind(modem_id)
+            .bind(message_id)
+            .bind(encrypted_pdus)
+            .bind(references)
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update outbox partial send")?;
+
+        Ok(())
+    }
+
+    async fn delete_outbox_message(&self, outbox_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM outbox WHERE outbox_id = $1")
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete outbox message")?;
+
+        Ok(())
+    }
+
+    async fn insert_scheduled_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+        send_at: u32,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO scheduled_messages (phone_number, message_content, flash, validity_period, timeout, send_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING scheduled_id"
+        )
+            .bind(&message.to)
+            .bind(encrypted_content)
+            .bind(message.flash.unwrap_or(false))
+            .bind(message.validity_period.map(i16::from))
+            .bind(message.timeout.map(i64::from))
+            .bind(i64::from(send_at))
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to insert scheduled message")
+    }
+
+    async fn get_scheduled_messages(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsScheduledMessage>> {
+        let query = build_pagination_query(
+            "SELECT scheduled_id, phone_number, message_content, flash, validity_period, send_at, created_at FROM scheduled_messages",
+            "send_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query scheduled messages")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| SmsScheduledMessage {
+                scheduled_id: row.get("scheduled_id"),
+                phone_number: row.get("phone_number"),
+                message_content: row.get("message_content"),
+                flash: row.get("flash"),
+                validity_period: row
+                    .get::<Option<i16>, _>("validity_period")
+                    .map(|validity_period| validity_period as u8),
+                send_at: row.get::<i64, _>("send_at") as u32,
+                created_at: Some(row.get::<i64, _>("created_at") as u32),
+            })
+            .collect())
+    }
+
+    async fn cancel_scheduled_message(&self, scheduled_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM scheduled_messages WHERE scheduled_id = $1")
+            .bind(scheduled_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to cancel scheduled message")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reschedule_scheduled_message(&self, scheduled_id: i64, send_at: u32) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE scheduled_messages SET send_at = $1 WHERE scheduled_id = $2")
+                .bind(i64::from(send_at))
+                .bind(scheduled_id)
+                .execute(&self.pool)
+                .await
+                .context("Failed to reschedule message")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn release_due_scheduled_messages(&self) -> Result<u64> {
+        // Moved in one statement, skipping rows another server is already releasing.
+        let result = sqlx::query(
+            "WITH released AS (DELETE FROM scheduled_messages WHERE scheduled_id IN (SELECT scheduled_id FROM scheduled_messages WHERE send_at <= unixepoch() FOR UPDATE SKIP LOCKED) RETURNING phone_number, message_content, flash, validity_period, timeout, scheduled_id, send_at) INSERT INTO outbox (phone_number, message_content, flash, validity_period, timeout, scheduled_id) SELECT phone_number, message_content, flash, validity_period, timeout, scheduled_id FROM released ORDER BY send_at ASC"
+        )
+            .execute(&self.pool)
+            .await
+            .context("Failed to move due scheduled messages into outbox")?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_next_scheduled_due_in(&self) -> Result<Option<i64>> {
+        sqlx::query_scalar(
+            "SELECT CASE WHEN COUNT(*) = 0 THEN NULL ELSE GREATEST(MIN(send_at) - unixepoch(), 0) END FROM scheduled_messages"
+        )
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to query next scheduled message")
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO webhook_deliveries (url, payload, attempts, last_error, next_attempt_at) VALUES ($1, $2, 1, $3, unixepoch() + $4) RETURNING delivery_id"
+        )
+            .bind(url)
+            .bind(encrypted_payload)
+            .bind(error_message)
+            .bind(delay as i64)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to insert webhook delivery")
+    }
+
+    async fn get_due_webhook_deliveries(&self, limit: u32) -> Result<Vec<WebhookDelivery>> {
+        let result = sqlx::query(
+            "UPDATE webhook_deliveries SET next_attempt_at = unixepoch() + $2 WHERE delivery_id IN (SELECT delivery_id FROM webhook_deliveries WHERE next_attempt_at <= unixepoch() ORDER BY delivery_id ASC LIMIT $1 FOR UPDATE SKIP LOCKED) RETURNING delivery_id, url, payload, attempts"
+        )
+            .bind(i64::from(limit))
+            .bind(CLAIM_LEASE)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to claim due webhook deliveries")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| WebhookDelivery {
+                delivery_id: row.get("delivery_id"),
+                url: row.get("url"),
+                payload: row.get("payload"),
+                attempts: row.get::<i32, _>("attempts") as u32,
+            })
+            .collect())
+    }
+
+    async fn update_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET attempts = attempts + 1, last_error = $1, next_attempt_at = unixepoch() + $2 WHERE delivery_id = $3"
+        )
+            .bind(error_message)
+            .bind(delay as i64)
+            .bind(delivery_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update webhook delivery")?;
+
+        Ok(())
+    }
+
+    async fn delete_webhook_delivery(&self, delivery_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM webhook_deliveries WHERE delivery_id = $1")
+            .bind(delivery_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete webhook delivery")?;
+
+        Ok(())
+    }
+
+    async fn dead_letter_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO webhook_dead_letters (url, payload, attempts, last_error) SELECT url, payload, $1, $2 FROM webhook_deliveries WHERE delivery_id = $3"
+        )
+            .bind(attempts as i32)
+            .bind(error_message)
+            .bind(delivery_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to insert webhook dead-letter")?;
+
+        sqlx::query("DELETE FROM webhook_deliveries WHERE delivery_id = $1")
+            .bind(delivery_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to delete dead-lettered webhook delivery")?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO webhook_dead_letters (url, payload, attempts, last_error) VALUES ($1, $2, $3, $4) RETURNING dead_letter_id"
+        )
+            .bind(url)
+            .bind(encrypted_payload)
+            .bind(attempts as i32)
+            .bind(error_message)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to insert webhook dead-letter")
+    }
+
+    async fn get_webhook_dead_letters(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<StoredDeadLetter>> {
+        let query = build_pagination_query(
+            "SELECT dead_letter_id, url, payload, attempts, last_error, created_at FROM webhook_dead_letters",
+            "dead_letter_id",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query webhook dead-letters")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| StoredDeadLetter {
+                dead_letter_id: row.get("dead_letter_id"),
+                url: row.get("url"),
+                payload: row.get("payload"),
+                attempts: row.get::<i32, _>("attempts") as u32,
+                last_error: row.get("last_error"),
+                created_at: row.get::<i64, _>("created_at") as u32,
+            })
+            .collect())
+    }
+
+    async fn replay_webhook_dead_letter(&self, dead_letter_id: i64) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (url, payload, attempts, last_error) SELECT url, payload, 0, last_error FROM webhook_dead_letters WHERE dead_letter_id = $1"
+        )
+            .bind(dead_letter_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to requeue webhook dead-letter")?;
+
+        sqlx::query("DELETE FROM webhook_dead_letters WHERE dead_letter_id = $1")
+            .bind(dead_letter_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to delete replayed webhook dead-letter")?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_untokenized_messages(
+        &self,
+        after_message_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>> {
+        sqlx::query_as(
+            "SELECT message_id, message_content FROM messages WHERE message_id > $1 AND NOT tokenized ORDER BY message_id ASC LIMIT $2"
+        )
+            .bind(after_message_id)
+            .bind(i64::from(limit))
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query messages without search tokens")
+    }
+
+    async fn insert_message_tokens(&self, messages: Vec<(i64, Vec<String>)>) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for (message_id, tokens) in messages {
+            insert_tokens(&mut transaction, message_id, &tokens).await?;
+            sqlx::query("UPDATE messages SET tokenized = TRUE WHERE message_id = $1")
+                .bind(message_id)
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to mark message as tokenized")?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_encrypted_rows(
+        &self,
+        (table, id_column, content_column): (&str, &str, &str),
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>> {
+        sqlx::query_as(&format!(
+            "SELECT {id_column}, {content_column} FROM {table} WHERE {id_column} > $1 AND {content_column} IS NOT NULL ORDER BY {id_column} ASC LIMIT $2"
+        ))
+            .bind(after_id)
+            .bind(i64::from(limit))
+            .fetch_all(&self.pool)
+            .await
+            .with_context(|| format!("Failed to query {table} for re-encryption"))
+    }
+
+    async fn update_encrypted_rows(
+        &self,
+        (table, id_column, content_column): (&str, &str, &str),
+        rows: Vec<ReencryptedRow>,
+    ) -> Result<()> {
+        let update_query =
+            format!("UPDATE {table} SET {content_column} = $1 WHERE {id_column} = $2");
+
+        let mut transaction = self.pool.begin().await?;
+        for row in rows {
+            sqlx::query(&update_query)
+                .bind(row.content)
+                .bind(row.id)
+                .execute(&mut *transaction)
+                .await
+                .with_context(|| format!("Failed to update {table} #{}", row.id))?;
+
+            if let Some(tokens) = row.tokens {
+                sqlx::query("DELETE FROM message_tokens WHERE message_id = $1")
+                    .bind(row.id)
+                    .execute(&mut *transaction)
+                    .await
+                    .context("Failed to delete message tokens")?;
+                insert_tokens(&mut transaction, row.id, &tokens).await?;
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1101 @@
+use crate::sms::migrations::{self, SCHEMA_VERSION_SQL, SQLITE_MIGRATIONS};
+use crate::sms::outbox::OutboxEntry;
+use crate::sms::storage::{
+    build_pagination_query, partial_send_from_columns, DeliveryReportCandidate, MultipartFragment,
+    ReencryptedRow, SMSStorage, StoredDeadLetter,
+};
+use crate::webhooks::WebhookDelivery;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use sms_types::modem::IncomingCall;
+use sms_types::sms::{
+    SmsDeliveryReport, SmsMessage, SmsMessagePart, SmsMultipartHeader, SmsOutgoingMessage,
+    SmsPartialDeliveryReport, SmsScheduledMessage,
+};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow, SqliteSynchronous,
+};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::log::{debug, info, warn};
+
+fn message_from_row(row: SqliteRow) -> SmsMessage {
+    SmsMessage {
+        message_id: row.get("message_id"),
+        phone_number: row.get("phone_number"),
+        message_content: row.get("message_content"),
+        message_reference: row.get("message_reference"),
+        is_outgoing: row.get("is_outgoing"),
+        created_at: row.get("created_at"),
+        completed_at: row.get("completed_at"),
+        status: Some(row.get::<u8, _>("status")),
+        modem_id: row.get("modem_id"),
+        incomplete: row.get("incomplete"),
+        sent_at: row.get("sent_at"),
+        raw_pdu: row.get("raw_pdu"),
+    }
+}
+
+async fn insert_tokens(
+    conn: &mut SqliteConnection,
+    message_id: i64,
+    tokens: &[String],
+) -> Result<()> {
+    for token in tokens {
+        sqlx::query("INSERT OR IGNORE INTO message_tokens (token, message_id) VALUES (?, ?)")
+            .bind(token)
+            .bind(message_id)
+            .execute(&mut *conn)
+            .await
+            .context("Failed to insert message token")?;
+    }
+    Ok(())
+}
+
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+impl SqliteStorage {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let connection_options = SqliteConnectOptions::new()
+            .filename(database_url)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(Duration::from_secs(30));
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(20)
+            .min_connections(5)
+            .acquire_timeout(Duration::from_secs(30))
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .test_before_acquire(true)
+            .after_connect(|conn, _meta| {
+                Box::pin(async move {
+                    // Optimise connection.
+                    sqlx::query("PRAGMA foreign_keys = ON")
+                        .execute(&mut *conn)
+                        .await?;
+                    sqlx::query("PRAGMA cache_size = -64000")
+                        .execute(&mut *conn)
+                        .await?; // 64MB Cache
+                    sqlx::query("PRAGMA temp_store = memory")
+                        .execute(&mut *conn)
+                        .await?;
+                    Ok(())
+                })
+            })
+            .connect_with(connection_options)
+            .await
+            .context("Failed to connect to database")?;
+
+        let storage = Self { pool };
+        storage.migrate(database_url).await?;
+        Ok(storage)
+    }
+
+    /// Apply all pending migrations in order, each in its own transaction. If the database already
+    /// has data, a backup is made first. Any failure aborts startup, leaving the backup to restore from.
+    async fn migrate(&self, database_url: &str) -> Result<()> {
+        let has_tables: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%')",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to query existing tables")?;
+
+        sqlx::query(SCHEMA_VERSION_SQL)
+            .execute(&self.pool)
+            .await
+            .context("Failed to create schema_version table")?;
+
+        let current: u32 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+                .fetch_one(&self.pool)
+                .await
+                .context("Failed to query schema version")?;
+
+        let pending = migrations::pending(SQLITE_MIGRATIONS, current)?;
+        if pending.is_empty() {
+            debug!("Database schema is up to date at version {current}");
+            return Ok(());
+        }
+
+        let backup = if has_tables {
+            Some(self.backup(database_url, current).await?)
+        } else {
+            None
+        };
+
+        for migration in pending {
+            info!(
+                "Applying database migration {} ({})",
+                migration.version, migration.name
+            );
+
+            let mut transaction = self.pool.begin().await?;
+            let result = async {
+                sqlx::raw_sql(migration.sql)
+                    .execute(&mut *transaction)
+                    .await?;
+                sqlx::query(
+                    "INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, unixepoch())",
+                )
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *transaction)
+                .await?;
+                anyhow::Ok(())
+            }
+            .await;
+
+            if let Err(e) = result {
+                if let Some(backup) = &backup {
+                    warn!("Database backup from before migrating is at {backup}");
+                }
+                return Err(e.context(format!(
+                    "Failed to apply migration {} ({})",
+                    migration.version, migration.name
+                )));
+            }
+            transaction.commit().await?;
+        }
+
+        let latest = pending
+            .last()
+            .map_or(current, |migration| migration.version);
+        info!("Database schema migrated from version {current} to {latest}");
+        Ok(())
+    }
+
+    /// Copy the database alongside the original with VACUUM INTO, returning the backup path.
+    async fn backup(&self, database_url: &str, version: u32) -> Result<String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let path = format!("{database_url}.v{version}-{timestamp}.bak");
+        if Path::new(&path).exists() {
+            bail!("Database backup {path} already exists");
+        }
+
+        sqlx::query("VACUUM INTO ?")
+            .bind(&path)
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("Failed to backup database to {path} before migrating"))?;
+
+        info!("Backed up database to {path} before migrating");
+        Ok(path)
+    }
+}
+
+#[async_trait]
+impl SMSStorage for SqliteStorage {
+    async fn insert_message(
+        &self,
+        message: &SmsMessage,
+        encrypted_content: String,
+        encrypted_raw_pdu: Option<String>,
+        tokens: Vec<String>,
+        is_final: bool,
+    ) -> Result<i64> {
+        let mut transaction = self.pool.begin().await?;
+        let result = if is_final {
+            sqlx::query(
+                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status, modem_id, incomplete, sent_at, raw_pdu, tokenized, completed_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, unixepoch())"
+            )
+        } else {
+            sqlx::query(
+                "INSERT INTO messages (phone_number, message_content, message_reference, is_outgoing, status, modem_id, incomplete, sent_at, raw_pdu, tokenized) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1)"
+            )
+        }
+            .bind(&message.phone_number)
+            .bind(encrypted_content)
+            .bind(message.message_reference)
+            .bind(message.is_outgoing)
+            .bind(message.status)
+            .bind(&message.modem_id)
+            .bind(message.incomplete)
+            .bind(message.sent_at)
+            .bind(encrypted_raw_pdu)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to insert SmsMessage")?;
+
+        let message_id = result.last_insert_rowid();
+        insert_tokens(&mut transaction, message_id, &tokens).await?;
+
+        transaction.commit().await?;
+        Ok(message_id)
+    }
+
+    async fn insert_send_failure(&self, message_id: i64, error_message: &str) -> Result<i64> {
+        let mut transaction = self.pool.begin().await?;
+        let result =
+            sqlx::query("INSERT INTO send_failures (message_id, error_message) VALUES (?, ?)")
+                .bind(message_id)
+                .bind(error_message)
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to insert send failure")?;
+
+        sqlx::query("UPDATE messages SET completed_at = unixepoch() WHERE message_id = ? AND completed_at IS NULL")
+            .bind(message_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to complete failed message")?;
+
+        transaction.commit().await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn insert_delivery_report(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        report: &SmsPartialDeliveryReport,
+        is_final: bool,
+        ambiguous: bool,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO delivery_reports (message_id, part_index, status, is_final, submitted_at, discharged_at, ambiguous) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(message_id)
+        .bind(part_index)
+        .bind(report.status)
+        .bind(is_final)
+        .bind(report.submitted_at)
+        .bind(report.discharged_at)
+        .bind(ambiguous)
+        .execute(&self.pool)
+        .await
+        .context("Failed to insert delivery report")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn insert_message_parts(
+        &self,
+        message_id: i64,
+        references: &[u8],
+        stored: usize,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for (idx, reference) in references.iter().enumerate().skip(stored) {
+            sqlx::query(
+                "INSERT INTO message_parts (message_id, part_index, message_reference, sent_at) VALUES (?, ?, ?, unixepoch())",
+            )
+            .bind(message_id)
+            .bind(idx as i64 + 1)
+            .bind(reference)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to insert message part")?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_delivery_report_candidates(
+        &self,
+        phone_number: &str,
+        reference_id: u8,
+        modem_id: &str,
+    ) -> Result<Vec<DeliveryReportCandidate>> {
+        let result = sqlx::query(
+            "SELECT p.message_id, p.part_index, COALESCE(p.sent_at, m.created_at) AS sent_at FROM message_parts p JOIN messages m ON m.message_id = p.message_id WHERE p.completed_at IS NULL AND p.message_reference = ? AND m.is_outgoing = 1 AND m.phone_number = ? AND (m.modem_id IS NULL OR m.modem_id = ?) ORDER BY p.message_id DESC"
+        )
+            .bind(reference_id)
+            .bind(phone_number)
+            .bind(modem_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query appropriate target messages for delivery report")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| DeliveryReportCandidate {
+                message_id: row.get("message_id"),
+                part_index: row.get("part_index"),
+                sent_at: row.get("sent_at"),
+            })
+            .collect())
+    }
+
+    async fn update_message_part_status(
+        &self,
+        message_id: i64,
+        part_index: u8,
+        status: u8,
+        completed: bool,
+    ) -> Result<()> {
+        let query = if completed {
+            sqlx::query(
+                "UPDATE message_parts SET status = ?, completed_at = unixepoch() WHERE message_id = ? AND part_index = ?",
+            )
+        } else {
+            sqlx::query(
+                "UPDATE message_parts SET status = ? WHERE message_id = ? AND part_index = ?",
+            )
+        };
+
+        query
+            .bind(status)
+            .bind(message_id)
+            .bind(part_index)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update message part status")?;
+
+        Ok(())
+    }
+
+    async fn get_message_parts(&self, message_id: i64) -> Result<Vec<SmsMessagePart>> {
+        sqlx::query_as(
+            "SELECT message_id, part_index, message_reference, status, completed_at FROM message_parts WHERE message_id = ? ORDER BY part_index ASC",
+        )
+        .bind(message_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query message parts")
+    }
+
+    async fn update_message_status(
+        &self,
+        message_id: i64,
+        status: u8,
+        completed: bool,
+    ) -> Result<()> {
+        let query = if completed {
+            sqlx::query(
+                "UPDATE messages SET status = ?, completed_at = unixepoch() WHERE message_id = ?",
+            )
+        } else {
+            sqlx::query("UPDATE messages SET status = ? WHERE message_id = ?")
+        };
+
+        query
+            .bind(status)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update message status")?;
+
+        Ok(())
+    }
+
+    async fn insert_multipart_fragment(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        header: &SmsMultipartHeader,
+        encrypted_content: String,
+        sent_at: Option<u32>,
+        encrypted_raw_pdu: Option<String>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO multipart_fragments (modem_id, phone_number, message_reference, part_index, total_parts, message_content, sent_at, raw_pdu) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+            .bind(modem_id)
+            .bind(phone_number)
+            .bind(header.message_reference)
+            .bind(header.index)
+            .bind(header.total)
+            .bind(encrypted_content)
+            .bind(sent_at)
+            .bind(encrypted_raw_pdu)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert multipart fragment")?;
+
+        Ok(())
+    }
+
+    async fn get_multipart_fragments(&self) -> Result<Vec<MultipartFragment>> {
+        let result = sqlx::query(
+            "SELECT modem_id, phone_number, message_reference, part_index, total_parts, message_content, sent_at, raw_pdu, created_at FROM multipart_fragments ORDER BY fragment_id ASC"
+        )
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query multipart fragments")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| MultipartFragment {
+                modem_id: row.get("modem_id"),
+                phone_number: row.get("phone_number"),
+                header: SmsMultipartHeader {
+                    message_reference: row.get("message_reference"),
+                    total: row.get("total_parts"),
+                    index: row.get("part_index"),
+                },
+                content: row.get("message_content"),
+                sent_at: row.get("sent_at"),
+                raw_pdu: row.get("raw_pdu"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    async fn delete_multipart_fragments(
+        &self,
+        modem_id: &str,
+        phone_number: &str,
+        message_reference: u16,
+    ) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM multipart_fragments WHERE modem_id = ? AND phone_number = ? AND message_reference = ?"
+        )
+            .bind(modem_id)
+            .bind(phone_number)
+            .bind(message_reference)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete multipart fragments")?;
+
+        Ok(())
+    }
+
+    async fn update_friendly_name(
+        &self,
+        phone_number: &str,
+        friendly_name: Option<&str>,
+    ) -> Result<()> {
+        match friendly_name {
+            Some(name) => {
+                sqlx::query(
+                    "INSERT INTO friendly_names (phone_number, friendly_name) VALUES (?, ?) ON CONFLICT(phone_number) DO UPDATE SET friendly_name = excluded.friendly_name"
+                )
+                    .bind(phone_number)
+                    .bind(name)
+                    .execute(&self.pool)
+                    .await
+                    .context("Failed to insert friendly name")?;
+            }
+            None => {
+                sqlx::query("DELETE FROM friendly_names WHERE phone_number = ?")
+                    .bind(phone_number)
+                    .execute(&self.pool)
+                    .await
+                    .context("Failed to delete friendly name")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_friendly_name(&self, phone_number: &str) -> Result<Option<String>> {
+        sqlx::query_scalar("SELECT friendly_name FROM friendly_names WHERE phone_number = ?")
+            .bind(phone_number)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query friendly name")
+    }
+
+    async fn get_latest_numbers(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<(String, Option<String>)>> {
+        let query = build_pagination_query(
+            "SELECT m.phone_number, f.friendly_name FROM messages m LEFT JOIN friendly_names f ON f.phone_number = m.phone_number GROUP BY m.phone_number",
+            "MAX(m.created_at)",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result: Vec<(String, Option<String>)> = sqlx::query_as(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query latest numbers")?;
+
+        Ok(result)
+    }
+
+    async fn get_messages(
+        &self,
+        phone_number: &str,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>> {
+        let query = build_pagination_query(
+            "SELECT message_id, phone_number, message_content, message_reference, is_outgoing, status, created_at, completed_at, modem_id, incomplete, sent_at, raw_pdu FROM messages WHERE phone_number = ?",
+            "created_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .bind(phone_number)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query SmsMessage's")?;
+
+        Ok(result.into_iter().map(message_from_row).collect())
+    }
+
+    async fn search_messages(
+        &self,
+        tokens: &[String],
+        phone_number: Option<&str>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsMessage>> {
+        let placeholders = vec!["?"; tokens.len()].join(", ");
+        let mut base_query = format!(
+            "SELECT message_id, phone_number, message_content, message_reference, is_outgoing, status, created_at, completed_at, modem_id, incomplete, sent_at, raw_pdu FROM messages WHERE message_id IN (SELECT message_id FROM message_tokens WHERE token IN ({placeholders}) GROUP BY message_id HAVING COUNT(*) = ?)"
+        );
+        if phone_number.is_some() {
+            base_query.push_str(" AND phone_number = ?");
+        }
+        let query = build_pagination_query(&base_query, "created_at", limit, offset, reverse);
+
+        let mut sql_query = sqlx::query(&query);
+        for token in tokens {
+            sql_query = sql_query.bind(token);
+        }
+        sql_query = sql_query.bind(tokens.len() as i64);
+        if let Some(phone_number) = phone_number {
+            sql_query = sql_query.bind(phone_number);
+        }
+
+        let result = sql_query
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to search SmsMessage's")?;
+
+        Ok(result.into_iter().map(message_from_row).collect())
+    }
+
+    async fn get_delivery_reports(
+        &self,
+        message_id: i64,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsDeliveryReport>> {
+        let query = build_pagination_query(
+            "SELECT report_id, message_id, part_index, status, is_final, submitted_at, discharged_at, ambiguous, created_at FROM delivery_reports WHERE message_id = ?",
+            "created_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        sqlx::query_as(&query)
+            .bind(message_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query delivery reports")
+    }
+
+    async fn insert_call(
+        &self,
+        modem_id: &str,
+        phone_number: Option<&str>,
+        rejected: bool,
+    ) -> Result<i64> {
+        let result =
+            sqlx::query("INSERT INTO calls (modem_id, phone_number, rejected) VALUES (?, ?, ?)")
+                .bind(modem_id)
+                .bind(phone_number)
+                .bind(rejected)
+                .execute(&self.pool)
+                .await
+                .context("Failed to insert call")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_calls(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<IncomingCall>> {
+        let query = build_pagination_query(
+            "SELECT call_id, modem_id, phone_number, rejected, created_at FROM calls",
+            "created_at",
+            limit,
+            offset,
+            reverse,
+        );
+
+        sqlx::query_as(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query calls")
+    }
+
+    async fn insert_outbox_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO outbox (phone_number, message_content, flash, validity_period, timeout) VALUES (?, ?, ?, ?, ?)"
+        )
+            .bind(&message.to)
+            .bind(encrypted_content)
+            .bind(message.flash.unwrap_or(false))
+            .bind(message.validity_period)
+            .bind(message.timeout)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert outbox message")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_due_outbox_messages(&self, limit: u32) -> Result<Vec<OutboxEntry>> {
+        let result = sqlx::query(
+            "SELECT outbox_id, phone_number, message_content, flash, validity_period, timeout, attempts, scheduled_id, partial_modem_id, partial_message_id, partial_pdus, partial_references FROM outbox WHERE next_attempt_at <= unixepoch() ORDER BY outbox_id ASC LIMIT ?"
+        )
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query due outbox messages")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| OutboxEntry {
+                outbox_id: row.get("outbox_id"),
+                message: SmsOutgoingMessage {
+                    to: row.get("phone_number"),
+                    content: row.get("message_content"),
+                    flash: Some(row.get("flash")),
+                    validity_period: row.get("validity_period"),
+                    timeout: row.get("timeout"),
+                },
+                attempts: row.get("attempts"),
+                scheduled_id: row.get("scheduled_id"),
+                partial: partial_send_from_columns(
+                    row.get("partial_modem_id"),
+                    row.get("partial_message_id"),
+                    row.get("partial_pdus"),
+                    row.get("partial_references"),
+                ),
+            })
+            .collect())
+    }
+
+    async fn release_outbox_messages(&self, _outbox_ids: &[i64]) -> Result<()> {
+        // An SQLite database belongs to a single server, so due messages are never claimed.
+        Ok(())
+    }
+
+    async fn get_next_outbox_due_in(&self) -> Result<Option<i64>> {
+        sqlx::query_scalar("SELECT MAX(MIN(next_attempt_at) - unixepoch(), 0) FROM outbox")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to query next outbox attempt")
+    }
+
+    async fn update_outbox_attempt(
+        &self,
+        outbox_id: i64,
+        error_message: &str,
+        consumed: bool,
+        delay: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET attempts = attempts + ?, last_error = ?, next_attempt_at = unixepoch() + ? WHERE outbox_id = ?"
+        )
+            .bind(u32::from(consumed))
+            .bind(error_message)
+            .bind(delay as i64)
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update outbox attempt")?;
+
+        Ok(())
+    }
+
+    async fn set_outbox_partial_send(
+        &self,
+        outbox_id: i64,
+        modem_id: &str,
+        message_id: Option<i64>,
+        encrypted_pdus: String,
+        references: String,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET partial_modem_id = ?, partial_message_id = ?, partial_pdus = ?, partial_references = ? WHERE outbox_id = ?"
+        )
+            .bind(modem_id)
+            .bind(message_id)
+            .bind(encrypted_pdus)
+            .bind(references)
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update outbox partial send")?;
+
+        Ok(())
+    }
+
+    async fn delete_outbox_message(&self, outbox_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM outbox WHERE outbox_id = ?")
+            .bind(outbox_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete outbox message")?;
+
+        Ok(())
+    }
+
+    async fn insert_scheduled_message(
+        &self,
+        message: &SmsOutgoingMessage,
+        encrypted_content: String,
+        send_at: u32,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO scheduled_messages (phone_number, message_content, flash, validity_period, timeout, send_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+            .bind(&message.to)
+            .bind(encrypted_content)
+            .bind(message.flash.unwrap_or(false))
+            .bind(message.validity_period)
+            .bind(message.timeout)
+            .bind(send_at)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert scheduled message")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_scheduled_messages(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<SmsScheduledMessage>> {
+        let query = build_pagination_query(
+            "SELECT scheduled_id, phone_number, message_content, flash, validity_period, send_at, created_at FROM scheduled_messages",
+            "send_at",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query scheduled messages")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| SmsScheduledMessage {
+                scheduled_id: row.get("scheduled_id"),
+                phone_number: row.get("phone_number"),
+                message_content: row.get("message_content"),
+                flash: row.get("flash"),
+                validity_period: row.get("validity_period"),
+                send_at: row.get("send_at"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    async fn cancel_scheduled_message(&self, scheduled_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM scheduled_messages WHERE scheduled_id = ?")
+            .bind(scheduled_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to cancel scheduled message")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reschedule_scheduled_message(&self, scheduled_id: i64, send_at: u32) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE scheduled_messages SET send_at = ? WHERE scheduled_id = ?")
+                .bind(send_at)
+                .bind(scheduled_id)
+                .execute(&self.pool)
+                .await
+                .context("Failed to reschedule message")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn release_due_scheduled_messages(&self) -> Result<u64> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO outbox (phone_number, message_content, flash, validity_period, timeout, scheduled_id) SELECT phone_number, message_content, flash, validity_period, timeout, scheduled_id FROM scheduled_messages WHERE send_at <= unixepoch() ORDER BY send_at ASC"
+        )
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to move due scheduled messages into outbox")?;
+
+        // Only remove what was actually copied, in case the clock ticked over between statements.
+        sqlx::query("DELETE FROM scheduled_messages WHERE scheduled_id IN (SELECT scheduled_id FROM outbox)")
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to remove released scheduled messages")?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn get_next_scheduled_due_in(&self) -> Result<Option<i64>> {
+        sqlx::query_scalar("SELECT MAX(MIN(send_at) - unixepoch(), 0) FROM scheduled_messages")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to query next scheduled message")
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (url, payload, attempts, last_error, next_attempt_at) VALUES (?, ?, 1, ?, unixepoch() + ?)"
+        )
+            .bind(url)
+            .bind(encrypted_payload)
+            .bind(error_message)
+            .bind(delay as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert webhook delivery")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_due_webhook_deliveries(&self, limit: u32) -> Result<Vec<WebhookDelivery>> {
+        let result = sqlx::query(
+            "SELECT delivery_id, url, payload, attempts FROM webhook_deliveries WHERE next_attempt_at <= unixepoch() ORDER BY delivery_id ASC LIMIT ?"
+        )
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query due webhook deliveries")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| WebhookDelivery {
+                delivery_id: row.get("delivery_id"),
+                url: row.get("url"),
+                payload: row.get("payload"),
+                attempts: row.get("attempts"),
+            })
+            .collect())
+    }
+
+    async fn update_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        error_message: &str,
+        delay: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET attempts = attempts + 1, last_error = ?, next_attempt_at = unixepoch() + ? WHERE delivery_id = ?"
+        )
+            .bind(error_message)
+            .bind(delay as i64)
+            .bind(delivery_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update webhook delivery")?;
+
+        Ok(())
+    }
+
+    async fn delete_webhook_delivery(&self, delivery_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM webhook_deliveries WHERE delivery_id = ?")
+            .bind(delivery_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete webhook delivery")?;
+
+        Ok(())
+    }
+
+    async fn dead_letter_webhook_delivery(
+        &self,
+        delivery_id: i64,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO webhook_dead_letters (url, payload, attempts, last_error) SELECT url, payload, ?, ? FROM webhook_deliveries WHERE delivery_id = ?"
+        )
+            .bind(attempts)
+            .bind(error_message)
+            .bind(delivery_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to insert webhook dead-letter")?;
+
+        sqlx::query("DELETE FROM webhook_deliveries WHERE delivery_id = ?")
+            .bind(delivery_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to delete dead-lettered webhook delivery")?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        url: &str,
+        encrypted_payload: String,
+        attempts: u32,
+        error_message: &str,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO webhook_dead_letters (url, payload, attempts, last_error) VALUES (?, ?, ?, ?)"
+        )
+            .bind(url)
+            .bind(encrypted_payload)
+            .bind(attempts)
+            .bind(error_message)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert webhook dead-letter")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_webhook_dead_letters(
+        &self,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        reverse: bool,
+    ) -> Result<Vec<StoredDeadLetter>> {
+        let query = build_pagination_query(
+            "SELECT dead_letter_id, url, payload, attempts, last_error, created_at FROM webhook_dead_letters",
+            "dead_letter_id",
+            limit,
+            offset,
+            reverse
+        );
+
+        let result = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query webhook dead-letters")?;
+
+        Ok(result
+            .into_iter()
+            .map(|row| StoredDeadLetter {
+                dead_letter_id: row.get("dead_letter_id"),
+                url: row.get("url"),
+                payload: row.get("payload"),
+                attempts: row.get("attempts"),
+                last_error: row.get("last_error"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    async fn replay_webhook_dead_letter(&self, dead_letter_id: i64) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (url, payload, attempts, last_error) SELECT url, payload, 0, last_error FROM webhook_dead_letters WHERE dead_letter_id = ?"
+        )
+            .bind(dead_letter_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to requeue webhook dead-letter")?;
+
+        sqlx::query("DELETE FROM webhook_dead_letters WHERE dead_letter_id = ?")
+            .bind(dead_letter_id)
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to delete replayed webhook dead-letter")?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_untokenized_messages(
+        &self,
+        after_message_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>> {
+        sqlx::query_as(
+            "SELECT message_id, message_content FROM messages WHERE message_id > ? AND NOT tokenized ORDER BY message_id ASC LIMIT ?"
+        )
+            .bind(after_message_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query messages without search tokens")
+    }
+
+    async fn insert_message_tokens(&self, messages: Vec<(i64, Vec<String>)>) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for (message_id, tokens) in messages {
+            insert_tokens(&mut transaction, message_id, &tokens).await?;
+            sqlx::query("UPDATE messages SET tokenized = 1 WHERE message_id = ?")
+                .bind(message_id)
+                .execute(&mut *transaction)
+                .await
+                .context("Failed to mark message as tokenized")?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_encrypted_rows(
+        &self,
+        (table, id_column, content_column): (&str, &str, &str),
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>> {
+        sqlx::query_as(&format!(
+            "SELECT {id_column}, {content_column} FROM {table} WHERE {id_column} > ? AND {content_column} IS NOT NULL ORDER BY {id_column} ASC LIMIT ?"
+        ))
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .with_context(|| format!("Failed to query {table} for re-encryption"))
+    }
+
+    async fn update_encrypted_rows(
+        &self,
+        (table, id_column, content_column): (&str, &str, &str),
+        rows: Vec<ReencryptedRow>,
+    ) -> Result<()> {
+        let update_query = format!("UPDATE {table} SET {content_column} = ? WHERE {id_column} = ?");
+
+        let mut transaction = self.pool.begin().await?;
+        for row in rows {
+            sqlx::query(&update_query)
+                .bind(row.content)
+                .bind(row.id)
+                .execute(&mut *transaction)
+                .await
+                .with_context(|| format!("Failed to update {table} #{}", row.id))?;
+
+            if let Some(tokens) = row.tokens {
+                sqlx::query("DELETE FROM message_tokens WHERE message_id = ?")
+                    .bind(row.id)
+                    .execute(&mut *transaction)
+                    .await
+                    .context("Failed to delete message tokens")?;
+                insert_tokens(&mut transaction, row.id, &tokens).await?;
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
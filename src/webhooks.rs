@@ -1,19 +1,52 @@
+use crate::backoff::backoff;
 use crate::config::ConfiguredWebhook;
+use crate::sms::SMSDatabase;
 use anyhow::{Context, Result};
 use futures::{stream, StreamExt};
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
 use reqwest::Client;
+use serde::Serialize;
 use sms_types::events::{Event, EventKind};
+use sms_types::webhook::{sign, SIGNATURE_HEADER};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tokio::time::interval;
 use tracing::log::{debug, error, info, warn};
 
 const CONCURRENCY_LIMIT: usize = 10;
 const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const RETRY_BATCH_SIZE: u32 = 50;
+const RETRY_MAX_DELAY: u64 = 60 * 60; // 1 hour
+
+/// A failed webhook delivery waiting to be retried, with the decrypted event payload.
+#[derive(Debug)]
+pub struct WebhookDelivery {
+    pub delivery_id: i64,
+    pub url: String,
+    pub payload: String,
+    pub attempts: u32,
+}
+
+/// A webhook delivery that ran out of attempts, and can be replayed.
+#[cfg_attr(not(feature = "http-server"), allow(dead_code))]
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookDeadLetter {
+    pub dead_letter_id: i64,
+    pub url: String,
+
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub event: serde_json::Value,
+
+    pub attempts: u32,
+    pub last_error: String,
+    pub created_at: u32,
+}
 
 fn client_builder(webhooks: &[ConfiguredWebhook]) -> Result<reqwest::ClientBuilder> {
     let builder = Client::builder();
@@ -90,12 +123,15 @@ pub struct WebhookSender {
     event_sender: mpsc::UnboundedSender<Event>,
 }
 impl WebhookSender {
-    pub fn new(webhooks: Vec<ConfiguredWebhook>) -> (Self, JoinHandle<()>) {
+    pub fn new(
+        webhooks: Vec<ConfiguredWebhook>,
+        database: Arc<SMSDatabase>,
+    ) -> (Self, JoinHandle<()>) {
         // Use an unbounded channel to ensure no webhooks are ever dropped.
         // The modem command channel is bound, so we should be fine from API spam.
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
         let handle = tokio::spawn(async move {
-            let worker = WebhookWorker::new(webhooks, event_receiver);
+            let worker = WebhookWorker::new(webhooks, database, event_receiver);
             worker.run().await;
         });
 
@@ -117,10 +153,12 @@ struct WebhookWorker {
     events_map: HashMap<EventKind, Vec<usize>>,
     event_receiver: mpsc::UnboundedReceiver<Event>,
     client: Client,
+    database: Arc<SMSDatabase>,
 }
 impl WebhookWorker {
     fn new(
         webhooks: Vec<ConfiguredWebhook>,
+        database: Arc<SMSDatabase>,
         event_receiver: mpsc::UnboundedReceiver<Event>,
     ) -> Self {
         let mut events_map: HashMap<EventKind, Vec<usize>> = HashMap::new();
@@ -155,13 +193,21 @@ impl WebhookWorker {
             events_map,
             event_receiver,
             client,
+            database,
         }
     }
 
     async fn run(mut self) {
         info!("Starting webhook worker");
-        while let Some(event) = self.event_receiver.recv().await {
-            self.process(event).await;
+        let mut retry_interval = interval(RETRY_POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                event = self.event_receiver.recv() => match event {
+                    Some(event) => self.process(event).await,
+                    None => break,
+                },
+                _ = retry_interval.tick() => self.retry_due().await
+            }
         }
     }
 
@@ -171,24 +217,32 @@ impl WebhookWorker {
             None => return,
         };
 
-        let event = Arc::new(event);
+        // Serialize once, the same payload is stored if any deliveries need retrying.
+        let payload = match serde_json::to_string(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize webhook event: {e}");
+                return;
+            }
+        };
         let webhooks = Arc::clone(&self.webhooks);
 
         stream::iter(webhook_indices.into_iter().enumerate())
             .map(|(task_idx, webhook_idx)| {
                 let webhook = &webhooks[webhook_idx];
-                let event = Arc::clone(&event);
-                let client = &self.client;
+                let payload = &payload;
 
-                // TODO: Maybe re-queue failed webhooks?
                 async move {
-                    match Self::execute_webhook(webhook, client, &event).await {
+                    match Self::execute_webhook(webhook, &self.client, payload).await {
                         Ok(()) => debug!(
                             "Webhook #{webhook_idx} for task #{task_idx} was sent successfully!"
                         ),
-                        Err(e) => warn!(
-                            "Failed to send Webhook #{webhook_idx} for task #{task_idx} with error: {e}"
-                        ),
+                        Err(e) => {
+                            warn!(
+                                "Failed to send Webhook #{webhook_idx} for task #{task_idx} with error: {e}"
+                            );
+                            self.store_failed(&webhook.0, payload, &e.to_string()).await;
+                        }
                     }
                 }
             })
@@ -197,17 +251,131 @@ impl WebhookWorker {
             .await;
     }
 
+    /// Persist a failed first attempt, to either be retried or dead-lettered straight away.
+    async fn store_failed(&self, webhook: &ConfiguredWebhook, payload: &str, error: &str) {
+        let result = if webhook.max_attempts <= 1 {
+            self.database
+                .insert_webhook_dead_letter(&webhook.url, payload, 1, error)
+                .await
+        } else {
+            self.database
+                .insert_webhook_delivery(
+                    &webhook.url,
+                    payload,
+                    error,
+                    backoff(webhook.retry_delay, RETRY_MAX_DELAY, 1),
+                )
+                .await
+        };
+
+        if let Err(e) = result {
+            error!(
+                "Failed to store failed webhook delivery for {}: {e:?}",
+                webhook.url
+            );
+        }
+    }
+
+    async fn retry_due(&self) {
+        let deliveries = match self
+            .database
+            .get_due_webhook_deliveries(RETRY_BATCH_SIZE)
+            .await
+        {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                error!("Failed to get due webhook deliveries: {e:?}");
+                return;
+            }
+        };
+        if deliveries.is_empty() {
+            return;
+        }
+
+        debug!("Retrying {} failed webhook deliveries", deliveries.len());
+        stream::iter(deliveries)
+            .map(|delivery| self.retry_delivery(delivery))
+            .buffer_unordered(CONCURRENCY_LIMIT)
+            .for_each(|_| async {})
+            .await;
+    }
+
+    async fn retry_delivery(&self, delivery: WebhookDelivery) {
+        let delivery_id = delivery.delivery_id;
+
+        // Deliveries are matched to webhooks by URL, so they survive config changes between restarts.
+        let Some(webhook) = self.webhooks.iter().find(|(w, _)| w.url == delivery.url) else {
+            warn!(
+                "Webhook delivery #{delivery_id} is for {} which is no longer configured!",
+                delivery.url
+            );
+            if let Err(e) = self
+                .database
+                .dead_letter_webhook_delivery(
+                    delivery_id,
+                    delivery.attempts,
+                    "Webhook is no longer configured",
+                )
+                .await
+            {
+                error!("Failed to dead-letter webhook delivery #{delivery_id}: {e:?}");
+            }
+            return;
+        };
+
+        let attempts = delivery.attempts + 1;
+        let result = match Self::execute_webhook(webhook, &self.client, &delivery.payload).await {
+            Ok(()) => {
+                debug!(
+                    "Webhook delivery #{delivery_id} was sent successfully on attempt {attempts}!"
+                );
+                self.database.delete_webhook_delivery(delivery_id).await
+            }
+            Err(e) if attempts >= webhook.0.max_attempts => {
+                warn!("Webhook delivery #{delivery_id} failed after {attempts} attempts, moving to dead-letters: {e}");
+                self.database
+                    .dead_letter_webhook_delivery(delivery_id, attempts, &e.to_string())
+                    .await
+            }
+            Err(e) => {
+                let delay = backoff(webhook.0.retry_delay, RETRY_MAX_DELAY, attempts);
+                debug!("Webhook delivery #{delivery_id} failed (attempt {attempts}), retrying in {delay}s: {e}");
+                self.database
+                    .update_webhook_delivery(delivery_id, &e.to_string(), delay)
+                    .await
+            }
+        };
+
+        if let Err(e) = result {
+            error!("Failed to update webhook delivery #{delivery_id}: {e:?}");
+        }
+    }
+
     async fn execute_webhook(
         (webhook, headers): &StoredWebhook,
         client: &Client,
-        event: &Event,
+        payload: &str,
     ) -> Result<()> {
-        let mut request = client.post(&webhook.url).json(event);
+        let mut request = client
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload.to_owned());
 
         if let Some(headers) = headers {
             request = request.headers(headers.clone());
         }
 
+        // Signed at send time, so retries have a fresh timestamp within the receiver's tolerance.
+        if let Some(secret) = &webhook.secret {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs());
+            request = request.header(
+                SIGNATURE_HEADER,
+                sign(secret.as_bytes(), timestamp, payload.as_bytes()),
+            );
+        }
+
         let status = request
             .send()
             .await